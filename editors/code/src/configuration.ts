@@ -28,6 +28,27 @@ class FmtConfig {
     }
 }
 
+class ProjectConfig {
+    dev_mode: boolean;
+    test_mode: boolean;
+    named_addresses: Record<string, string>;
+    language_version: string | null;
+    compiler_version: string | null;
+
+    constructor(
+        dev_mode: boolean,
+        test_mode: boolean,
+        named_addresses: Record<string, string>,
+        language_version: string | null,
+        compiler_version: string | null) {
+        this.dev_mode = dev_mode;
+        this.test_mode = test_mode;
+        this.named_addresses = named_addresses;
+        this.language_version = language_version;
+        this.compiler_version = compiler_version;
+    }
+}
+
 /**
  * User-defined configuration values, such as those specified in VS Code settings.
  *
@@ -86,6 +107,22 @@ class Configuration {
         const indent_size = this.configuration.get<number>('movefmt.indent_size')!;
         return new FmtConfig(enable, max_width, indent_size);
     }
+
+    project_config(): ProjectConfig {
+        const dev_mode = this.configuration.get<boolean>('project.dev_mode', false);
+        const test_mode = this.configuration.get<boolean>('project.test_mode', true);
+        const named_addresses = this.configuration.get<Record<string, string>>('project.named_addresses', {});
+        // An empty version means the compiler default.
+        const language_version = this.configuration.get<string>('project.language_version', '');
+        const compiler_version = this.configuration.get<string>('project.compiler_version', '');
+        return new ProjectConfig(
+            dev_mode,
+            test_mode,
+            named_addresses,
+            language_version.length > 0 ? language_version : null,
+            compiler_version.length > 0 ? compiler_version : null,
+        );
+    }
 }
 
-export { InlayHintsConfig, FmtConfig, Configuration };
+export { InlayHintsConfig, FmtConfig, ProjectConfig, Configuration };
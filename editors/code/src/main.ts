@@ -120,6 +120,7 @@ export async function activate(
       log.info(`new_configuration: ${new_configuration.toString()}`);
       void client.sendRequest('move/lsp/client/inlay_hints/config', new_configuration.inlay_hints_config());
       void client.sendRequest('move/lsp/movefmt/config', new_configuration.movefmt_config());
+      void client.sendRequest('move/lsp/project/config', new_configuration.project_config());
     }
   };
   reload_cfg();
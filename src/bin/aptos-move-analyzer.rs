@@ -4,28 +4,44 @@
 use anyhow::Result;
 use aptos_move_analyzer::{
     analyzer_handler::ConvertLoc,
-    completion,
-    context::{Context, FileDiags},
-    goto_definition, hover, inlay_hints,
+    code_action, completion,
+    context::{Context, DiagKind, FileDiags},
+    folding_range, goto_definition, hover,
+    indexer::{spawn_indexer, IndexEvent, IndexJob},
+    inlay_hints,
     inlay_hints::*,
-    move_generate_spec_file::on_generate_spec_file,
+    manifest_diagnostics::manifest_diagnostics,
+    move_coverage::{on_show_coverage, CoverageReport, CoverageSender, SHOW_COVERAGE_COMMAND},
+    move_generate_spec_file::{generate_module_specs, merge_module_specs, on_generate_spec_file},
     move_generate_spec_sel::on_generate_spec_sel,
+    move_unit_test::{
+        on_run_tests, test_failure_diagnostics, TestSender, UnitTestReport, RUN_TESTS_COMMAND,
+    },
     movefmt::*,
     multiproject::MultiProject,
-    references, symbols,
+    progress::ProgressReporter,
+    project::{build_dir_lock, ProjectConfig},
+    references, selection_range,
+    spec_diagnostics::{model_diagnostics, tag_sources},
+    symbols,
     utils::*,
 };
 use clap::Parser;
-use crossbeam::channel::{bounded, select, Sender};
+use crossbeam::channel::{bounded, select, unbounded, Sender};
 use log::{Level, Metadata, Record};
-use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
 use lsp_types::{
-    notification::Notification as _, request::Request as _, CompletionOptions,
-    HoverProviderCapability, OneOf, SaveOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
-    TextDocumentSyncOptions, WorkDoneProgressOptions,
+    notification::Notification as _, request::Request as _, CodeActionKind, CodeActionOptions,
+    CodeActionProviderCapability, CompletionOptions, ExecuteCommandOptions, ExecuteCommandParams,
+    FoldingRangeProviderCapability, HoverProviderCapability, OneOf, SaveOptions,
+    SelectionRangeProviderCapability, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, WorkDoneProgressOptions, WorkspaceFoldersServerCapabilities,
+    WorkspaceServerCapabilities,
 };
 use move_command_line_common::files::FileHash;
 use move_compiler::{diagnostics::Diagnostics, PASS_TYPING};
+use move_package::source_package::layout::SourcePackageLayout;
+use similar::TextDiff;
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
@@ -36,6 +52,8 @@ use url::Url;
 struct AnalyzerConfig {
     pub inlay_hints_config: InlayHintsConfig,
     pub movefmt_config: FmtConfig,
+    /// The client resolves the edits of the code actions with `codeAction/resolve`.
+    pub resolve_code_action_edits: bool,
 }
 
 impl Default for AnalyzerConfig {
@@ -43,6 +61,7 @@ impl Default for AnalyzerConfig {
         Self {
             inlay_hints_config: InlayHintsConfig::default(),
             movefmt_config: FmtConfig::default(),
+            resolve_code_action_edits: false,
         }
     }
 }
@@ -71,12 +90,47 @@ pub fn init_log() {
 
 #[derive(Parser)]
 #[clap(author, version, about)]
-struct Options {}
+struct Options {
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Generate the `.spec.move` files of a package, or of some of its Move files, without
+    /// starting the language server.
+    GenerateSpec(GenerateSpecArgs),
+}
+
+#[derive(clap::Args)]
+struct GenerateSpecArgs {
+    /// Package directories or Move files.
+    #[clap(default_value = ".")]
+    paths: Vec<PathBuf>,
+    /// Print the changes to the spec files as a unified diff instead of writing them.
+    #[clap(long)]
+    dry_run: bool,
+    /// Write nothing, exit with a nonzero status if specs are missing.
+    #[clap(long)]
+    check: bool,
+    /// Generate again the clauses marked as auto-generated in the existing specs.
+    #[clap(long)]
+    refresh: bool,
+}
 
 fn main() {
-    // For now, aptos-move-analyzer only responds to options built-in to clap,
-    // such as `--help` or `--version`.
-    Options::parse();
+    // Without a subcommand, aptos-move-analyzer runs the language server.
+    if let Some(Command::GenerateSpec(args)) = Options::parse().command {
+        let code = match generate_spec(&args) {
+            Ok(true) => 0,
+            Ok(false) => 1,
+            Err(err) => {
+                eprintln!("error: {:#}", err);
+                2
+            },
+        };
+        std::process::exit(code);
+    }
     init_log();
     // stdio is used to communicate Language Server Protocol requests and responses.
     // stderr is used for logging (and, when Visual Studio Code is used to communicate with this
@@ -97,10 +151,12 @@ fn main() {
         diag_version: FileDiags::new(),
     };
 
-    let (id, _client_response) = context
+    let (id, client_response) = context
         .connection
         .initialize_start()
         .expect("could not start connection initialization");
+    let client_params = serde_json::from_value::<lsp_types::InitializeParams>(client_response)
+        .expect("could not deserialize initialize params");
 
     let capabilities = serde_json::to_value(lsp_types::ServerCapabilities {
         // The server receives notifications from the client as users open, close,
@@ -119,7 +175,7 @@ fn main() {
                 ),
             },
         )),
-        selection_range_provider: None,
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
         completion_provider: Some(CompletionOptions {
             resolve_provider: None,
@@ -133,6 +189,31 @@ fn main() {
         definition_provider: Some(OneOf::Left(true)),
         references_provider: Some(OneOf::Left(true)),
         document_symbol_provider: Some(OneOf::Left(true)),
+        workspace_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![CodeActionKind::REFACTOR]),
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+            resolve_provider: Some(true),
+        })),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        }),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![
+                RUN_TESTS_COMMAND.to_string(),
+                SHOW_COVERAGE_COMMAND.to_string(),
+            ],
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: Some(true),
+            },
+        }),
         ..Default::default()
     })
     .expect("could not serialize server capabilities");
@@ -146,11 +227,73 @@ fn main() {
             }),
         )
         .expect("could not finish connection initialization");
+    let watch_files = client_params
+        .capabilities
+        .workspace
+        .as_ref()
+        .and_then(|x| x.did_change_watched_files.as_ref())
+        .and_then(|x| x.dynamic_registration)
+        .unwrap_or(false);
+    if watch_files {
+        register_watched_files(&context);
+    }
     let (diag_sender, diag_receiver) = bounded::<(PathBuf, Diagnostics)>(1);
     let diag_sender = Arc::new(Mutex::new(diag_sender));
-    let mut analyzer_cfg = AnalyzerConfig::default();
+    let (test_sender, test_receiver) = bounded::<UnitTestReport>(1);
+    let test_sender = Arc::new(Mutex::new(test_sender));
+    let (coverage_sender, coverage_receiver) = bounded::<CoverageReport>(1);
+    let coverage_sender = Arc::new(Mutex::new(coverage_sender));
+    let command_senders = CommandSenders {
+        test: test_sender,
+        coverage: coverage_sender,
+        diag: diag_sender.clone(),
+    };
+    let resolve_code_action_edits = client_params
+        .capabilities
+        .text_document
+        .as_ref()
+        .and_then(|x| x.code_action.as_ref())
+        .and_then(|x| x.resolve_support.as_ref())
+        .map_or(false, |x| x.properties.iter().any(|x| x == "edit"));
+    let mut analyzer_cfg = AnalyzerConfig {
+        resolve_code_action_edits,
+        ..Default::default()
+    };
+    #[allow(deprecated)]
+    let folders = match client_params.workspace_folders {
+        Some(x) => x.into_iter().map(|x| x.uri).collect(),
+        None => client_params.root_uri.into_iter().collect::<Vec<_>>(),
+    };
+    let (index_sender, index_receiver) = unbounded::<IndexEvent>();
+    let indexer = spawn_indexer(index_sender);
+    for folder in folders.iter() {
+        if let Ok(folder) = folder.to_file_path() {
+            let _ = indexer.send(IndexJob {
+                folder,
+                config: context.projects.config.clone(),
+            });
+        }
+    }
+    let mut indexing: Option<(ProgressReporter, usize, usize)> = None;
     loop {
+        // load the packages of the opened documents when there is nothing else to do.
+        if !context.projects.pending_manifests.is_empty()
+            && context.connection.receiver.is_empty()
+            && diag_receiver.is_empty()
+            && test_receiver.is_empty()
+            && coverage_receiver.is_empty()
+            && index_receiver.is_empty()
+        {
+            load_next_package(&mut context, diag_sender.clone());
+            continue;
+        }
         select! {
+            recv(index_receiver) -> message => {
+                match message {
+                    Ok(event) => on_index_event(&mut context, &mut indexing, event),
+                    Err(error) => log::error!("IDE index message error: {:?}", error),
+                }
+            },
             recv(diag_receiver) -> message => {
                 match message {
                     Ok ((mani ,x)) => {
@@ -159,10 +302,24 @@ fn main() {
                     Err(error) => log::error!("IDE diag message error: {:?}", error),
                 }
             },
+            recv(test_receiver) -> message => {
+                match message {
+                    Ok(report) => send_test_diag(&mut context, report),
+                    Err(error) => log::error!("IDE test message error: {:?}", error),
+                }
+            },
+            recv(coverage_receiver) -> message => {
+                match message {
+                    Ok(report) => send_coverage_diag(&mut context, report),
+                    Err(error) => log::error!("IDE coverage message error: {:?}", error),
+                }
+            },
             recv(context.connection.receiver) -> message => {
-                context.projects.try_reload_projects(&context.connection);
+                for mani in context.projects.try_reload_projects(&context.connection) {
+                    send_manifest_diag(&mut context, &mani);
+                }
                 match message {
-                    Ok(Message::Request(request)) => on_request(&mut context, &request , &mut analyzer_cfg),
+                    Ok(Message::Request(request)) => on_request(&mut context, &request , &mut analyzer_cfg, &command_senders),
                     Ok(Message::Response(response)) => on_response(&context, &response),
                     Ok(Message::Notification(notification)) => {
                         match notification.method.as_str() {
@@ -172,7 +329,7 @@ fn main() {
                                 // It ought to, especially once it begins processing requests that may
                                 // take a long time to respond to.
                             }
-                            _ => on_notification(&mut context, &notification, diag_sender.clone()),
+                            _ => on_notification(&mut context, &notification, diag_sender.clone(), &indexer),
                         }
                     }
                     Err(error) => log::error!("IDE message error: {:?}", error),
@@ -185,7 +342,103 @@ fn main() {
     log::error!("Shut down language server '{}'.", exe);
 }
 
-fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut AnalyzerConfig) {
+/// Generate the spec files of the Move files in `args.paths`, false when `--check` finds specs
+/// missing.
+fn generate_spec(args: &GenerateSpecArgs) -> Result<bool> {
+    let (connection, _client) = Connection::memory();
+    let mut projects = MultiProject::new();
+    let current_dir = std::env::current_dir()?;
+    let mut fpaths = vec![];
+    for path in args.paths.iter() {
+        let path = path_concat(current_dir.as_path(), path.as_path());
+        if path.is_dir() {
+            let sources = path.join(SourcePackageLayout::Sources.location_str());
+            let mut files: Vec<_> = walkdir::WalkDir::new(sources.as_path())
+                .into_iter()
+                .flatten()
+                .map(|x| x.into_path())
+                .filter(|x| x.is_file())
+                .collect();
+            files.sort();
+            fpaths.extend(files);
+        } else {
+            fpaths.push(path);
+        }
+    }
+    fpaths.retain(|x| {
+        let name = x.to_string_lossy();
+        name.ends_with(".move") && !name.ends_with(".spec.move")
+    });
+
+    let mut ok = true;
+    for fpath in fpaths.iter() {
+        if projects.get_project(fpath).is_none() {
+            let (mani, _) = discover_manifest_and_kind(fpath)
+                .ok_or_else(|| anyhow::anyhow!("{:?} is not in a Move package", fpath))?;
+            let p = projects.load_projects(&connection, &mani)?;
+            projects.insert_project(p);
+        }
+        let project = projects
+            .get_project(fpath)
+            .ok_or_else(|| anyhow::anyhow!("could not load the package of {:?}", fpath))?;
+        // the specs of a package which does not build are not worth checking.
+        if !project.err_diags.is_empty() {
+            anyhow::bail!(
+                "the package of {:?} does not build:\n{}",
+                fpath,
+                project.err_diags
+            );
+        }
+        // a module whose functions and structs all get empty specs has nothing to generate,
+        // its `spec module` block of default pragmas is skipped too.
+        let modules: Vec<_> = generate_module_specs(project, fpath)
+            .into_iter()
+            .filter_map(|mut x| {
+                x.specs.retain(|x| !x.is_empty());
+                if x.specs.iter().all(|x| x.name == "module") {
+                    None
+                } else {
+                    Some(x)
+                }
+            })
+            .collect();
+        let update = merge_module_specs(fpath, &modules, args.refresh)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        if !update.is_changed() {
+            continue;
+        }
+        let origin = update.origin.clone().unwrap_or_default();
+        if args.check {
+            println!("specs missing in {}", update.path.display());
+            ok = false;
+        } else if args.dry_run {
+            let old_header = match update.origin {
+                Some(_) => update.path.display().to_string(),
+                None => "/dev/null".to_string(),
+            };
+            print!(
+                "{}",
+                TextDiff::from_lines(origin.as_str(), update.content.as_str())
+                    .unified_diff()
+                    .header(
+                        old_header.as_str(),
+                        update.path.display().to_string().as_str()
+                    )
+            );
+        } else {
+            std::fs::write(update.path.as_path(), update.content.as_str())?;
+            println!("wrote {}", update.path.display());
+        }
+    }
+    Ok(ok)
+}
+
+fn on_request(
+    context: &mut Context,
+    request: &Request,
+    analyzer_cfg: &mut AnalyzerConfig,
+    command_senders: &CommandSenders,
+) {
     // log::info!("aptos receive method:{}", request.method.as_str());
     match request.method.as_str() {
         lsp_types::request::GotoDefinition::METHOD => {
@@ -206,9 +459,37 @@ fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut Analy
         lsp_types::request::DocumentSymbolRequest::METHOD => {
             symbols::on_document_symbol_request(context, request);
         },
+        lsp_types::request::WorkspaceSymbolRequest::METHOD => {
+            symbols::on_workspace_symbol_request(context, request);
+        },
+        lsp_types::request::FoldingRangeRequest::METHOD => {
+            folding_range::on_folding_range_request(context, request);
+        },
+        lsp_types::request::SelectionRangeRequest::METHOD => {
+            selection_range::on_selection_range_request(context, request);
+        },
+        lsp_types::request::CodeActionRequest::METHOD => {
+            code_action::on_code_action_request(
+                context,
+                request,
+                analyzer_cfg.resolve_code_action_edits,
+            );
+        },
+        lsp_types::request::CodeActionResolveRequest::METHOD => {
+            code_action::on_code_action_resolve_request(context, request);
+        },
         lsp_types::request::Formatting::METHOD => {
             on_movefmt_request(context, request, &analyzer_cfg.movefmt_config);
         },
+        lsp_types::request::RangeFormatting::METHOD => {
+            on_movefmt_range_request(context, request, &analyzer_cfg.movefmt_config);
+        },
+        lsp_types::request::OnTypeFormatting::METHOD => {
+            on_movefmt_on_type_request(context, request, &analyzer_cfg.movefmt_config);
+        },
+        lsp_types::request::ExecuteCommand::METHOD => {
+            on_execute_command(context, request, command_senders);
+        },
         "move/generate/spec/file" => {
             on_generate_spec_file(context, request, true);
         },
@@ -270,6 +551,20 @@ fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut Analy
                     .unwrap();
             }
         },
+        "move/lsp/project/config" => {
+            let parameters = serde_json::from_value::<ProjectConfig>(request.params.clone())
+                .expect("could not deserialize project config");
+            log::info!("call project config {:?}", parameters);
+            let reloaded = context.projects.set_config(&context.connection, parameters);
+            for mani in reloaded {
+                send_manifest_diag(context, &mani);
+                make_diag(
+                    context,
+                    command_senders.diag.clone(),
+                    mani.join(PROJECT_FILE_NAME),
+                );
+            }
+        },
         "move/lsp/movefmt/config" => {
             let parameters = serde_json::from_value::<FmtConfig>(request.params.clone())
                 .expect("could not deserialize movefmt config");
@@ -283,10 +578,17 @@ fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut Analy
             analyzer_cfg.movefmt_config = parameters;
             if !analyzer_cfg.movefmt_config.enable {
                 let params = lsp_types::UnregistrationParams {
-                    unregisterations: vec![lsp_types::Unregistration {
-                        id: lsp_types::request::Formatting::METHOD.to_string(),
-                        method: lsp_types::request::Formatting::METHOD.to_string(),
-                    }],
+                    unregisterations: [
+                        lsp_types::request::Formatting::METHOD,
+                        lsp_types::request::RangeFormatting::METHOD,
+                        lsp_types::request::OnTypeFormatting::METHOD,
+                    ]
+                    .iter()
+                    .map(|method| lsp_types::Unregistration {
+                        id: method.to_string(),
+                        method: method.to_string(),
+                    })
+                    .collect(),
                 };
                 context
                     .connection
@@ -299,11 +601,32 @@ fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut Analy
                     .unwrap();
             } else {
                 let params = lsp_types::RegistrationParams {
-                    registrations: vec![lsp_types::Registration {
-                        id: lsp_types::request::Formatting::METHOD.to_string(),
-                        method: lsp_types::request::Formatting::METHOD.to_string(),
-                        register_options: None,
-                    }],
+                    registrations: vec![
+                        lsp_types::Registration {
+                            id: lsp_types::request::Formatting::METHOD.to_string(),
+                            method: lsp_types::request::Formatting::METHOD.to_string(),
+                            register_options: None,
+                        },
+                        lsp_types::Registration {
+                            id: lsp_types::request::RangeFormatting::METHOD.to_string(),
+                            method: lsp_types::request::RangeFormatting::METHOD.to_string(),
+                            register_options: None,
+                        },
+                        lsp_types::Registration {
+                            id: lsp_types::request::OnTypeFormatting::METHOD.to_string(),
+                            method: lsp_types::request::OnTypeFormatting::METHOD.to_string(),
+                            register_options: Some(
+                                serde_json::to_value(
+                                    lsp_types::DocumentOnTypeFormattingRegistrationOptions {
+                                        document_selector: None,
+                                        first_trigger_character: "}".to_string(),
+                                        more_trigger_character: Some(vec![";".to_string()]),
+                                    },
+                                )
+                                .unwrap(),
+                            ),
+                        },
+                    ],
                 };
                 context
                     .connection
@@ -322,6 +645,42 @@ fn on_request(context: &mut Context, request: &Request, analyzer_cfg: &mut Analy
     }
 }
 
+/// Where the commands running in the background hand their results back to the main loop.
+struct CommandSenders {
+    test: TestSender,
+    coverage: CoverageSender,
+    diag: DiagSender,
+}
+
+fn on_execute_command(context: &Context, request: &Request, command_senders: &CommandSenders) {
+    log::info!("on_execute_command request = {:?}", request);
+    let parameters = serde_json::from_value::<ExecuteCommandParams>(request.params.clone())
+        .expect("could not deserialize execute command request");
+    match parameters.command.as_str() {
+        RUN_TESTS_COMMAND => {
+            on_run_tests(context, request, parameters, command_senders.test.clone())
+        },
+        SHOW_COVERAGE_COMMAND => on_show_coverage(
+            context,
+            request,
+            parameters,
+            command_senders.coverage.clone(),
+        ),
+        _ => {
+            let r = Response::new_err(
+                request.id.clone(),
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported command: '{}'", parameters.command),
+            );
+            context
+                .connection
+                .sender
+                .send(Message::Response(r))
+                .unwrap();
+        },
+    }
+}
+
 fn on_response(_context: &Context, _response: &Response) {
     log::info!("handle response[{:?}] from client", _response);
 }
@@ -338,78 +697,32 @@ fn report_diag(context: &mut Context, fpath: PathBuf) {
     };
     log::info!("report_diag -------------");
 
-    let mut result: HashMap<Url, Vec<lsp_types::Diagnostic>> = HashMap::new();
-    let diag_err = proj.err_diags.clone();
-    let tokens: Vec<&str> = diag_err.as_str().split("error").collect();
-    for token in tokens {
-        if token.lines().count() < 3 {
-            continue;
-        }
-        let err_msg = token.lines().next().unwrap_or_default();
-        let loc_str = match token.lines().nth(1) {
-            Some(str) => str.to_string(),
-            None => "".to_string(),
-        };
-
-        let mut file_path = "";
-        let mut pos = lsp_types::Position::default();
-        if let Some(start_idx) = loc_str.find('/') {
-            if let Some(end_idx) = loc_str.find(':') {
-                if start_idx <= end_idx {
-                    file_path = &loc_str[start_idx..end_idx];
-                    let line = loc_str[end_idx..].split(':').nth(1).unwrap_or_default();
-                    let col = loc_str[end_idx..].split(':').nth(2).unwrap_or_default();
-                    let line_num = if line.parse::<u32>().unwrap() > 0 {
-                        line.parse::<u32>().unwrap() - 1
-                    } else {
-                        line.parse::<u32>().unwrap()
-                    };
-                    let col_num = if col.parse::<u32>().unwrap() > 0 {
-                        col.parse::<u32>().unwrap() - 1
-                    } else {
-                        col.parse::<u32>().unwrap()
-                    };
-                    pos = lsp_types::Position::new(line_num, col_num);
-                }
-            }
-        }
-
-        if file_path.contains("aptos-move/") || file_path.is_empty() {
-            continue;
-        }
-
-        let mut code_str = "".to_string();
-        for line_idx in 2..token.lines().count() {
-            code_str.push_str(token.lines().nth(line_idx).unwrap());
-        }
-        let d = lsp_types::Diagnostic {
-            range: lsp_types::Range {
-                start: pos,
-                end: pos,
-            },
-            severity: Some(lsp_types::DiagnosticSeverity::ERROR),
-            message: format!("{}\n{}{:?}", err_msg, code_str, "".to_string()),
-            ..Default::default()
-        };
-        let url = url::Url::from_file_path(PathBuf::from(file_path).as_path()).unwrap();
-        result.insert(url, vec![d]);
-    }
-    for (k, v) in result.into_iter() {
-        let ds = lsp_types::PublishDiagnosticsParams::new(k.clone(), v, None);
-        context
-            .connection
-            .sender
-            .send(lsp_server::Message::Notification(Notification {
-                method: lsp_types::notification::PublishDiagnostics::METHOD.to_string(),
-                params: serde_json::to_value(ds).unwrap(),
-            }))
-            .unwrap();
-    }
+    let result = model_diagnostics(proj, fpath.as_path());
+    let mani = match discover_manifest_and_kind(fpath.as_path()) {
+        Some((x, _)) => x,
+        None => return,
+    };
+    publish_diags(
+        &context.connection,
+        &mut context.diag_version,
+        DiagKind::Model,
+        mani.as_path(),
+        result,
+    );
 }
 
-fn on_notification(context: &mut Context, notification: &Notification, diag_sender: DiagSender) {
+fn on_notification(
+    context: &mut Context,
+    notification: &Notification,
+    diag_sender: DiagSender,
+    indexer: &Sender<IndexJob>,
+) {
     fn update_defs_on_changed(context: &mut Context, fpath: PathBuf, content: String) {
         let file_hash = FileHash::new(content.as_str());
+        context
+            .projects
+            .documents
+            .insert(fpath.clone(), content.clone());
         context.projects.update_defs(fpath.clone(), content.clone());
         context
             .projects
@@ -466,6 +779,10 @@ fn on_notification(context: &mut Context, notification: &Notification, diag_send
                     .expect("could not deserialize DidOpenTextDocumentParams request");
             let fpath = parameters.text_document.uri.to_file_path().unwrap();
             let fpath = path_concat(&std::env::current_dir().unwrap(), &fpath);
+            context
+                .projects
+                .documents
+                .insert(fpath.clone(), parameters.text_document.text.clone());
             let (mani, _) = match discover_manifest_and_kind(&fpath) {
                 Some(x) => x,
                 None => {
@@ -479,12 +796,20 @@ fn on_notification(context: &mut Context, notification: &Notification, diag_send
                     if std::fs::read_to_string(fpath.as_path()).is_ok() {
                         // update_defs_on_changed(context, fpath.clone(), x);
                     };
+                    context.projects.touch_project(&fpath);
                     return;
                 },
                 None => {
                     log::error!("project '{:?}' not found try load.", fpath.as_path());
                 },
             };
+            send_manifest_diag(context, &mani);
+            if context.projects.load_cached_index(&mani) {
+                // serve the package from its index, the model is built when the server is idle.
+                context.projects.pending_manifests.retain(|x| *x != mani);
+                context.projects.pending_manifests.push_front(mani);
+                return;
+            }
             let p = match context.projects.load_projects(&context.connection, &mani) {
                 anyhow::Result::Ok(x) => x,
                 anyhow::Result::Err(e) => {
@@ -497,6 +822,51 @@ fn on_notification(context: &mut Context, notification: &Notification, diag_send
             make_diag(context, diag_sender, fpath.clone());
             report_diag(context, fpath);
         },
+        lsp_types::notification::DidChangeWorkspaceFolders::METHOD => {
+            use lsp_types::DidChangeWorkspaceFoldersParams;
+            let parameters = serde_json::from_value::<DidChangeWorkspaceFoldersParams>(
+                notification.params.clone(),
+            )
+            .expect("could not deserialize DidChangeWorkspaceFoldersParams request");
+            for folder in parameters.event.removed.iter() {
+                if let Ok(folder) = folder.uri.to_file_path() {
+                    context.projects.remove_workspace_folder(&folder);
+                }
+            }
+            for folder in parameters.event.added.iter() {
+                if let Ok(folder) = folder.uri.to_file_path() {
+                    let _ = indexer.send(IndexJob {
+                        folder,
+                        config: context.projects.config.clone(),
+                    });
+                }
+            }
+        },
+        lsp_types::notification::DidChangeWatchedFiles::METHOD => {
+            use lsp_types::{DidChangeWatchedFilesParams, FileChangeType};
+            let parameters =
+                serde_json::from_value::<DidChangeWatchedFilesParams>(notification.params.clone())
+                    .expect("could not deserialize DidChangeWatchedFilesParams request");
+            let mut changes = Vec::new();
+            for event in parameters.changes.iter() {
+                let fpath = match event.uri.to_file_path() {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                let fpath = path_concat(&std::env::current_dir().unwrap(), &fpath);
+                if event.typ == FileChangeType::DELETED {
+                    clear_file_diags(context, &event.uri);
+                }
+                changes.push((fpath, event.typ));
+            }
+            let reloaded = context
+                .projects
+                .on_watched_files_changed(&context.connection, &changes);
+            for mani in reloaded {
+                send_manifest_diag(context, &mani);
+                make_diag(context, diag_sender.clone(), mani.join(PROJECT_FILE_NAME));
+            }
+        },
         lsp_types::notification::DidCloseTextDocument::METHOD => {
             use lsp_types::DidCloseTextDocumentParams;
             let parameters =
@@ -504,6 +874,7 @@ fn on_notification(context: &mut Context, notification: &Notification, diag_send
                     .expect("could not deserialize DidCloseTextDocumentParams request");
             let fpath = parameters.text_document.uri.to_file_path().unwrap();
             let fpath = path_concat(&std::env::current_dir().unwrap(), &fpath);
+            context.projects.documents.remove(&fpath);
             let (_, _) = match discover_manifest_and_kind(&fpath) {
                 Some(x) => x,
                 None => {
@@ -518,22 +889,87 @@ fn on_notification(context: &mut Context, notification: &Notification, diag_send
     }
 }
 
-fn get_package_compile_diagnostics(pkg_path: &Path) -> Result<Diagnostics> {
+/// Take what the indexer sent, reporting the progress of indexing to the client.
+fn on_index_event(
+    context: &mut Context,
+    indexing: &mut Option<(ProgressReporter, usize, usize)>,
+    event: IndexEvent,
+) {
+    let mani = match event {
+        IndexEvent::Discovered(manifests) => {
+            let (_, _, total) = indexing.get_or_insert_with(|| {
+                (
+                    ProgressReporter::begin(
+                        &context.connection.sender,
+                        None,
+                        "move/index",
+                        "Indexing Move packages",
+                    ),
+                    0,
+                    0,
+                )
+            });
+            *total += manifests.len();
+            return;
+        },
+        IndexEvent::Indexed(mani, indexes) => {
+            context.projects.insert_indexes(indexes);
+            send_manifest_diag(context, &mani);
+            mani
+        },
+        IndexEvent::Unindexed(mani) => {
+            // built once on this thread when idle, loading the project indexes its packages.
+            let loaded = context.projects.projects.keys().any(|k| k.contains(&mani));
+            if !loaded && !context.projects.pending_manifests.contains(&mani) {
+                context.projects.pending_manifests.push_back(mani.clone());
+            }
+            mani
+        },
+    };
+    if let Some((progress, done, total)) = indexing.as_mut() {
+        *done += 1;
+        progress.report(
+            format!("{:?}", mani.file_name().unwrap_or_default()),
+            Some((*done * 100 / (*total).max(1)) as u32),
+        );
+    }
+    if matches!(indexing.as_ref(), Some((_, done, total)) if done >= total) {
+        if let Some((progress, done, _)) = indexing.take() {
+            progress.end(Some(format!("{} packages", done)));
+        }
+    }
+}
+
+/// Load one queued package, the model of a package has to be built on this thread.
+fn load_next_package(context: &mut Context, diag_sender: DiagSender) {
+    if let Some(mani) = context.projects.load_next_pending(&context.connection) {
+        send_manifest_diag(context, &mani);
+        // a document opened while the package was served from the index cache.
+        let opened = context
+            .projects
+            .documents
+            .keys()
+            .find(|x| discover_manifest_and_kind(x).map(|(x, _)| x) == Some(mani.clone()))
+            .cloned();
+        if let Some(fpath) = opened {
+            make_diag(context, diag_sender, fpath.clone());
+            report_diag(context, fpath);
+        }
+    }
+}
+
+fn get_package_compile_diagnostics(
+    pkg_path: &Path,
+    build_config: move_package::BuildConfig,
+) -> Result<Diagnostics> {
     use anyhow::*;
     use move_package::compilation::build_plan::BuildPlan;
-    use tempfile::tempdir;
-    let build_config = move_package::BuildConfig {
-        test_mode: true,
-        install_dir: Some(tempdir().unwrap().path().to_path_buf()),
-        skip_fetch_latest_git_deps: true,
-        ..Default::default()
-    };
+    let compile_cfg = build_config.compiler_config.clone();
     // resolution graph diagnostics are only needed for CLI commands so ignore them by passing a
     // vector as the writer
     let resolution_graph = build_config.resolution_graph_for_package(pkg_path, &mut Vec::new())?;
     let build_plan = BuildPlan::create(resolution_graph)?;
     let mut diagnostics = None;
-    let compile_cfg: move_package::CompilerConfig = Default::default();
     build_plan.compile_with_driver(
         &mut std::io::sink(),
         &compile_cfg,
@@ -572,8 +1008,13 @@ fn make_diag(context: &Context, diag_sender: DiagSender, fpath: PathBuf) {
         },
         None => return,
     };
+    let build_config = context.projects.config.build_config(&mani);
+    let build_lock = build_config.install_dir.as_deref().map(build_dir_lock);
     std::thread::spawn(move || {
-        let x = match get_package_compile_diagnostics(mani.as_path()) {
+        let _build_guard = build_lock
+            .as_ref()
+            .map(|x| x.lock().unwrap_or_else(|x| x.into_inner()));
+        let x = match get_package_compile_diagnostics(mani.as_path(), build_config) {
             Ok(x) => x,
             Err(err) => {
                 log::error!("get_package_compile_diagnostics failed,err:{:?}", err);
@@ -584,6 +1025,52 @@ fn make_diag(context: &Context, diag_sender: DiagSender, fpath: PathBuf) {
     });
 }
 
+/// Ask the client to notify the server of `.move` and `Move.toml` files changed on disk.
+fn register_watched_files(context: &Context) {
+    let watchers = ["**/*.move", "**/Move.toml"]
+        .iter()
+        .map(|x| lsp_types::FileSystemWatcher {
+            glob_pattern: lsp_types::GlobPattern::String(x.to_string()),
+            kind: None,
+        })
+        .collect();
+    let params = lsp_types::RegistrationParams {
+        registrations: vec![lsp_types::Registration {
+            id: lsp_types::notification::DidChangeWatchedFiles::METHOD.to_string(),
+            method: lsp_types::notification::DidChangeWatchedFiles::METHOD.to_string(),
+            register_options: Some(serde_json::json!(
+                lsp_types::DidChangeWatchedFilesRegistrationOptions { watchers }
+            )),
+        }],
+    };
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Request(Request {
+            id: "watched_files".to_string().into(),
+            method: lsp_types::request::RegisterCapability::METHOD.to_string(),
+            params: serde_json::json!(params),
+        }))
+        .unwrap();
+}
+
+/// Clear the diagnostics of a file deleted from disk, whatever reported them: compiler, model,
+/// manifest, tests or coverage.
+fn clear_file_diags(context: &mut Context, url: &Url) {
+    if !context.diag_version.remove_file(url) {
+        return;
+    }
+    let ds = lsp_types::PublishDiagnosticsParams::new(url.clone(), vec![], None);
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Notification(Notification {
+            method: lsp_types::notification::PublishDiagnostics::METHOD.to_string(),
+            params: serde_json::to_value(ds).unwrap(),
+        }))
+        .unwrap();
+}
+
 fn send_not_project_file_error(context: &mut Context, fpath: PathBuf, is_open: bool) {
     let url = url::Url::from_file_path(fpath.as_path()).unwrap();
     let content = std::fs::read_to_string(fpath.as_path()).unwrap_or_else(|_| "".to_string());
@@ -667,26 +1154,29 @@ fn send_diag(context: &mut Context, mani: PathBuf, x: Diagnostics) {
             };
         }
     }
-    // update version.
-    for (k, v) in result.iter() {
-        context.diag_version.update(&mani, k, v.len());
-    }
-    context.diag_version.with_manifest(&mani, |x| {
-        for (old, v) in x.iter() {
-            if !result.contains_key(old) && *v > 0 {
-                result.insert(old.clone(), vec![]);
-            }
-        }
-    });
-    for (k, x) in result.iter() {
-        if x.is_empty() {
-            context.diag_version.update(&mani, k, 0);
-        }
-    }
-    for (k, v) in result.into_iter() {
-        let ds = lsp_types::PublishDiagnosticsParams::new(k.clone(), v, None);
-        context
-            .connection
+    tag_sources(&mut result);
+    publish_diags(
+        &context.connection,
+        &mut context.diag_version,
+        DiagKind::Compiler,
+        mani.as_path(),
+        result,
+    );
+}
+
+/// Publish the diagnostics `kind` reported for the project at `mani`, merged with the other
+/// diagnostics of their files, and clear the files which had diagnostics of `kind` before but
+/// have none now.
+fn publish_diags(
+    connection: &Connection,
+    diag_version: &mut FileDiags,
+    kind: DiagKind,
+    mani: &Path,
+    result: HashMap<Url, Vec<lsp_types::Diagnostic>>,
+) {
+    for (k, v) in diag_version.update(kind, mani, result).into_iter() {
+        let ds = lsp_types::PublishDiagnosticsParams::new(k, v, None);
+        connection
             .sender
             .send(lsp_server::Message::Notification(Notification {
                 method: lsp_types::notification::PublishDiagnostics::METHOD.to_string(),
@@ -695,3 +1185,46 @@ fn send_diag(context: &mut Context, mani: PathBuf, x: Diagnostics) {
             .unwrap();
     }
 }
+
+fn send_test_diag(context: &mut Context, report: UnitTestReport) {
+    if let Some(err) = report.error.as_ref() {
+        log::error!("run unit tests failed,err:{}", err);
+    }
+    let result = test_failure_diagnostics(&context.projects, &report);
+    publish_diags(
+        &context.connection,
+        &mut context.diag_version,
+        DiagKind::Test,
+        report.manifest.as_path(),
+        result,
+    );
+}
+
+fn send_coverage_diag(context: &mut Context, report: CoverageReport) {
+    if !report.stale_modules.is_empty() {
+        log::info!(
+            "coverage of {:?} is out of date, rerun the tests with coverage.",
+            report.stale_modules
+        );
+    }
+    let result = report.diagnostics();
+    publish_diags(
+        &context.connection,
+        &mut context.diag_version,
+        DiagKind::Coverage,
+        report.manifest.as_path(),
+        result,
+    );
+}
+
+/// Publish the diagnostics of the `Move.toml` files of the project at `mani`.
+fn send_manifest_diag(context: &mut Context, mani: &Path) {
+    let result = manifest_diagnostics(mani, &context.projects.config);
+    publish_diags(
+        &context.connection,
+        &mut context.diag_version,
+        DiagKind::Manifest,
+        mani,
+        result,
+    );
+}
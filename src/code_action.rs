@@ -0,0 +1,264 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Code actions of a Move file.
+//!
+//! Spec generation is offered as refactorings whose edits the client previews and applies, so it
+//! works in any editor and not only through the `move/generate/spec/*` requests of the VS Code
+//! extension. Generating a spec takes a while, the actions are listed without their edits, which
+//! are computed when the client resolves the action it picked. Clients which can not resolve the
+//! edit of an action get the actions with their edits.
+
+use crate::{
+    context::Context,
+    move_generate_spec_file::{spec_file_update, SpecFileUpdate},
+    move_generate_spec_merge::GENERATED_BEGIN,
+    move_generate_spec_sel::{
+        existing_spec_edit, find_existing_spec, find_spec_target, generate_spec_target,
+    },
+    movefmt::text_edits,
+    project::Project,
+    utils::path_concat,
+};
+use lsp_server::{ErrorCode, Request, Response};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams, CreateFile,
+    CreateFileOptions, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// What a spec code action generates, kept in its `data` until it is resolved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SpecActionData {
+    fpath: PathBuf,
+    /// The position of the function or the struct, None for the spec file of the module.
+    position: Option<Position>,
+}
+
+/// Handles code action request of the language server, `resolve_edits` when the client resolves
+/// the edits of the actions with `codeAction/resolve`.
+pub fn on_code_action_request(
+    context: &Context,
+    request: &Request,
+    resolve_edits: bool,
+) -> Response {
+    log::info!("on_code_action_request request = {:?}", request);
+    let parameters = serde_json::from_value::<CodeActionParams>(request.params.clone())
+        .expect("could not deserialize code action request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let mut actions: Vec<CodeActionOrCommand> = vec![];
+    if is_kind_requested(&CodeActionKind::REFACTOR, parameters.context.only.as_ref()) {
+        if let Some(project) = context.projects.get_project(&fpath) {
+            actions.extend(
+                spec_code_actions(project, &fpath, parameters.range.start, resolve_edits)
+                    .into_iter()
+                    .map(CodeActionOrCommand::CodeAction),
+            );
+        }
+    }
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(actions).unwrap());
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+/// Handles code action resolve request of the language server, the edit of the action is
+/// generated.
+pub fn on_code_action_resolve_request(context: &Context, request: &Request) -> Response {
+    log::info!("on_code_action_resolve_request request = {:?}", request);
+    let action = serde_json::from_value::<CodeAction>(request.params.clone())
+        .expect("could not deserialize code action resolve request");
+    let fpath = action
+        .data
+        .clone()
+        .and_then(|x| serde_json::from_value::<SpecActionData>(x).ok())
+        .map(|x| x.fpath);
+    let r = match fpath.and_then(|x| context.projects.get_project(&x)) {
+        Some(project) => Response::new_ok(
+            request.id.clone(),
+            serde_json::to_value(resolve_code_action(project, action)).unwrap(),
+        ),
+        None => Response::new_err(
+            request.id.clone(),
+            ErrorCode::InvalidParams as i32,
+            "the project of the code action is not loaded.".to_string(),
+        ),
+    };
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+/// "Generate spec for" the function or the struct at `pos`, or "Refresh spec for" it when its
+/// spec has generated clauses, and "Generate spec file for this module" when the spec file misses
+/// specs. With `resolve_edits` the actions have no edit, `resolve_code_action` generates it.
+pub fn spec_code_actions(
+    project: &Project,
+    fpath: &Path,
+    pos: Position,
+    resolve_edits: bool,
+) -> Vec<CodeAction> {
+    let mut ret = vec![];
+    if let Some(target) = find_spec_target(project, fpath, pos.line, pos.character) {
+        let verb = match find_existing_spec(fpath, &target) {
+            None => Some("Generate"),
+            Some((_, content, start, end)) if content[start..end].contains(GENERATED_BEGIN) => {
+                Some("Refresh")
+            },
+            // written by hand, nothing to generate.
+            Some(_) => None,
+        };
+        if let Some(verb) = verb {
+            let title = format!(
+                "{} spec for {} `{}`",
+                verb,
+                if target.is_struct {
+                    "struct"
+                } else {
+                    "function"
+                },
+                target.name
+            );
+            let mut action = refactor(title, fpath, Some(pos));
+            if !resolve_edits {
+                action = resolve_code_action(project, action);
+            }
+            ret.push(action);
+        }
+    }
+    // the specs of the whole module are generated to know whether the file misses some, the
+    // edit comes from them when it is not resolved later.
+    if let Ok(update) = spec_file_update(project, fpath, false) {
+        if update.is_changed() {
+            let mut action = refactor(
+                "Generate spec file for this module".to_string(),
+                fpath,
+                None,
+            );
+            if !resolve_edits {
+                action.edit = Some(spec_file_edit_of(update).unwrap_or_default());
+            }
+            ret.push(action);
+        }
+    }
+    ret
+}
+
+/// `action` with its edit, an empty one when the specs are there already.
+pub fn resolve_code_action(project: &Project, mut action: CodeAction) -> CodeAction {
+    let data = match action
+        .data
+        .clone()
+        .and_then(|x| serde_json::from_value::<SpecActionData>(x).ok())
+    {
+        Some(x) => x,
+        None => return action,
+    };
+    let edit = match data.position {
+        Some(pos) => spec_edit(project, data.fpath.as_path(), pos),
+        None => spec_file_edit(project, data.fpath.as_path()),
+    };
+    action.edit = Some(edit.unwrap_or_default());
+    action
+}
+
+/// The spec of the function or the struct at `pos` inserted after it, or the refresh of the
+/// generated clauses of its spec.
+fn spec_edit(project: &Project, fpath: &Path, pos: Position) -> Option<WorkspaceEdit> {
+    let target = generate_spec_target(project, fpath, pos.line, pos.character)?;
+    match existing_spec_edit(fpath, &target) {
+        Some((edit, _)) => Some(edit),
+        None => Url::from_file_path(fpath).ok().map(|url| {
+            // on the line after the closing brace of the function or the struct.
+            let pos = Position::new(target.end_line + 1, 0);
+            WorkspaceEdit::new(HashMap::from([(
+                url,
+                vec![TextEdit {
+                    range: Range::new(pos, pos),
+                    new_text: target.content.clone(),
+                }],
+            )]))
+        }),
+    }
+}
+
+/// The spec file of the module created, or the specs it misses added to it.
+fn spec_file_edit(project: &Project, fpath: &Path) -> Option<WorkspaceEdit> {
+    match spec_file_update(project, fpath, false) {
+        Ok(x) if x.is_changed() => spec_file_edit_of(x),
+        _ => None,
+    }
+}
+
+fn spec_file_edit_of(update: SpecFileUpdate) -> Option<WorkspaceEdit> {
+    let url = Url::from_file_path(update.path.as_path()).ok()?;
+    Some(match update.origin.as_ref() {
+        Some(origin) => WorkspaceEdit::new(HashMap::from([(
+            url,
+            text_edits(origin.as_str(), update.content.as_str(), None),
+        )])),
+        None => WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: url.clone(),
+                    options: Some(CreateFileOptions {
+                        overwrite: Some(false),
+                        ignore_if_exists: Some(true),
+                    }),
+                    annotation_id: None,
+                })),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: url,
+                        version: None,
+                    },
+                    edits: vec![OneOf::Left(TextEdit {
+                        range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+                        new_text: update.content,
+                    })],
+                }),
+            ])),
+            ..Default::default()
+        },
+    })
+}
+
+fn refactor(title: String, fpath: &Path, position: Option<Position>) -> CodeAction {
+    let data = SpecActionData {
+        fpath: fpath.to_path_buf(),
+        position,
+    };
+    CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR),
+        data: serde_json::to_value(data).ok(),
+        ..Default::default()
+    }
+}
+
+/// Whether `kind` is one of `only`, or a sub kind of them, when the client asks for some kinds.
+fn is_kind_requested(kind: &CodeActionKind, only: Option<&Vec<CodeActionKind>>) -> bool {
+    let only = match only {
+        Some(x) => x,
+        None => return true,
+    };
+    only.iter().any(|x| {
+        kind.as_str() == x.as_str()
+            || kind
+                .as_str()
+                .starts_with(format!("{}.", x.as_str()).as_str())
+    })
+}
@@ -3,7 +3,11 @@
 
 use crate::multiproject::MultiProject;
 use lsp_server::Connection;
-use std::{collections::HashMap, path::PathBuf};
+use lsp_types::Diagnostic;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 /// The context within which the language server is running.
 pub struct Context {
@@ -13,9 +17,23 @@ pub struct Context {
     pub diag_version: FileDiags,
 }
 
+/// What reported a set of diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DiagKind {
+    Compiler,
+    Model,
+    Manifest,
+    Test,
+    Coverage,
+}
+
+/// The diagnostics published for every file, by what reported them and for which project.
+///
+/// A publish replaces all the diagnostics of a file in the client, so the diagnostics of a file
+/// are always published merged with those the other kinds reported for it.
 #[derive(Default)]
 pub struct FileDiags {
-    diags: HashMap<PathBuf, HashMap<url::Url, usize>>,
+    diags: HashMap<(DiagKind, PathBuf), HashMap<url::Url, Vec<Diagnostic>>>,
 }
 
 impl FileDiags {
@@ -23,18 +41,51 @@ impl FileDiags {
         Self::default()
     }
 
-    pub fn update(&mut self, mani: &PathBuf, fpath: &url::Url, diags: usize) {
-        if let Some(x) = self.diags.get_mut(mani) {
-            x.insert(fpath.clone(), diags);
-        } else {
-            let mut x: HashMap<url::Url, usize> = HashMap::new();
-            x.insert(fpath.clone(), diags);
-            self.diags.insert(mani.clone(), x);
-        }
+    /// Replace the diagnostics `kind` reported for the project at `mani` by `result`, returns the
+    /// merged diagnostics of the files to publish again, those which had diagnostics of `kind`
+    /// before but have none now included.
+    pub fn update(
+        &mut self,
+        kind: DiagKind,
+        mani: &Path,
+        result: HashMap<url::Url, Vec<Diagnostic>>,
+    ) -> HashMap<url::Url, Vec<Diagnostic>> {
+        let old = self
+            .diags
+            .insert((kind, mani.to_path_buf()), result.clone())
+            .unwrap_or_default();
+        let changed: Vec<url::Url> = old
+            .into_iter()
+            .filter(|(k, v)| !v.is_empty() && !result.contains_key(k))
+            .map(|(k, _)| k)
+            .chain(result.into_keys())
+            .collect();
+        changed
+            .into_iter()
+            .map(|x| {
+                let ds = self.merged(&x);
+                (x, ds)
+            })
+            .collect()
     }
 
-    pub fn with_manifest(&self, mani: &PathBuf, mut call: impl FnMut(&HashMap<url::Url, usize>)) {
-        let empty = Default::default();
-        call(self.diags.get(mani).unwrap_or(&empty));
+    /// All the diagnostics of `fpath`.
+    pub fn merged(&self, fpath: &url::Url) -> Vec<Diagnostic> {
+        let mut keys: Vec<_> = self.diags.keys().collect();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|k| self.diags[k].get(fpath))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Forget a file, returns true if it had diagnostics which should be cleared.
+    pub fn remove_file(&mut self, fpath: &url::Url) -> bool {
+        let mut had_diags = false;
+        for x in self.diags.values_mut() {
+            had_diags |= x.remove(fpath).map(|x| !x.is_empty()).unwrap_or(false);
+        }
+        had_diags
     }
 }
@@ -0,0 +1,188 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dependency resolutions and source lists shared between the projects of a `MultiProject`.
+//!
+//! Compiled dependencies are not shared: every project builds its own `GlobalEnv` and compiles its
+//! dependencies again. The model builder of the pinned move-model compiles every package it is
+//! given from source and can not take precompiled units or an existing `GlobalEnv`, so sharing
+//! them needs a new entry point in move-model first. What is saved is the work done before
+//! compiling. Resolving the dependency graph of a package reads every
+//! manifest reachable from it and looks up the local package cache, the resolutions are kept by
+//! root package until one of the manifests they read changes. Scanning the source tree of a
+//! dependency walks its directories, the source lists are kept by package path and by the
+//! addresses of the named addresses the dependency can use, so the projects depending on the same
+//! framework scan it once.
+
+use crate::{
+    analyzer_handler::file_modify_time,
+    utils::{normal_path, path_concat, PROJECT_FILE_NAME},
+};
+use move_compiler::shared::{NumericalAddress, PackagePaths};
+use move_package::source_package::manifest_parser::parse_move_manifest_from_file;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+/// The dependency graph of a root package.
+#[derive(Clone, Debug, Default)]
+pub struct ResolvedDependencies {
+    /// Package paths of the dependencies, the root package excluded.
+    pub packages: Vec<PathBuf>,
+    /// `name=address` assignments of the whole graph.
+    pub named_addresses: Vec<String>,
+    /// Manifests read by the resolution, with their modification time.
+    manifests: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ResolvedDependencies {
+    pub fn new(root: &Path, packages: Vec<PathBuf>, named_addresses: Vec<String>) -> Self {
+        let manifests = std::iter::once(root)
+            .chain(packages.iter().map(|x| x.as_path()))
+            .map(|x| {
+                let file = x.join(PROJECT_FILE_NAME);
+                let time = file_modify_time(file.as_path());
+                (file, time)
+            })
+            .collect();
+        Self {
+            packages,
+            named_addresses,
+            manifests,
+        }
+    }
+
+    fn up_to_date(&self) -> bool {
+        self.manifests
+            .iter()
+            .all(|(file, time)| file_modify_time(file.as_path()) == *time)
+    }
+}
+
+/// (package path, `name=address` assignments of the named addresses the package can use).
+type DependencyKey = (PathBuf, Vec<String>);
+
+#[derive(Default)]
+pub struct DependencyCache {
+    resolutions: HashMap<PathBuf, Rc<ResolvedDependencies>>,
+    packages: HashMap<DependencyKey, Rc<PackagePaths<String, String>>>,
+    /// Named addresses declared by the manifest of a package and of its dependencies, None when
+    /// a manifest could not be read.
+    address_names: HashMap<PathBuf, Option<Rc<BTreeSet<String>>>>,
+}
+
+impl DependencyCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The resolution of `root`, if none of the manifests it read has changed since.
+    pub fn resolution(&self, root: &Path) -> Option<Rc<ResolvedDependencies>> {
+        self.resolutions
+            .get(&normal_path(root))
+            .filter(|x| x.up_to_date())
+            .cloned()
+    }
+
+    pub fn insert_resolution(
+        &mut self,
+        root: &Path,
+        resolved: ResolvedDependencies,
+    ) -> Rc<ResolvedDependencies> {
+        let resolved = Rc::new(resolved);
+        self.resolutions.insert(normal_path(root), resolved.clone());
+        resolved
+    }
+
+    /// The sources of the dependency at `package` compiled with the named addresses of
+    /// `addresses` it can use, `load` scans them the first time. Two roots assigning their own
+    /// named addresses differently share the sources of a dependency which does not use them.
+    pub fn package_paths(
+        &mut self,
+        package: &Path,
+        addresses: &BTreeMap<String, NumericalAddress>,
+        load: impl FnOnce() -> Vec<PathBuf>,
+    ) -> Rc<PackagePaths<String, String>> {
+        let package = normal_path(package);
+        let named_address_map: BTreeMap<String, NumericalAddress> =
+            match self.address_names(&package) {
+                Some(names) => addresses
+                    .iter()
+                    .filter(|(name, _)| names.contains(*name))
+                    .map(|(name, addr)| (name.clone(), *addr))
+                    .collect(),
+                None => addresses.clone(),
+            };
+        let key = (
+            package,
+            named_address_map
+                .iter()
+                .map(|(name, addr)| format!("{}={}", name, addr))
+                .collect(),
+        );
+        self.packages
+            .entry(key)
+            .or_insert_with(|| {
+                Rc::new(PackagePaths {
+                    name: None,
+                    paths: load()
+                        .into_iter()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .collect(),
+                    named_address_map,
+                })
+            })
+            .clone()
+    }
+
+    /// Named addresses declared by the manifests of `package` and of its dependencies, the
+    /// only ones the sources of `package` can use.
+    fn address_names(&mut self, package: &Path) -> Option<Rc<BTreeSet<String>>> {
+        if let Some(x) = self.address_names.get(package) {
+            return x.clone();
+        }
+        let mut names = BTreeSet::new();
+        let mut visited = HashSet::new();
+        let ret = collect_address_names(package, &mut visited, &mut names).map(|_| Rc::new(names));
+        self.address_names
+            .insert(package.to_path_buf(), ret.clone());
+        ret
+    }
+
+    /// Forget everything learned about the package at `package`, after its files or its
+    /// manifest changed on disk.
+    pub fn invalidate(&mut self, package: &Path) {
+        let package = normal_path(package);
+        self.packages.retain(|(path, _), _| *path != package);
+        // the manifest of the package may be read for any package depending on it.
+        self.address_names.clear();
+        self.resolutions.retain(|root, x| {
+            *root != package && !x.packages.iter().any(|p| normal_path(p) == package)
+        });
+    }
+
+    pub fn package_count(&self) -> usize {
+        self.packages.len()
+    }
+}
+
+fn collect_address_names(
+    package: &Path,
+    visited: &mut HashSet<PathBuf>,
+    names: &mut BTreeSet<String>,
+) -> Option<()> {
+    if !visited.insert(normal_path(package)) {
+        return Some(());
+    }
+    let manifest = parse_move_manifest_from_file(package).ok()?;
+    for name in manifest.addresses.iter().flat_map(|x| x.keys()) {
+        names.insert(name.to_string());
+    }
+    for de in manifest.dependencies.values() {
+        collect_address_names(&path_concat(package, &de.local), visited, names)?;
+    }
+    Some(())
+}
@@ -0,0 +1,204 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Folding ranges of a Move file.
+//!
+//! Modules, functions, structs and spec blocks come from the parser AST, use groups and comments
+//! come from the tokens, so folding keeps working while a file does not type check.
+
+use crate::{
+    context::Context,
+    utils::{lexer_for_buffer, parse_buffer, path_concat, LineIndex},
+};
+use lsp_server::{Request, Response};
+use lsp_types::{FoldingRange, FoldingRangeKind, FoldingRangeParams};
+use move_compiler::parser::{
+    ast::{Definition, ModuleDefinition, ModuleMember},
+    lexer::Tok,
+};
+use move_ir_types::location::Loc;
+
+/// Handles folding range request of the language server.
+pub fn on_folding_range_request(context: &Context, request: &Request) -> Response {
+    log::info!("on_folding_range_request request = {:?}", request);
+    let parameters = serde_json::from_value::<FoldingRangeParams>(request.params.clone())
+        .expect("could not deserialize folding range request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let ranges = context
+        .projects
+        .get_document(&fpath)
+        .map(|content| folding_ranges(content.as_str()))
+        .unwrap_or_default();
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(ranges).unwrap());
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+pub fn folding_ranges(content: &str) -> Vec<FoldingRange> {
+    let mut collector = FoldingCollector {
+        index: LineIndex::new(content),
+        ranges: vec![],
+    };
+    if let Some(defs) = parse_buffer(content) {
+        for d in defs.iter() {
+            collector.collect_definition(d);
+        }
+    }
+    let tokens = lexer_for_buffer(content);
+    collector.collect_use_groups(&tokens);
+    collector.collect_comments(content, &tokens);
+    collector.ranges.sort_by_key(|x| (x.start_line, x.end_line));
+    collector.ranges
+}
+
+struct FoldingCollector {
+    index: LineIndex,
+    ranges: Vec<FoldingRange>,
+}
+
+impl FoldingCollector {
+    fn collect_definition(&mut self, d: &Definition) {
+        match d {
+            Definition::Module(m) => self.collect_module(m),
+            Definition::Address(a) => {
+                self.add_loc(&a.loc, FoldingRangeKind::Region);
+                for m in a.modules.iter() {
+                    self.collect_module(m);
+                }
+            },
+            Definition::Script(s) => {
+                self.add_loc(&s.loc, FoldingRangeKind::Region);
+                self.add_loc(&s.function.loc, FoldingRangeKind::Region);
+                for spec in s.specs.iter() {
+                    self.add_loc(&spec.loc, FoldingRangeKind::Region);
+                }
+            },
+        }
+    }
+
+    fn collect_module(&mut self, m: &ModuleDefinition) {
+        self.add_loc(&m.loc, FoldingRangeKind::Region);
+        for member in m.members.iter() {
+            match member {
+                ModuleMember::Function(f) => self.add_loc(&f.loc, FoldingRangeKind::Region),
+                ModuleMember::Struct(s) => self.add_loc(&s.loc, FoldingRangeKind::Region),
+                ModuleMember::Spec(s) => self.add_loc(&s.loc, FoldingRangeKind::Region),
+                _ => {},
+            }
+        }
+    }
+
+    /// Contiguous `use` declarations, like `use a::b; use a::c;`.
+    fn collect_use_groups(&mut self, tokens: &[(Tok, (usize, usize))]) {
+        let mut group: Option<(usize, usize)> = None;
+        let mut index = 0;
+        while index < tokens.len() {
+            if tokens[index].0 != Tok::Use {
+                if let Some((start, end)) = group.take() {
+                    self.add(start, end, FoldingRangeKind::Imports);
+                }
+                index += 1;
+                continue;
+            }
+            let start = tokens[index].1 .0;
+            let mut end = start;
+            while index < tokens.len() {
+                end = tokens[index].1 .1;
+                index += 1;
+                if tokens[index - 1].0 == Tok::Semicolon {
+                    break;
+                }
+            }
+            group = match group {
+                Some((group_start, _)) => Some((group_start, end)),
+                None => Some((start, end)),
+            };
+        }
+        if let Some((start, end)) = group {
+            self.add(start, end, FoldingRangeKind::Imports);
+        }
+    }
+
+    /// Block comments and runs of line comments. Comments live between the tokens, so only the
+    /// gaps between tokens are scanned, which keeps `//` inside byte strings out.
+    fn collect_comments(&mut self, content: &str, tokens: &[(Tok, (usize, usize))]) {
+        let mut gaps = vec![];
+        let mut last_end = 0;
+        for (_, (start, end)) in tokens.iter() {
+            gaps.push((last_end, *start));
+            last_end = *end;
+        }
+        gaps.push((last_end, content.len()));
+
+        for (gap_start, gap_end) in gaps {
+            let gap = &content[gap_start..gap_end];
+            // (start, end) of the current run of line comments.
+            let mut run: Option<(usize, usize)> = None;
+            let mut offset = 0;
+            while offset < gap.len() {
+                let rest = &gap[offset..];
+                if rest.starts_with("//") {
+                    let len = rest.find('\n').unwrap_or(rest.len());
+                    let (start, end) = (gap_start + offset, gap_start + offset + len);
+                    run = match run {
+                        Some((run_start, run_end))
+                            if self.index.position(start).line
+                                == self.index.position(run_end).line + 1 =>
+                        {
+                            Some((run_start, end))
+                        },
+                        _ => {
+                            self.flush_comment_run(run);
+                            Some((start, end))
+                        },
+                    };
+                    offset += len;
+                } else if rest.starts_with("/*") {
+                    self.flush_comment_run(run.take());
+                    let len = rest[2..].find("*/").map(|x| x + 4).unwrap_or(rest.len());
+                    self.add(
+                        gap_start + offset,
+                        gap_start + offset + len,
+                        FoldingRangeKind::Comment,
+                    );
+                    offset += len;
+                } else {
+                    offset += rest.chars().next().map(|x| x.len_utf8()).unwrap_or(1);
+                }
+            }
+            self.flush_comment_run(run);
+        }
+    }
+
+    fn flush_comment_run(&mut self, run: Option<(usize, usize)>) {
+        if let Some((start, end)) = run {
+            self.add(start, end, FoldingRangeKind::Comment);
+        }
+    }
+
+    fn add_loc(&mut self, loc: &Loc, kind: FoldingRangeKind) {
+        self.add(loc.start() as usize, loc.end() as usize, kind);
+    }
+
+    fn add(&mut self, start: usize, end: usize, kind: FoldingRangeKind) {
+        let start = self.index.position(start);
+        let end = self.index.position(end);
+        // nothing to fold on a single line.
+        if start.line >= end.line {
+            return;
+        }
+        self.ranges.push(FoldingRange {
+            start_line: start.line,
+            start_character: None,
+            end_line: end.line,
+            end_character: None,
+            kind: Some(kind),
+            collapsed_text: None,
+        });
+    }
+}
@@ -4,7 +4,7 @@
 use crate::{
     analyzer_handler::*,
     context::*,
-    utils::{path_concat, FileRange},
+    utils::{call_capture_loc, path_concat, FileRange},
 };
 use codespan::Span;
 use lsp_server::*;
@@ -41,6 +41,22 @@ pub fn on_go_to_def_request(context: &Context, request: &Request) -> lsp_server:
         Some(x) => x,
         None => {
             log::error!("project not found:{:?}", fpath.as_path());
+            // the model is not built yet, answer from the package indexes.
+            if let Some(symbol) = context.projects.index_symbol_at(&fpath, &loc) {
+                let locations = Url::from_file_path(&symbol.file)
+                    .map(|url| vec![Location::new(url, symbol.range)])
+                    .unwrap_or_default();
+                let r = Response::new_ok(
+                    request.id.clone(),
+                    serde_json::to_value(GotoDefinitionResponse::Array(locations)).unwrap(),
+                );
+                context
+                    .connection
+                    .sender
+                    .send(Message::Response(r.clone()))
+                    .unwrap();
+                return r;
+            }
             return Response {
                 id: "".to_string().into(),
                 result: Some(serde_json::json!({"msg": "No available project"})),
@@ -812,6 +828,12 @@ impl Handler {
                     self.collect_local_var_in_pattern(pattern);
                     true
                 },
+                // the parameters of a lambda passed to an inline function.
+                Lambda(_, pattern, _) => {
+                    self.collect_local_var_in_pattern(pattern);
+                    self.process_pattern(env, pattern);
+                    true
+                },
                 _ => {
                     log::trace!("________________");
                     true
@@ -900,17 +922,21 @@ impl Handler {
 
     fn process_call(&mut self, env: &GlobalEnv, expdata: &move_model::ast::ExpData) {
         log::trace!(">> process_call");
-        if let Call(node_id, MoveFunction(mid, fid), _) = expdata {
-            let this_call_loc = env.get_node_loc(*node_id);
+        if let Call(node_id, MoveFunction(mid, fid), args) = expdata {
+            let called_module = env.get_module(*mid);
+            let called_fun = called_module.get_function(*fid);
+            let call_loc = env.get_node_loc(*node_id);
             log::trace!(
                 ">> exp.visit this_call_loc = {:?}",
-                env.get_location(&this_call_loc)
+                env.get_location(&call_loc)
             );
-            if this_call_loc.span().start() < self.mouse_span.end()
-                && self.mouse_span.end() < this_call_loc.span().end()
-            {
-                let called_module = env.get_module(*mid);
-                let called_fun = called_module.get_function(*fid);
+            if let Some(this_call_loc) = call_capture_loc(
+                env,
+                &call_loc,
+                args,
+                &called_fun.get_name_str(),
+                self.mouse_span.end(),
+            ) {
                 log::trace!(
                     " get_called_functions = {:?}",
                     called_fun.get_full_name_str()
@@ -976,17 +1002,22 @@ impl Handler {
             );
             let called_field = called_struct.get_field(*fid);
             let field_name = called_field.get_name();
-            let field_name_str = field_name.display(env.symbol_pool());
+            let field_name_str = field_name.display(env.symbol_pool()).to_string();
 
+            // the fields of a positional struct are named after their position, the model
+            // locates them at their type.
+            if field_name_str.parse::<usize>().is_ok() {
+                self.insert_result(env, called_field.get_loc(), &this_call_loc);
+                return;
+            }
             let called_struct_loc = called_struct.get_loc();
             let call_struct_source = env.get_source(&called_struct_loc);
             if let Ok(call_struct_str) = call_struct_source {
-                if let Some(index) = call_struct_str.find(field_name_str.to_string().as_str()) {
+                if let Some(index) = call_struct_str.find(field_name_str.as_str()) {
                     let field_start = called_struct_loc.span().start()
                         + codespan::ByteOffset(index.try_into().unwrap());
-                    let field_len = field_name_str.to_string().len();
-                    let field_end =
-                        field_start + codespan::ByteOffset(field_len.try_into().unwrap());
+                    let field_end = field_start
+                        + codespan::ByteOffset(field_name_str.len().try_into().unwrap());
                     let field_loc = move_model::model::Loc::new(
                         called_struct_loc.file_id(),
                         codespan::Span::new(field_start, field_end),
@@ -1,7 +1,11 @@
 // Copyright (c) The BitsLab.MoveBit Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{analyzer_handler::*, context::*, utils::path_concat};
+use crate::{
+    analyzer_handler::*,
+    context::*,
+    utils::{call_capture_loc, path_concat},
+};
 use codespan::Span;
 use lsp_server::*;
 use lsp_types::*;
@@ -33,6 +37,20 @@ pub fn on_hover_request(context: &Context, request: &Request) -> lsp_server::Res
         Some(x) => x,
         None => {
             log::error!("project not found:{:?}", fpath.as_path());
+            // the model is not built yet, answer from the package indexes.
+            if let Some(symbol) = context.projects.index_symbol_at(&fpath, &loc) {
+                let hover = Hover {
+                    contents: HoverContents::Scalar(MarkedString::String(symbol.detail.clone())),
+                    range: None,
+                };
+                let r = Response::new_ok(request.id.clone(), serde_json::to_value(hover).unwrap());
+                context
+                    .connection
+                    .sender
+                    .send(Message::Response(r.clone()))
+                    .unwrap();
+                return r;
+            }
             return Response {
                 id: "".to_string().into(),
                 result: Some(serde_json::json!({"msg": "No available project"})),
@@ -567,7 +585,7 @@ impl Handler {
                     self.match_pattern(env, pattern);
                     true
                 },
-                Assign(_, pattern, _) => {
+                Assign(_, pattern, _) | Lambda(_, pattern, _) => {
                     self.match_pattern(env, pattern);
                     true
                 },
@@ -665,16 +683,30 @@ impl Handler {
     }
 
     fn process_call(&mut self, env: &GlobalEnv, expdata: &move_model::ast::ExpData) {
-        if let Call(node_id, MoveFunction(mid, fid), _) = expdata {
-            let this_call_loc = env.get_node_loc(*node_id);
+        if let Call(node_id, MoveFunction(mid, fid), args) = expdata {
+            let called_module = env.get_module(*mid);
+            let called_fun = called_module.get_function(*fid);
+            if let Some(this_call_loc) = call_capture_loc(
+                env,
+                &env.get_node_loc(*node_id),
+                args,
+                &called_fun.get_name_str(),
+                self.mouse_span.end(),
+            ) {
+                if self.capture_items_span_push(&this_call_loc.span()) {
+                    self.result_candidates.push(called_fun.get_header_string());
+                }
+            }
+        }
 
+        // `borrow_global<R>(addr)`, or `R[addr]` in Move 2.
+        if let Call(node_id, BorrowGlobal(..), _) = expdata {
+            let this_call_loc = env.get_node_loc(*node_id);
             if this_call_loc.span().start() < self.mouse_span.end()
                 && self.mouse_span.end() < this_call_loc.span().end()
             {
-                let called_module = env.get_module(*mid);
-                let called_fun = called_module.get_function(*fid);
-                if self.capture_items_span_push(&this_call_loc.span()) {
-                    self.result_candidates.push(called_fun.get_header_string());
+                for inst in &env.get_node_instantiation(*node_id) {
+                    self.process_type(env, &this_call_loc, inst);
                 }
             }
         }
@@ -0,0 +1,394 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Persistent index of the packages, for a fast startup.
+//!
+//! Building the model of a package compiles all of its dependencies, which takes a while with the
+//! Aptos framework. Once a project is loaded the definitions and the reference sites of each of
+//! its packages are written to the cache directory, together with the build settings and the hashes
+//! of the manifests and source files of the package and of its dependencies. At the next start the
+//! index of a package none of them changed for answers goto definition, hover and workspace symbols
+//! until the model of the project is built again.
+
+use crate::{
+    project::{Project, ProjectConfig},
+    utils::{normal_path, path_concat, short_hash, PROJECT_FILE_NAME},
+};
+use lsp_types::{Position, Range, SymbolKind};
+use move_command_line_common::files::FileHash;
+use move_model::{
+    ast::{ExpData, ExpData::*, Operation::*, Pattern},
+    model::{GlobalEnv, Loc, ModuleId, StructId},
+    ty::{Type, TypeDisplayContext},
+};
+use move_package::source_package::manifest_parser::parse_move_manifest_from_file;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Bumped whenever the layout of `PackageIndex` changes, older files are ignored.
+pub const INDEX_VERSION: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexedSymbol {
+    pub name: String,
+    /// Module declaring the symbol, like `0x1::vector`, empty for modules.
+    pub container: String,
+    pub kind: SymbolKind,
+    pub file: PathBuf,
+    pub range: Range,
+    /// Shown on hover.
+    pub detail: String,
+}
+
+impl IndexedSymbol {
+    pub fn qualified_name(&self) -> String {
+        if self.container.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}::{}", self.container, self.name)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IndexedReference {
+    pub file: PathBuf,
+    pub range: Range,
+    /// Qualified name of the symbol referred to.
+    pub target: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PackageIndex {
+    pub version: u32,
+    pub package: PathBuf,
+    /// `ProjectConfig::cache_key` of the settings the package was indexed with.
+    pub config: String,
+    /// Packages the package depends on, so their indexes are loaded along.
+    pub dependencies: Vec<PathBuf>,
+    /// Manifest and source files of the package with the hash of their content.
+    pub files: BTreeMap<PathBuf, String>,
+    /// Manifest and source files of the dependencies with the hash of their content, the
+    /// references of the package resolve into them.
+    pub dependency_files: BTreeMap<PathBuf, String>,
+    pub symbols: Vec<IndexedSymbol>,
+    pub references: Vec<IndexedReference>,
+}
+
+impl PackageIndex {
+    /// Index the modules of the package at `package` in the model of `project`.
+    pub fn build(project: &Project, package: &Path) -> Self {
+        let env = &project.global_env;
+        let package = normal_path(package);
+        // the root package uses the dev dependencies as well.
+        let dependencies = if project.manifest_paths.first() == Some(&package) {
+            project.manifest_paths.iter().skip(1).cloned().collect()
+        } else {
+            manifest_dependencies(&package)
+        };
+        let mut index = PackageIndex {
+            version: INDEX_VERSION,
+            config: project.config.cache_key(),
+            files: source_hashes(&package),
+            dependency_files: dependency_hashes(&dependencies),
+            dependencies,
+            package,
+            ..Default::default()
+        };
+        let display_context = TypeDisplayContext::new(env);
+        for module_env in env.get_modules() {
+            let file = normal_path(&PathBuf::from(env.get_file(module_env.get_loc().file_id())));
+            if !index.files.contains_key(&file) {
+                continue;
+            }
+            let container = module_env.get_full_name_str();
+            index.symbols.push(IndexedSymbol {
+                name: container.clone(),
+                container: String::new(),
+                kind: SymbolKind::MODULE,
+                file: file.clone(),
+                range: project.loc_to_range(&module_env.get_loc()),
+                detail: format!("module {}", container),
+            });
+            for f in module_env.get_functions() {
+                index.symbols.push(IndexedSymbol {
+                    name: f.get_name_str().to_string(),
+                    container: container.clone(),
+                    kind: SymbolKind::FUNCTION,
+                    file: file.clone(),
+                    range: project.loc_to_range(&f.get_loc()),
+                    detail: f.get_header_string(),
+                });
+            }
+            for s in module_env.get_structs() {
+                let fields = s
+                    .get_fields()
+                    .map(|x| {
+                        format!(
+                            "{}: {}",
+                            x.get_name().display(env.symbol_pool()),
+                            x.get_type().display(&display_context)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                let name = s.get_name().display(env.symbol_pool()).to_string();
+                index.symbols.push(IndexedSymbol {
+                    detail: format!("struct {} {{ {} }}", name, fields.join(", ")),
+                    name,
+                    container: container.clone(),
+                    kind: SymbolKind::STRUCT,
+                    file: file.clone(),
+                    range: project.loc_to_range(&s.get_loc()),
+                });
+                let container =
+                    format!("{}::{}", container, s.get_name().display(env.symbol_pool()));
+                for field in s.get_fields() {
+                    index.symbols.push(IndexedSymbol {
+                        name: field.get_name().display(env.symbol_pool()).to_string(),
+                        container: container.clone(),
+                        kind: SymbolKind::FIELD,
+                        file: file.clone(),
+                        range: project.loc_to_range(field.get_loc()),
+                        detail: format!(
+                            "{}: {}",
+                            field.get_name().display(env.symbol_pool()),
+                            field.get_type().display(&display_context)
+                        ),
+                    });
+                }
+            }
+            for c in module_env.get_named_constants() {
+                let name = c.get_name().display(env.symbol_pool()).to_string();
+                index.symbols.push(IndexedSymbol {
+                    detail: format!(
+                        "const {}: {} = {}",
+                        name,
+                        c.get_type().display(&display_context),
+                        env.display(&c.get_value())
+                    ),
+                    name,
+                    container: container.clone(),
+                    kind: SymbolKind::CONSTANT,
+                    file: file.clone(),
+                    range: project.loc_to_range(&c.get_loc()),
+                });
+            }
+            for f in module_env.get_functions() {
+                if let Some(exp) = f.get_def().as_deref() {
+                    exp.visit_pre_order(&mut |e| {
+                        for (loc, target) in exp_references(env, e) {
+                            index.references.push(IndexedReference {
+                                file: file.clone(),
+                                range: project.loc_to_range(&loc),
+                                target,
+                            });
+                        }
+                        true
+                    });
+                }
+            }
+        }
+        index
+    }
+
+    /// Whether the index was built with `config` from the manifests and sources of the package
+    /// and of its dependencies as they are now.
+    pub fn up_to_date(&self, config: &ProjectConfig) -> bool {
+        self.version == INDEX_VERSION
+            && self.config == config.cache_key()
+            && source_hashes(&self.package) == self.files
+            && dependency_hashes(&self.dependencies) == self.dependency_files
+    }
+
+    /// The cached index of the package at `package` built with `config`, if the package and its
+    /// dependencies did not change since.
+    pub fn load(package: &Path, config: &ProjectConfig) -> Option<Self> {
+        let content = std::fs::read_to_string(index_path(package, config)?).ok()?;
+        let index = serde_json::from_str::<PackageIndex>(content.as_str()).ok()?;
+        if index.package != normal_path(package) || !index.up_to_date(config) {
+            return None;
+        }
+        Some(index)
+    }
+
+    pub fn save(&self, config: &ProjectConfig) -> anyhow::Result<()> {
+        let path = index_path(&self.package, config)
+            .ok_or_else(|| anyhow::anyhow!("no cache directory on this platform"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Directory of the index files, under the cache directory of the user.
+pub fn index_dir() -> Option<PathBuf> {
+    Some(
+        dirs_next::cache_dir()?
+            .join("aptos-move-analyzer")
+            .join("index"),
+    )
+}
+
+/// The index file of the package at `package` built with `config`, named after the sha256 of the
+/// path and of the settings so the name stays the same across runs and toolchains.
+pub fn index_path(package: &Path, config: &ProjectConfig) -> Option<PathBuf> {
+    let hash = short_hash(normal_path(package).to_string_lossy().as_ref());
+    let name = package
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Some(index_dir()?.join(format!("{}-{}-{}.json", name, hash, config.cache_key())))
+}
+
+/// Hashes of the manifest and of the source files of the package at `package`.
+pub fn source_hashes(package: &Path) -> BTreeMap<PathBuf, String> {
+    let mut ret = BTreeMap::new();
+    let manifest = package.join(PROJECT_FILE_NAME);
+    if !manifest.exists() {
+        return ret;
+    }
+    for file in std::iter::once(manifest).chain(Project::package_source_files(package)) {
+        if let Ok(content) = std::fs::read_to_string(file.as_path()) {
+            let hash = FileHash::new(content.as_str());
+            let hash: String = hash.0.iter().map(|x| format!("{:02x}", x)).collect();
+            ret.insert(normal_path(&file), hash);
+        }
+    }
+    ret
+}
+
+/// Hashes of the manifests and of the source files of the packages of `packages`.
+fn dependency_hashes(packages: &[PathBuf]) -> BTreeMap<PathBuf, String> {
+    packages.iter().flat_map(|x| source_hashes(x)).collect()
+}
+
+/// Packages the package at `package` depends on, read from the manifests. The dev dependencies
+/// are only used by the root package.
+fn manifest_dependencies(package: &Path) -> Vec<PathBuf> {
+    fn collect(package: &Path, visited: &mut HashSet<PathBuf>, ret: &mut Vec<PathBuf>) {
+        let manifest = match parse_move_manifest_from_file(package) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        for de in manifest.dependencies.values() {
+            let dep = normal_path(&path_concat(package, &de.local));
+            if visited.insert(dep.clone()) {
+                ret.push(dep.clone());
+                collect(&dep, visited, ret);
+            }
+        }
+    }
+    let mut ret = Vec::new();
+    collect(package, &mut HashSet::new(), &mut ret);
+    ret
+}
+
+/// The reference sites of `e` with the qualified name of the symbol each refers to: calls,
+/// struct packs and unpacks, field selections and global storage operations.
+fn exp_references(env: &GlobalEnv, e: &ExpData) -> Vec<(Loc, String)> {
+    let struct_name = |mid: ModuleId, sid: StructId| {
+        let m = env.get_module(mid);
+        let name = m.get_struct(sid).get_name();
+        format!(
+            "{}::{}",
+            m.get_full_name_str(),
+            name.display(env.symbol_pool())
+        )
+    };
+    let mut ret = Vec::new();
+    match e {
+        Call(node_id, MoveFunction(mid, fid), _) => {
+            let m = env.get_module(*mid);
+            let target = m.get_function(*fid).get_name_str();
+            let loc = head_loc(env, &env.get_node_loc(*node_id));
+            ret.push((loc, format!("{}::{}", m.get_full_name_str(), target)));
+        },
+        Call(node_id, Pack(mid, sid), _) => {
+            let loc = head_loc(env, &env.get_node_loc(*node_id));
+            ret.push((loc, struct_name(*mid, *sid)));
+        },
+        Call(node_id, Select(mid, sid, fid), _) => {
+            let field = env.get_module(*mid).get_struct(*sid).get_field(*fid);
+            let target = format!(
+                "{}::{}",
+                struct_name(*mid, *sid),
+                field.get_name().display(env.symbol_pool())
+            );
+            ret.push((env.get_node_loc(*node_id), target));
+        },
+        Call(node_id, Exists(_) | BorrowGlobal(_) | MoveFrom | MoveTo, _) => {
+            // the resource is the type argument of the operation.
+            if let Some(Type::Struct(mid, sid, _)) = env.get_node_instantiation(*node_id).first() {
+                ret.push((env.get_node_loc(*node_id), struct_name(*mid, *sid)));
+            }
+        },
+        Block(_, pattern, _, _) | Assign(_, pattern, _) => {
+            pattern_references(env, pattern, &struct_name, &mut ret);
+        },
+        _ => {},
+    }
+    ret
+}
+
+/// The structs unpacked by `pattern` and by the patterns nested in it.
+fn pattern_references(
+    env: &GlobalEnv,
+    pattern: &Pattern,
+    struct_name: &impl Fn(ModuleId, StructId) -> String,
+    ret: &mut Vec<(Loc, String)>,
+) {
+    match pattern {
+        Pattern::Struct(node_id, q_id, fields) => {
+            let loc = head_loc(env, &env.get_node_loc(*node_id));
+            ret.push((loc, struct_name(q_id.module_id, q_id.id)));
+            for x in fields.iter() {
+                pattern_references(env, x, struct_name, ret);
+            }
+        },
+        Pattern::Tuple(_, items) => {
+            for x in items.iter() {
+                pattern_references(env, x, struct_name, ret);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// `vector::push_back` of a call like `vector::push_back(v, 1)`, the part naming the target.
+fn head_loc(env: &GlobalEnv, loc: &Loc) -> Loc {
+    let len = match env.get_source(loc) {
+        Ok(source) => {
+            let end = source.find(&['(', '<', '{'][..]).unwrap_or(source.len());
+            source[..end].trim_end().len()
+        },
+        Err(_) => return loc.clone(),
+    };
+    Loc::new(
+        loc.file_id(),
+        codespan::Span::new(
+            loc.span().start(),
+            loc.span().start() + codespan::ByteOffset(len as i64),
+        ),
+    )
+}
+
+/// The smallest range containing `pos`.
+pub fn smallest_containing<'a, T>(
+    items: impl Iterator<Item = (&'a Range, T)>,
+    pos: &Position,
+) -> Option<T> {
+    items
+        .filter(|(range, _)| range.start <= *pos && *pos <= range.end)
+        .min_by_key(|(range, _)| {
+            (
+                range.end.line - range.start.line,
+                range.end.character.wrapping_sub(range.start.character),
+            )
+        })
+        .map(|(_, x)| x)
+}
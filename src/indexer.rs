@@ -0,0 +1,69 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Indexing of the workspace folders on a worker thread.
+//!
+//! A `Project` keeps its model behind `Rc`s and can not leave the thread which built it, so the
+//! worker only reads the cached indexes of the packages of a folder and sends them back. The
+//! packages without an up to date index are queued on the main thread, which builds their
+//! project once and indexes it when it is loaded, so opening one of their documents reuses it.
+
+use crate::{index_cache::PackageIndex, project::ProjectConfig, utils::discover_manifests};
+use crossbeam::channel::{unbounded, Receiver, Sender};
+use std::path::{Path, PathBuf};
+
+/// A workspace folder to index, with the build settings when it was added.
+pub struct IndexJob {
+    pub folder: PathBuf,
+    pub config: ProjectConfig,
+}
+
+pub enum IndexEvent {
+    /// The packages found in a workspace folder, an `Indexed` or an `Unindexed` follows for
+    /// each of them.
+    Discovered(Vec<PathBuf>),
+    /// The cached indexes of a package and of its dependencies.
+    Indexed(PathBuf, Vec<PackageIndex>),
+    /// A package without an up to date index, indexed when its project is loaded.
+    Unindexed(PathBuf),
+}
+
+/// Start the worker, the folders sent to the returned sender are indexed in order.
+pub fn spawn_indexer(events: Sender<IndexEvent>) -> Sender<IndexJob> {
+    let (sender, jobs) = unbounded::<IndexJob>();
+    std::thread::spawn(move || run_indexer(jobs, events));
+    sender
+}
+
+fn run_indexer(jobs: Receiver<IndexJob>, events: Sender<IndexEvent>) {
+    for job in jobs {
+        let manifests = discover_manifests(&job.folder);
+        if events
+            .send(IndexEvent::Discovered(manifests.clone()))
+            .is_err()
+        {
+            return;
+        }
+        for mani in manifests {
+            let event = match cached_indexes(&mani, &job.config) {
+                Some(indexes) => IndexEvent::Indexed(mani, indexes),
+                None => IndexEvent::Unindexed(mani),
+            };
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Cached indexes of the package at `mani` and of its dependencies built with `config`, `None`
+/// when the package or its dependencies changed since it was indexed.
+pub fn cached_indexes(mani: &Path, config: &ProjectConfig) -> Option<Vec<PackageIndex>> {
+    let index = PackageIndex::load(mani, config)?;
+    let dependencies: Vec<_> = index
+        .dependencies
+        .iter()
+        .filter_map(|x| PackageIndex::load(x, config))
+        .collect();
+    Some(std::iter::once(index).chain(dependencies).collect())
+}
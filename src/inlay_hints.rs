@@ -312,7 +312,7 @@ impl Handler {
                 }
                 true
             },
-            Block(_, pattern, _, _) => {
+            Block(_, pattern, _, _) | Lambda(_, pattern, _) => {
                 for (sym_node_id, sym_symbol) in pattern.vars() {
                     let mut sym_loc = env.get_node_loc(sym_node_id);
                     sym_loc = move_model::model::Loc::new(
@@ -33,23 +33,35 @@ macro_rules! impl_convert_loc {
 }
 
 pub mod analyzer_handler;
+pub mod code_action;
 pub mod completion;
 pub mod context;
+pub mod dependency_cache;
 pub mod diagnostics;
+pub mod folding_range;
 pub mod goto_definition;
 pub mod hover;
+pub mod index_cache;
+pub mod indexer;
 pub mod inlay_hints;
 pub mod item;
+pub mod manifest_diagnostics;
 pub mod multiproject;
+pub mod progress;
 pub mod project;
 pub mod project_manager;
 pub mod references;
+pub mod selection_range;
+pub mod spec_diagnostics;
 pub mod utils;
 
+pub mod move_coverage;
 pub mod move_generate_spec;
 pub mod move_generate_spec_file;
+pub mod move_generate_spec_merge;
 pub mod move_generate_spec_sel;
 pub mod move_generate_spec_utils;
+pub mod move_unit_test;
 pub mod movefmt;
 pub mod symbols;
 pub mod type_display_for_spec;
@@ -0,0 +1,229 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Diagnostics of `Move.toml`.
+//!
+//! The manifests of a package and of its dependencies are checked for the problems that keep a
+//! project from loading: manifests which do not parse, dependencies which can not be found,
+//! named addresses assigned differently by two packages and named addresses never assigned.
+//! Each diagnostic points at the line of `Move.toml` to fix.
+
+use crate::{
+    project::ProjectConfig,
+    utils::{normal_path, path_concat, LineIndex, PROJECT_FILE_NAME},
+};
+use lsp_types::{Diagnostic, DiagnosticSeverity, Range};
+use move_core_types::account_address::AccountAddress;
+use move_package::source_package::{manifest_parser::*, parsed_manifest::SourceManifest};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Diagnostics of the manifests reachable from the package at `root`, built with `config`.
+pub fn manifest_diagnostics(root: &Path, config: &ProjectConfig) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut checker = ManifestChecker {
+        // the package system uses the dev sections in test mode too.
+        dev_mode: config.dev_mode || config.test_mode,
+        ..Default::default()
+    };
+    checker.check_manifest(root);
+    checker.check_addresses(config);
+    checker.result
+}
+
+#[derive(Default)]
+struct ManifestChecker {
+    /// Whether `[dev-addresses]` and `[dev-dependencies]` are used.
+    dev_mode: bool,
+    visited: HashSet<PathBuf>,
+    /// (Move.toml path, content, manifest), the root package last.
+    manifests: Vec<(PathBuf, String, SourceManifest)>,
+    result: HashMap<Url, Vec<Diagnostic>>,
+}
+
+impl ManifestChecker {
+    fn check_manifest(&mut self, dir: &Path) {
+        let dir = normal_path(dir);
+        if !self.visited.insert(dir.clone()) {
+            return;
+        }
+        let toml_path = dir.join(PROJECT_FILE_NAME);
+        let content = match std::fs::read_to_string(&toml_path) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        let manifest = match parse_move_manifest_from_file(&dir) {
+            Ok(x) => x,
+            Err(err) => {
+                self.add(
+                    &toml_path,
+                    Range::default(),
+                    format!("parse manifest failed, {:#}", err),
+                );
+                return;
+            },
+        };
+        let dev_dependencies = if self.dev_mode {
+            manifest.dev_dependencies.iter().collect()
+        } else {
+            vec![]
+        };
+        for (dep_name, de) in manifest.dependencies.iter().chain(dev_dependencies) {
+            let dep_dir = path_concat(dir.as_path(), &de.local);
+            if dep_dir.join(PROJECT_FILE_NAME).exists() {
+                self.check_manifest(&dep_dir);
+                continue;
+            }
+            let msg = if de.git_info.is_some() {
+                format!(
+                    "git dependency '{}' is not in the local package cache at {:?}, \
+                     run 'aptos move compile --fetch-deps-only' to fetch it",
+                    dep_name, dep_dir
+                )
+            } else if de.node_info.is_some() {
+                format!(
+                    "on-chain dependency '{}' is not in the local package cache at {:?}, \
+                     run 'aptos move compile --fetch-deps-only' to fetch it",
+                    dep_name, dep_dir
+                )
+            } else {
+                format!(
+                    "dependency '{}' not found, there is no {} in {:?}",
+                    dep_name, PROJECT_FILE_NAME, dep_dir
+                )
+            };
+            let range = key_range(
+                &content,
+                &["dependencies", "dev-dependencies"],
+                dep_name.as_str(),
+            );
+            self.add(&toml_path, range, msg);
+        }
+        self.manifests.push((toml_path, content, manifest));
+    }
+
+    fn check_addresses(&mut self, config: &ProjectConfig) {
+        // name -> (manifest index, address).
+        let mut assigned: BTreeMap<String, Vec<(usize, AccountAddress)>> = BTreeMap::new();
+        let mut unassigned: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, (_, _, manifest)) in self.manifests.iter().enumerate() {
+            for (name, addr) in manifest.addresses.iter().flat_map(|x| x.iter()) {
+                match addr {
+                    Some(addr) => assigned
+                        .entry(name.to_string())
+                        .or_default()
+                        .push((index, *addr)),
+                    None => unassigned.entry(name.to_string()).or_default().push(index),
+                }
+            }
+        }
+        // the dev addresses of the root package and the addresses set by the client assign the
+        // names the manifests leave unassigned.
+        let mut dev_assigned: HashSet<String> =
+            config.additional_named_addresses().into_keys().collect();
+        if self.dev_mode {
+            dev_assigned.extend(
+                self.manifests
+                    .last()
+                    .and_then(|(_, _, x)| x.dev_address_assignments.as_ref())
+                    .into_iter()
+                    .flat_map(|x| x.keys().map(|x| x.to_string())),
+            );
+        }
+
+        let mut diags = vec![];
+        for (name, values) in assigned.iter() {
+            for (index, addr) in values.iter() {
+                if let Some((other, other_addr)) = values.iter().find(|(_, x)| x != addr) {
+                    diags.push((
+                        *index,
+                        name.clone(),
+                        format!(
+                            "named address '{}' is assigned {} here, but {} in package '{}'",
+                            name,
+                            addr.to_hex_literal(),
+                            other_addr.to_hex_literal(),
+                            self.manifests[*other].2.package.name
+                        ),
+                    ));
+                }
+            }
+        }
+        for (name, indexes) in unassigned.iter() {
+            if assigned.contains_key(name) || dev_assigned.contains(name) {
+                continue;
+            }
+            for index in indexes.iter() {
+                diags.push((
+                    *index,
+                    name.clone(),
+                    format!(
+                        "named address '{}' is not assigned, assign it in [addresses] or \
+                         [dev-addresses] of the root package",
+                        name
+                    ),
+                ));
+            }
+        }
+        for (index, name, msg) in diags.into_iter() {
+            let (toml_path, content, _) = &self.manifests[index];
+            let range = key_range(content, &["addresses"], name.as_str());
+            let toml_path = toml_path.clone();
+            self.add(&toml_path, range, msg);
+        }
+    }
+
+    fn add(&mut self, toml_path: &Path, range: Range, message: String) {
+        let url = match Url::from_file_path(toml_path) {
+            Ok(x) => x,
+            Err(_) => return,
+        };
+        self.result.entry(url).or_default().push(Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("move-manifest".to_string()),
+            message,
+            ..Default::default()
+        });
+    }
+}
+
+/// Range of the line declaring `key` in one of the `sections` of a toml file, either
+/// `key = ...` inside of `[section]` or a `[section.key]` header.
+pub fn key_range(content: &str, sections: &[&str], key: &str) -> Range {
+    let index = LineIndex::new(content);
+    let mut section = "";
+    let mut section_line = None;
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+        let trimmed = line.trim();
+        let start = line_offset + line.len() - line.trim_start().len();
+        let range = Range::new(index.position(start), index.position(start + trimmed.len()));
+        if let Some(header) = trimmed.strip_prefix('[').and_then(|x| x.split(']').next()) {
+            section = header.trim();
+            if sections.contains(&section) && section_line.is_none() {
+                section_line = Some(range);
+            }
+            if sections
+                .iter()
+                .any(|s| section == format!("{}.{}", s, key).as_str())
+            {
+                return range;
+            }
+            continue;
+        }
+        if !sections.contains(&section) {
+            continue;
+        }
+        if let Some((k, _)) = trimmed.split_once('=') {
+            if k.trim().trim_matches('"') == key {
+                return range;
+            }
+        }
+    }
+    section_line.unwrap_or_default()
+}
@@ -0,0 +1,266 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Shows the code not covered by the unit tests of a package.
+//!
+//! `aptos move test --coverage` leaves a `.coverage_map.mvcov` in the package directory and the
+//! compiled modules together with their source maps under `build/<package>`. The coverage map is
+//! keyed by bytecode offsets, the source maps bring these back to source locations, and the
+//! uncovered parts are published as `HINT` diagnostics.
+
+use crate::{context::Context, utils::discover_manifest_and_kind};
+use crossbeam::channel::Sender;
+use lsp_server::{ErrorCode, Message, Request, Response};
+use lsp_types::{Diagnostic, DiagnosticSeverity, ExecuteCommandParams, Position, Range};
+use move_binary_format::file_format::CompiledModule;
+use move_bytecode_source_map::utils::source_map_from_file;
+use move_command_line_common::files::FileHash;
+use move_coverage::{
+    coverage_map::CoverageMap,
+    source_coverage::{SourceCoverageBuilder, StringSegment},
+};
+use move_package::source_package::{layout::SourcePackageLayout, manifest_parser::*};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use url::Url;
+use walkdir::WalkDir;
+
+/// `workspace/executeCommand` command which shows the coverage of a package.
+pub const SHOW_COVERAGE_COMMAND: &str = "aptos-move-analyzer.show_coverage";
+
+/// File written by `aptos move test --coverage`.
+pub const COVERAGE_MAP_NAME: &str = ".coverage_map.mvcov";
+
+pub type CoverageSender = Arc<Mutex<Sender<CoverageReport>>>;
+
+#[derive(Clone, Deserialize)]
+pub struct ShowCoverageArgs {
+    /// Any file inside the package.
+    pub fpath: String,
+    /// Remove the coverage diagnostics of the package instead.
+    #[serde(default)]
+    pub clear: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CoverageReport {
+    pub manifest: PathBuf,
+    /// Uncovered code, by file.
+    pub uncovered: HashMap<Url, Vec<Range>>,
+    /// Modules whose source changed since the build, these are not reported.
+    pub stale_modules: Vec<String>,
+}
+
+impl CoverageReport {
+    pub fn diagnostics(&self) -> HashMap<Url, Vec<Diagnostic>> {
+        self.uncovered
+            .iter()
+            .map(|(url, ranges)| {
+                let diags = ranges
+                    .iter()
+                    .map(|range| Diagnostic {
+                        range: *range,
+                        severity: Some(DiagnosticSeverity::HINT),
+                        source: Some("move-coverage".to_string()),
+                        message: "not covered by unit tests".to_string(),
+                        ..Default::default()
+                    })
+                    .collect();
+                (url.clone(), diags)
+            })
+            .collect()
+    }
+}
+
+pub fn on_show_coverage(
+    context: &Context,
+    request: &Request,
+    parameters: ExecuteCommandParams,
+    coverage_sender: CoverageSender,
+) {
+    let args = match parameters
+        .arguments
+        .into_iter()
+        .next()
+        .map(serde_json::from_value::<ShowCoverageArgs>)
+    {
+        Some(Ok(x)) => x,
+        _ => {
+            send_err(
+                context,
+                request,
+                "expected arguments {fpath, clear}.".to_string(),
+            );
+            return;
+        },
+    };
+    let fpath = PathBuf::from_str(args.fpath.as_str()).unwrap();
+    let (mani, _) = match discover_manifest_and_kind(&fpath) {
+        Some(x) => x,
+        None => {
+            send_err(context, request, "not a move project.".to_string());
+            return;
+        },
+    };
+    // reading the coverage map and the source maps takes a while for a big package, and the
+    // main loop has to be free to receive the report.
+    let sender = context.connection.sender.clone();
+    let id = request.id.clone();
+    std::thread::spawn(move || {
+        let report = if args.clear {
+            CoverageReport {
+                manifest: mani,
+                ..Default::default()
+            }
+        } else {
+            match load_coverage(&mani) {
+                Ok(x) => x,
+                Err(err) => {
+                    let r = Response::new_err(
+                        id,
+                        ErrorCode::UnknownErrorCode as i32,
+                        format!("{:?}", err),
+                    );
+                    let _ = sender.send(Message::Response(r));
+                    return;
+                },
+            }
+        };
+        let r = Response::new_ok(
+            id,
+            serde_json::json!({
+                "uncovered": report.uncovered.values().map(|x| x.len()).sum::<usize>(),
+                "stale_modules": report.stale_modules,
+            }),
+        );
+        let _ = sender.send(Message::Response(r));
+        coverage_sender.lock().unwrap().send(report).unwrap();
+    });
+}
+
+/// Load the coverage map of the package at `mani` and compute the uncovered source ranges.
+pub fn load_coverage(mani: &Path) -> anyhow::Result<CoverageReport> {
+    let coverage_path = mani.join(COVERAGE_MAP_NAME);
+    if !coverage_path.exists() {
+        return Err(anyhow::anyhow!(
+            "{} not found, run `aptos move test --coverage` first.",
+            coverage_path.display()
+        ));
+    }
+    let coverage_map = CoverageMap::from_binary_file(&coverage_path)?;
+    let manifest = parse_move_manifest_from_file(mani)?;
+    let build_dir = mani
+        .join(SourcePackageLayout::Build.path())
+        .join(manifest.package.name.as_str());
+    let sources = source_files_by_hash(mani);
+
+    let mut report = CoverageReport {
+        manifest: mani.to_path_buf(),
+        ..Default::default()
+    };
+    for item in WalkDir::new(build_dir.join("bytecode_modules")).max_depth(1) {
+        let item = match item {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let module_path = item.path();
+        if module_path.extension().and_then(|x| x.to_str()) != Some("mv") {
+            continue;
+        }
+        let source_map_path = build_dir
+            .join("source_maps")
+            .join(module_path.file_name().unwrap())
+            .with_extension("mvsm");
+        let module = CompiledModule::deserialize(&std::fs::read(module_path)?)?;
+        let source_map = source_map_from_file(&source_map_path)?;
+        let file_hash = source_map.definition_location.file_hash();
+        let source_path = match sources.get(&file_hash) {
+            Some(x) => x,
+            None => {
+                // the source has been modified after the coverage run.
+                report
+                    .stale_modules
+                    .push(module.self_id().short_str_lossless());
+                continue;
+            },
+        };
+        let url = match Url::from_file_path(source_path) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let coverage = SourceCoverageBuilder::new(&module, &coverage_map, &source_map)
+            .compute_source_coverage(source_path);
+        let ranges = report.uncovered.entry(url).or_default();
+        for (line, segments) in coverage.source_lines.iter().enumerate() {
+            ranges.extend(uncovered_ranges(line as u32, segments));
+        }
+    }
+    report.uncovered.retain(|_, v| !v.is_empty());
+    Ok(report)
+}
+
+/// Ranges of the uncovered segments of one line, leading and trailing whitespace excluded.
+pub fn uncovered_ranges(line: u32, segments: &[StringSegment]) -> Vec<Range> {
+    let mut ret: Vec<Range> = vec![];
+    let mut col = 0;
+    let mut prev_uncovered = false;
+    for s in segments {
+        let (text, covered) = match s {
+            StringSegment::Covered(x) => (x, true),
+            StringSegment::Uncovered(x) => (x, false),
+        };
+        let len = text.chars().count() as u32;
+        let trimmed = text.trim();
+        if !covered && !trimmed.is_empty() {
+            let start = col + (text.chars().count() - text.trim_start().chars().count()) as u32;
+            let end = start + trimmed.chars().count() as u32;
+            match ret.last_mut() {
+                // merge with the previous segment.
+                Some(last) if prev_uncovered => last.end.character = end,
+                _ => ret.push(Range::new(
+                    Position::new(line, start),
+                    Position::new(line, end),
+                )),
+            }
+            prev_uncovered = true;
+        } else if covered {
+            prev_uncovered = false;
+        }
+        col += len;
+    }
+    ret
+}
+
+fn source_files_by_hash(mani: &Path) -> HashMap<FileHash, PathBuf> {
+    let mut ret = HashMap::new();
+    for item in WalkDir::new(mani) {
+        let item = match item {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let path = item.path();
+        if path.starts_with(mani.join(SourcePackageLayout::Build.path()))
+            || path.extension().and_then(|x| x.to_str()) != Some("move")
+        {
+            continue;
+        }
+        if let Ok(content) = std::fs::read_to_string(path) {
+            ret.insert(FileHash::new(content.as_str()), path.to_path_buf());
+        }
+    }
+    ret
+}
+
+fn send_err(context: &Context, request: &Request, msg: String) {
+    let r = Response::new_err(request.id.clone(), ErrorCode::UnknownErrorCode as i32, msg);
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
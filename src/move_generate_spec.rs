@@ -2,19 +2,25 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    move_generate_spec_utils::{BinOPReason, SpecExpItem},
-    type_display_for_spec::TypeDisplayForSpec,
+    move_generate_spec_utils::{
+        param_arg_str, substitute_params, BinOPReason, CalleeScope, ResourceAccessReason,
+        ResourceRef, SpecExpItem,
+    },
+    type_display_for_spec::{SpecUseDecls, TypeDisplayForSpec},
 };
 use move_model::{
     ast::{
-        Exp as MoveModelExp, ExpData as MoveModelExpData, ModuleName,
-        Operation as MoveModelOperation,
+        Exp as MoveModelExp, ExpData as MoveModelExpData, Operation as MoveModelOperation,
+        Pattern as MoveModelPattern,
     },
-    model::{FunctionEnv, GlobalEnv, ModuleEnv, StructEnv},
+    model::{FunId, FunctionEnv, GlobalEnv, ModuleEnv, QualifiedId, StructEnv},
     symbol::Symbol,
-    ty::{Type as MoveModelType, TypeDisplayContext},
+    ty::{PrimitiveType, Type as MoveModelType, TypeDisplayContext},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
 };
-use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct StructSpecGenerator {
@@ -30,7 +36,7 @@ impl StructSpecGenerator {
         self.result
     }
 
-    pub(crate) fn generate(&mut self, x: &StructEnv) {
+    pub(crate) fn generate(&mut self, x: &StructEnv, using_module_map: &SpecUseDecls) {
         self.result.push_str(
             format!(
                 "{}spec {}",
@@ -40,32 +46,107 @@ impl StructSpecGenerator {
             .as_str(),
         );
         self.result.push_str("{\n");
+        for (invariant, holds) in StructSpecGenerator::collect_field_invariants(x) {
+            let field = invariant.field().display(x.symbol_pool()).to_string();
+            // left for the user to check when the field may change after the pack.
+            self.result.push_str(
+                format!(
+                    "{}{}invariant {};\n",
+                    indent(2),
+                    if holds { "" } else { "// " },
+                    invariant.display(field.as_str(), x.module_env.env, using_module_map)
+                )
+                .as_str(),
+            );
+        }
         self.result.push_str(format!("{}}}\n", indent(1)).as_str())
     }
+
+    /// `invariant [suspendable] forall addr: address where exists<T>(addr): ..` for the resource
+    /// `x`, to go in the `spec module` block. It is left commented out for the user to complete
+    /// when nothing is known to hold for the fields of `x`.
+    pub(crate) fn generate_global_invariant(
+        x: &StructEnv,
+        using_module_map: &SpecUseDecls,
+    ) -> Option<String> {
+        if !x.get_abilities().has_key() || !x.get_type_parameters().is_empty() {
+            return None;
+        }
+        let name = x.get_name().display(x.symbol_pool()).to_string();
+        let invariants = StructSpecGenerator::collect_field_invariants(x);
+        let condition = |holds: bool| {
+            invariants
+                .iter()
+                .filter(|(_, known)| *known == holds)
+                .map(|(invariant, _)| {
+                    let field = format!(
+                        "global<{}>(addr).{}",
+                        name,
+                        invariant.field().display(x.symbol_pool())
+                    );
+                    invariant.display(field.as_str(), x.module_env.env, using_module_map)
+                })
+                .collect::<Vec<_>>()
+                .join(" && ")
+        };
+        let head = format!(
+            "invariant [suspendable] forall addr: address where exists<{}>(addr):",
+            name
+        );
+        let (known, unsure) = (condition(true), condition(false));
+        Some(if !known.is_empty() {
+            format!("{}{} {};\n", indent(2), head, known)
+        } else if !unsure.is_empty() {
+            format!("{}// {} {};\n", indent(2), head, unsure)
+        } else {
+            format!("{}// {} ...;\n", indent(2), head)
+        })
+    }
 }
 
 #[derive(Default)]
 pub struct FunSpecGenerator {
     result: String,
+    /// The function whose spec is generated, followed by the helper functions being inlined.
+    pub(crate) callee_stack: RefCell<Vec<QualifiedId<FunId>>>,
+    /// Locals bound to a mutable reference of a global resource, in the function being visited.
+    pub(crate) resource_refs: RefCell<HashMap<Symbol, ResourceRef>>,
+}
+
+/// What the types of a spec are printed against.
+struct SpecEmitContext<'a> {
+    env: &'a GlobalEnv,
+    module_env: &'a ModuleEnv<'a>,
+    using_module_map: &'a SpecUseDecls,
+    display_context: TypeDisplayContext<'a>,
+    /// Parameters of the function the spec is for.
+    params: Vec<String>,
 }
 
 pub fn generate_fun_spec_zx(
     global_env: &GlobalEnv,
     module_env: &ModuleEnv,
     f: &FunctionEnv,
-    using_module_map: &HashMap<ModuleName, Vec<Symbol>>,
+    using_module_map: &SpecUseDecls,
 ) -> String {
     let mut g = FunSpecGenerator::new();
     g.generate_zx(global_env, module_env, f, using_module_map);
     g.get_result_string()
 }
 
-pub fn genrate_struct_spec(s: &StructEnv) -> String {
+pub fn genrate_struct_spec(s: &StructEnv, using_module_map: &SpecUseDecls) -> String {
     let mut g = StructSpecGenerator::new();
-    g.generate(s);
+    g.generate(s, using_module_map);
     g.get_result_string()
 }
 
+pub fn generate_struct_global_invariant(
+    s: &StructEnv,
+    using_module_map: &SpecUseDecls,
+) -> Option<String> {
+    StructSpecGenerator::generate_global_invariant(s, using_module_map)
+}
+
 impl FunSpecGenerator {
     pub(crate) fn new() -> Self {
         Self::default()
@@ -80,9 +161,19 @@ impl FunSpecGenerator {
         global_env: &GlobalEnv,
         module_env: &ModuleEnv,
         f: &FunctionEnv,
-        using_module_map: &HashMap<ModuleName, Vec<Symbol>>,
+        using_module_map: &SpecUseDecls,
     ) {
-        let display_context = f.get_type_display_ctx();
+        let ctx = SpecEmitContext {
+            env: global_env,
+            module_env,
+            using_module_map,
+            display_context: f.get_type_display_ctx(),
+            params: f
+                .get_parameters()
+                .iter()
+                .map(|p| p.0.display(f.symbol_pool()).to_string())
+                .collect(),
+        };
         self.result
             .push_str(format!("{}spec {}", indent(1), f.get_name_str()).as_str());
 
@@ -109,7 +200,7 @@ impl FunSpecGenerator {
                 self.result.push_str(": ");
                 let display_context_para = TypeDisplayForSpec {
                     type_: &para.1,
-                    context: &display_context,
+                    context: &ctx.display_context,
                     module_env,
                     using_module_map,
                 };
@@ -125,7 +216,7 @@ impl FunSpecGenerator {
         let return_type = f.get_result_type();
         let display_context_return = TypeDisplayForSpec {
             type_: &return_type,
-            context: &display_context,
+            context: &ctx.display_context,
             module_env,
             using_module_map,
         };
@@ -140,27 +231,87 @@ impl FunSpecGenerator {
         }
         self.result.push_str(return_type_string.as_str());
         self.result.push_str("{\n");
-        let assert = Self::generate_body_zx(self, f, global_env);
+        let assert = Self::generate_body_zx(self, f, &ctx);
         self.result.push_str(assert.as_str());
         self.result.push_str(format!("{}}}\n", indent(1)).as_str());
     }
 
-    fn generate_body_zx(&self, f: &FunctionEnv, global_env: &GlobalEnv) -> String {
-        let mut statements = String::new();
+    fn generate_body_zx(&self, f: &FunctionEnv, ctx: &SpecEmitContext) -> String {
+        let mut statements = SpecStatements::default();
         if let Some(exp) = f.get_def().as_deref() {
-            FunSpecGenerator::try_emit_exp_zx(self, &mut statements, exp, global_env);
+            self.callee_stack.borrow_mut().push(f.get_qualified_id());
+            FunSpecGenerator::try_emit_exp_zx(self, &mut statements, exp, ctx);
+            self.callee_stack.borrow_mut().pop();
         } else {
             log::trace!("body is none");
-            return statements;
         }
 
-        statements
+        statements.render()
+    }
+}
+
+/// The statements of a spec in the order they are written, one per line.
+#[derive(Default)]
+struct SpecStatements {
+    lines: Vec<String>,
+    /// Clauses of `lines`, without the indentation.
+    clauses: HashSet<String>,
+}
+
+impl SpecStatements {
+    /// Add `line` even when the spec already has it.
+    fn push_str(&mut self, line: &str) {
+        self.clauses.insert(line.trim().to_string());
+        self.lines.push(line.to_string());
+    }
+
+    /// Add `statement` unless the spec already has the same clause, a resource is often
+    /// accessed more than once.
+    fn push(&mut self, statement: String) {
+        if self.clauses.insert(statement.trim().to_string()) {
+            self.lines.push(statement);
+        }
+    }
+
+    fn any_line(&self, f: impl Fn(&str) -> bool) -> bool {
+        self.lines.iter().any(|x| f(x.as_str()))
+    }
+
+    fn retain_lines(&mut self, f: impl Fn(&str) -> bool) {
+        let clauses = &mut self.clauses;
+        self.lines.retain(|x| {
+            let keep = f(x.as_str());
+            if !keep {
+                clauses.remove(x.trim());
+            }
+            keep
+        });
+    }
+
+    fn render(&self) -> String {
+        self.lines.concat()
     }
 }
 
 impl FunSpecGenerator {
-    fn try_emit_exp_zx(&self, statements: &mut String, exp: &MoveModelExp, env: &GlobalEnv) {
-        let items = FunSpecGenerator::collect_spec_exp_zx(self, exp, env);
+    fn try_emit_exp_zx(
+        &self,
+        statements: &mut SpecStatements,
+        exp: &MoveModelExp,
+        ctx: &SpecEmitContext,
+    ) {
+        let items = FunSpecGenerator::collect_spec_exp_zx(self, exp, ctx.env);
+        FunSpecGenerator::emit_items(statements, &items, ctx, None);
+    }
+
+    /// Write the conditions of `items`, which are in a helper function when `scope` is given.
+    fn emit_items(
+        statements: &mut SpecStatements,
+        items: &[SpecExpItem],
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) {
+        let env = ctx.env;
         let display_context = &TypeDisplayContext::new(env);
         for item in items.iter() {
             match item {
@@ -171,19 +322,17 @@ impl FunSpecGenerator {
                 } => {
                     let _left_node_id = left.as_ref().node_id();
                     let _right_node_id = right.as_ref().node_id();
-                    let _left_node_loc = env.get_node_loc(_left_node_id);
-                    let _right_node_loc = env.get_node_loc(_right_node_id);
                     let _left_node_type = env.get_node_type(_left_node_id);
                     let _right_node_type = env.get_node_type(_right_node_id);
 
-                    let _left_exp_str = match env.get_source(&_left_node_loc) {
-                        Ok(x) => x,
-                        Err(_) => continue,
+                    let _left_exp_str = match FunSpecGenerator::exp_source(left, env, scope) {
+                        Some(x) => x,
+                        None => continue,
                     };
 
-                    let _right_exp_str = match env.get_source(&_right_node_loc) {
-                        Ok(x) => x,
-                        Err(_) => continue,
+                    let _right_exp_str = match FunSpecGenerator::exp_source(right, env, scope) {
+                        Some(x) => x,
+                        None => continue,
                     };
 
                     if *reason != BinOPReason::DivByZero
@@ -251,12 +400,12 @@ impl FunSpecGenerator {
                             | MoveModelOperation::Le
                             | MoveModelOperation::Ge => {
                                 FunSpecGenerator::handle_binop_exp(
-                                    statements, if_exp, op, abort_exp, env,
+                                    statements, if_exp, op, abort_exp, env, scope,
                                 );
                             },
                             MoveModelOperation::MoveFunction(_, _) => {
                                 FunSpecGenerator::handle_funcop_exp(
-                                    statements, if_exp, abort_exp, env,
+                                    statements, if_exp, abort_exp, env, scope,
                                 );
                             },
                             _ => {},
@@ -271,32 +420,372 @@ impl FunSpecGenerator {
                         Err(_) => continue,
                     };
 
-                    statements.push_str(format!("{}let ", indent(2)).as_str());
-                    statements.push_str(_left_exp_str);
-
-                    statements.push_str(" = ");
                     let _right_node_id = right.as_ref().node_id();
                     let _right_node_loc = env.get_node_loc(_right_node_id);
                     let _right_exp_str = match env.get_source(&_right_node_loc) {
                         Ok(x) => x,
                         _ => continue,
                     };
-                    statements.push_str(_right_exp_str);
-                    statements.push_str(";\n");
+                    statements.push_str(
+                        format!("{}let {} = {};\n", indent(2), _left_exp_str, _right_exp_str)
+                            .as_str(),
+                    );
+                },
+                SpecExpItem::ResourceAccess { reason, ty, addr } => {
+                    FunSpecGenerator::handle_resource_access(
+                        statements, *reason, ty, addr, ctx, scope,
+                    );
+                },
+                SpecExpItem::ResourceWrite {
+                    resource,
+                    field,
+                    value,
+                } => {
+                    FunSpecGenerator::handle_resource_write(
+                        statements, resource, field, value, ctx, scope,
+                    );
+                },
+                SpecExpItem::Emit {
+                    msg,
+                    handle,
+                    resource,
+                } => {
+                    FunSpecGenerator::handle_emit(
+                        statements,
+                        msg,
+                        handle.as_ref(),
+                        resource.as_ref(),
+                        ctx,
+                        scope,
+                    );
+                },
+                SpecExpItem::Result { exp } => {
+                    if scope.is_some() {
+                        continue;
+                    }
+                    if let Some(x) = FunSpecGenerator::exp_source(exp, env, None) {
+                        statements
+                            .push_str(format!("{}ensures result == {};\n", indent(2), x).as_str());
+                    }
+                },
+                SpecExpItem::Callee { .. } => {
+                    FunSpecGenerator::handle_callee(statements, item, ctx, scope);
                 },
-                SpecExpItem::BorrowGlobalMut { .. } => {},
                 SpecExpItem::TypeName { .. } => {},
                 SpecExpItem::TypeOf { .. } => {},
             }
         }
     }
 
+    /// The source of `exp`, written in the spec of the caller when `exp` is in a helper function.
+    fn exp_source(
+        exp: &MoveModelExp,
+        env: &GlobalEnv,
+        scope: Option<&CalleeScope>,
+    ) -> Option<String> {
+        let source = env
+            .get_source(&env.get_node_loc(exp.as_ref().node_id()))
+            .ok()?;
+        let scope = match scope {
+            Some(x) => x,
+            None => return Some(source.to_string()),
+        };
+        // the locals of a helper function are only known by the `let` they are bound with.
+        let pool = env.symbol_pool();
+        if exp
+            .free_vars()
+            .iter()
+            .any(|x| !scope.params.contains_key(&x.display(pool).to_string()))
+        {
+            return None;
+        }
+        Some(substitute_params(source, &scope.params))
+    }
+
+    /// The source of `exp` with the locals of `extra` replaced, written in the spec of the caller
+    /// when `exp` is in a helper function.
+    fn exp_source_with(
+        exp: &MoveModelExp,
+        env: &GlobalEnv,
+        scope: Option<&CalleeScope>,
+        extra: &HashMap<String, String>,
+    ) -> Option<String> {
+        match scope {
+            None => FunSpecGenerator::exp_source(exp, env, None)
+                .map(|x| substitute_params(x.as_str(), extra)),
+            Some(scope) => {
+                let mut scope = scope.clone();
+                scope
+                    .params
+                    .extend(extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+                FunSpecGenerator::exp_source(exp, env, Some(&scope))
+            },
+        }
+    }
+
+    /// The type and the address of a global resource, as written in the spec.
+    fn resource_str(
+        ty: &MoveModelType,
+        addr: &MoveModelExp,
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) -> Option<(String, String)> {
+        if !FunSpecGenerator::is_support_exp(addr, ctx.env) {
+            return None;
+        }
+        let addr_str = param_arg_str(FunSpecGenerator::exp_source(addr, ctx.env, scope)?.as_str());
+        let ty = match scope {
+            Some(scope) => scope.instantiate(ty),
+            None => ty.clone(),
+        };
+        let ty_str = TypeDisplayForSpec {
+            type_: &ty,
+            context: &ctx.display_context,
+            module_env: ctx.module_env,
+            using_module_map: ctx.using_module_map,
+        }
+        .to_string();
+        Some((ty_str, addr_str))
+    }
+
+    /// `local` of `resource`, replaced by `global` in the expressions written through it.
+    fn resource_local(
+        resource: &ResourceRef,
+        global: String,
+        env: &GlobalEnv,
+    ) -> HashMap<String, String> {
+        let mut ret = HashMap::new();
+        if let Some(local) = resource.local {
+            ret.insert(local.display(env.symbol_pool()).to_string(), global);
+        }
+        ret
+    }
+
+    /// `signer::address_of`, named the way the `use` declarations of the module allow.
+    fn signer_address_of(ctx: &SpecEmitContext) -> String {
+        let env = ctx.env;
+        let pool = env.symbol_pool();
+        let module = env
+            .get_modules()
+            .find(|x| x.get_full_name_str() == "0x1::signer")
+            .map(|x| x.get_name().clone());
+        if let Some(module) = module {
+            let address_of = pool.make("address_of");
+            if let Some(x) = ctx
+                .using_module_map
+                .members
+                .get(&(module.clone(), address_of))
+            {
+                return x.display(pool).to_string();
+            }
+            if let Some(x) = ctx.using_module_map.modules.get(&module) {
+                return format!("{}::address_of", x.display(pool));
+            }
+        }
+        "0x1::signer::address_of".to_string()
+    }
+
+    fn handle_resource_access(
+        statements: &mut SpecStatements,
+        reason: ResourceAccessReason,
+        ty: &MoveModelType,
+        addr: &MoveModelExp,
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) {
+        let (ty_str, addr_str) = match FunSpecGenerator::resource_str(ty, addr, ctx, scope) {
+            Some(x) => x,
+            None => return,
+        };
+        // `move_to` publishes under the address of a signer.
+        let is_signer = matches!(
+            ctx.env.get_node_type(addr.node_id()).skip_reference(),
+            MoveModelType::Primitive(PrimitiveType::Signer)
+        );
+        let addr_str = if reason == ResourceAccessReason::MoveTo && is_signer {
+            format!("{}({})", FunSpecGenerator::signer_address_of(ctx), addr_str)
+        } else {
+            addr_str
+        };
+        let exists = format!("exists<{}>({})", ty_str, addr_str);
+        let mut clauses = vec![match reason {
+            ResourceAccessReason::MoveTo => format!("aborts_if {}", exists),
+            _ => format!("aborts_if !{}", exists),
+        }];
+        if reason != ResourceAccessReason::BorrowGlobal {
+            clauses.push(format!("modifies global<{}>({})", ty_str, addr_str));
+        }
+        match reason {
+            ResourceAccessReason::MoveFrom => clauses.push(format!("ensures !{}", exists)),
+            ResourceAccessReason::MoveTo => clauses.push(format!("ensures {}", exists)),
+            _ => {},
+        }
+        for clause in clauses {
+            statements.push(format!("{}{};\n", indent(2), clause));
+        }
+    }
+
+    fn handle_resource_write(
+        statements: &mut SpecStatements,
+        resource: &ResourceRef,
+        field: &Symbol,
+        value: &MoveModelExp,
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) {
+        let env = ctx.env;
+        if !FunSpecGenerator::is_support_exp(value, env) {
+            return;
+        }
+        let (ty_str, addr_str) =
+            match FunSpecGenerator::resource_str(&resource.ty, &resource.addr, ctx, scope) {
+                Some(x) => x,
+                None => return,
+            };
+        let global = format!("global<{}>({})", ty_str, addr_str);
+        // the value is computed from the resource before it is written.
+        let extra = FunSpecGenerator::resource_local(resource, format!("old({})", global), env);
+        let pool = env.symbol_pool();
+        // only the last write of a field holds after the call.
+        let target = format!(
+            "{}ensures {}.{} == ",
+            indent(2),
+            global,
+            field.display(pool)
+        );
+        let written = statements.any_line(|x| x.contains(format!("ensures {}.", global).as_str()));
+        statements.retain_lines(|x| !x.starts_with(target.as_str()));
+        let free_vars: Vec<String> = value
+            .free_vars()
+            .iter()
+            .map(|x| x.display(pool).to_string())
+            .collect();
+        // a local computed in the body, like `let v = counter.value`, has no value in the spec.
+        if scope.is_none()
+            && free_vars
+                .iter()
+                .any(|x| !ctx.params.contains(x) && !extra.contains_key(x))
+        {
+            return;
+        }
+        // after an earlier write the resource no longer holds its `old` value.
+        if written && free_vars.iter().any(|x| extra.contains_key(x)) {
+            return;
+        }
+        let value_str = match FunSpecGenerator::exp_source_with(value, env, scope, &extra) {
+            Some(x) => x,
+            None => return,
+        };
+        statements.push(format!("{}{};\n", target, value_str));
+    }
+
+    fn handle_emit(
+        statements: &mut SpecStatements,
+        msg: &MoveModelExp,
+        handle: Option<&MoveModelExp>,
+        resource: Option<&ResourceRef>,
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) {
+        let env = ctx.env;
+        if !FunSpecGenerator::is_support_exp(msg, env) {
+            return;
+        }
+        // the event handle of a resource is read after it is written.
+        let mut extra = HashMap::new();
+        if let Some(resource) = resource {
+            if let Some((ty_str, addr_str)) =
+                FunSpecGenerator::resource_str(&resource.ty, &resource.addr, ctx, scope)
+            {
+                let global = format!("global<{}>({})", ty_str, addr_str);
+                extra = FunSpecGenerator::resource_local(resource, global, env);
+            }
+        }
+        let msg_str = match FunSpecGenerator::exp_source_with(msg, env, scope, &extra) {
+            Some(x) => x,
+            None => return,
+        };
+        let statement = match handle {
+            Some(handle) => match FunSpecGenerator::exp_source_with(handle, env, scope, &extra) {
+                Some(x) => format!(
+                    "{}emits {} to {};\n",
+                    indent(2),
+                    msg_str,
+                    param_arg_str(x.as_str())
+                ),
+                None => return,
+            },
+            None => format!("{}emits {};\n", indent(2), msg_str),
+        };
+        statements.push(statement);
+    }
+
+    fn handle_callee(
+        statements: &mut SpecStatements,
+        item: &SpecExpItem,
+        ctx: &SpecEmitContext,
+        scope: Option<&CalleeScope>,
+    ) {
+        let (params, args, inst, items) = match item {
+            SpecExpItem::Callee {
+                params,
+                args,
+                inst,
+                items,
+            } => (params, args, inst, items),
+            _ => return,
+        };
+        let mut callee_scope = CalleeScope::default();
+        for (param, arg) in params.iter().zip(args.iter()) {
+            let arg_str = match FunSpecGenerator::exp_source(arg, ctx.env, scope) {
+                Some(x) => param_arg_str(x.as_str()),
+                None => return,
+            };
+            callee_scope
+                .params
+                .insert(param.display(ctx.env.symbol_pool()).to_string(), arg_str);
+        }
+        callee_scope.inst = inst
+            .iter()
+            .map(|ty| match scope {
+                Some(scope) => scope.instantiate(ty),
+                None => ty.clone(),
+            })
+            .collect();
+        let mut callee_statements = SpecStatements::default();
+        for callee_item in items.iter() {
+            match callee_item {
+                // the value of a local of the helper takes its place in the spec of the caller.
+                SpecExpItem::PatternLet { left, right } => {
+                    if let MoveModelPattern::Var(_, sym) = left {
+                        let name = sym.display(ctx.env.symbol_pool()).to_string();
+                        match FunSpecGenerator::exp_source(right, ctx.env, Some(&callee_scope)) {
+                            Some(x) => callee_scope.params.insert(name, param_arg_str(x.as_str())),
+                            None => callee_scope.params.remove(&name),
+                        };
+                    }
+                },
+                _ => FunSpecGenerator::emit_items(
+                    &mut callee_statements,
+                    std::slice::from_ref(callee_item),
+                    ctx,
+                    Some(&callee_scope),
+                ),
+            }
+        }
+        // the caller may check the same condition before calling the helper.
+        for line in callee_statements.lines {
+            statements.push(line);
+        }
+    }
+
     fn handle_binop_exp(
-        statements: &mut String,
+        statements: &mut SpecStatements,
         if_exp: &MoveModelExp,
         op: &MoveModelOperation,
         abort_exp: &MoveModelExp,
         env: &GlobalEnv,
+        scope: Option<&CalleeScope>,
     ) {
         fn inverse_binop_zx(op: &MoveModelOperation) -> (String, String) {
             match op {
@@ -312,11 +801,9 @@ impl FunSpecGenerator {
 
         let op2 = inverse_binop_zx(op);
 
-        let if_exp_node_id = if_exp.as_ref().node_id();
-        let if_exp_node_loc = env.get_node_loc(if_exp_node_id);
-        let if_exp_str = match env.get_source(&if_exp_node_loc) {
-            Ok(x) => x,
-            Err(_) => return,
+        let if_exp_str = match FunSpecGenerator::exp_source(if_exp, env, scope) {
+            Some(x) => x,
+            None => return,
         };
 
         #[allow(unused_assignments)]
@@ -327,11 +814,9 @@ impl FunSpecGenerator {
             return;
         }
 
-        let abort_exp_node_id = abort_exp.as_ref().node_id();
-        let abort_exp_node_loc = env.get_node_loc(abort_exp_node_id);
-        let abort_exp_str = match env.get_source(&abort_exp_node_loc) {
-            Ok(x) => x,
-            Err(_) => return,
+        let abort_exp_str = match FunSpecGenerator::exp_source(abort_exp, env, scope) {
+            Some(x) => x,
+            None => return,
         };
 
         statements.push_str(
@@ -346,23 +831,20 @@ impl FunSpecGenerator {
     }
 
     fn handle_funcop_exp(
-        statements: &mut String,
+        statements: &mut SpecStatements,
         if_exp: &MoveModelExp,
         abort_exp: &MoveModelExp,
         env: &GlobalEnv,
+        scope: Option<&CalleeScope>,
     ) {
-        let if_exp_node_id = if_exp.as_ref().node_id();
-        let if_exp_node_loc = env.get_node_loc(if_exp_node_id);
-        let if_exp_str = match env.get_source(&if_exp_node_loc) {
-            Ok(x) => x,
-            Err(_) => return,
+        let if_exp_str = match FunSpecGenerator::exp_source(if_exp, env, scope) {
+            Some(x) => x,
+            None => return,
         };
 
-        let abort_exp_node_id = abort_exp.as_ref().node_id();
-        let abort_exp_node_loc = env.get_node_loc(abort_exp_node_id);
-        let abort_exp_str = match env.get_source(&abort_exp_node_loc) {
-            Ok(x) => x,
-            Err(_) => return,
+        let abort_exp_str = match FunSpecGenerator::exp_source(abort_exp, env, scope) {
+            Some(x) => x,
+            None => return,
         };
 
         statements.push_str(
@@ -4,11 +4,17 @@
 use super::move_generate_spec::*;
 use crate::{
     context::Context,
+    move_generate_spec_merge::{
+        drop_inline_specs, merge_spec_file, spec_file_path, GeneratedModuleSpec, GeneratedSpec,
+    },
+    movefmt::text_edits,
+    project::Project,
     utils::{collect_use_decl, get_modules_by_fpath_in_target_modules},
 };
 use lsp_server::*;
+use lsp_types::{Url, WorkspaceEdit};
 use move_compiler::expansion::ast::Address;
-use move_model::model::{FunctionEnv, StructEnv};
+use move_model::model::{FunctionEnv, GlobalEnv, ModuleEnv, StructEnv};
 use serde::Deserialize;
 use std::{
     collections::HashMap,
@@ -28,7 +34,6 @@ where
     let parameters = serde_json::from_value::<ReqParameters>(request.params.clone())
         .expect("could not deserialize on_generate_spec_file request");
     let fpath = PathBuf::from_str(parameters.fpath.as_str()).unwrap();
-    let result_file_path = Resp::mk_result_filepath(&fpath);
     let send_err = |context: &Context, msg: String| {
         let r = Response::new_err(request.id.clone(), ErrorCode::UnknownErrorCode as i32, msg);
         context
@@ -37,14 +42,6 @@ where
             .send(Message::Response(r))
             .unwrap();
     };
-    if result_file_path.exists() {
-        send_err(context, "file already exists.".to_string());
-        return lsp_server::Response {
-            id: "".to_string().into(),
-            result: Some(serde_json::json!({"msg": "file already exists."})),
-            error: None,
-        };
-    }
     let project = match context.projects.get_project(&fpath) {
         Some(x) => x,
         None => {
@@ -56,29 +53,137 @@ where
             };
         },
     };
-    let env = &project.global_env;
+    let update = match spec_file_update(project, &fpath, parameters.refresh) {
+        Ok(x) => x,
+        Err(msg) => {
+            send_err(context, msg.clone());
+            return lsp_server::Response {
+                id: "".to_string().into(),
+                result: Some(serde_json::json!({ "msg": msg })),
+                error: None,
+            };
+        },
+    };
+    let result_file_path = update.path.clone();
 
-    let mut result = ModuleSpecBuilder::new();
+    // an existing spec file is updated by the client, with the specs it does not have.
+    if let Some(origin) = update.origin.as_ref() {
+        let mut changes = HashMap::new();
+        if let Ok(url) = Url::from_file_path(result_file_path.as_path()) {
+            changes.insert(
+                url,
+                text_edits(origin.as_str(), update.content.as_str(), None),
+            );
+        }
+        let r = Response::new_ok(
+            request.id.clone(),
+            serde_json::to_value(Resp {
+                fpath: result_file_path.to_str().unwrap().to_string(),
+                edit: Some(WorkspaceEdit::new(changes)),
+            })
+            .unwrap(),
+        );
+        context
+            .connection
+            .sender
+            .send(Message::Response(r))
+            .unwrap();
+        return Response::new_ok(request.id.clone(), serde_json::json!(update.content));
+    }
 
-    let mut addr_num_and_module_name_to_addr_name: HashMap<(String, String), String> =
-        Default::default();
-    env.get_module_idents()
-        .iter()
-        .for_each(|module_ident| match module_ident.address {
-            Address::Numerical(may_addr_symbol, addr_num) => {
-                if let Some(addr_symbol) = may_addr_symbol {
-                    let k = (
-                        addr_num.value.to_string().to_uppercase(),
-                        module_ident.module.to_string(),
-                    );
-                    eprintln!("{:?} : {}", k.clone(), addr_symbol.value.to_string());
-                    addr_num_and_module_name_to_addr_name.insert(k, addr_symbol.value.to_string());
-                }
+    let file_content = update.content;
+    if is_generate {
+        match std::fs::write(result_file_path.clone(), file_content.clone()) {
+            Ok(_) => {},
+            Err(err) => {
+                send_err(context, format!("write to file failed,err:{:?}", err));
+                return lsp_server::Response {
+                    id: "".to_string().into(),
+                    result: Some(serde_json::json!({"msg": "write to file failed"})),
+                    error: None,
+                };
             },
-            Address::NamedUnassigned(_) => {},
+        };
+    }
+
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(Resp {
+            fpath: result_file_path.to_str().unwrap().to_string(),
+            edit: None,
+        })
+        .unwrap(),
+    );
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+
+    Response::new_ok(request.id.clone(), serde_json::json!(file_content))
+}
+
+/// The spec file generated for a Move file, `origin` is its content when it exists already and
+/// the new specs are merged into it.
+pub struct SpecFileUpdate {
+    pub path: PathBuf,
+    pub origin: Option<String>,
+    pub content: String,
+}
+
+impl SpecFileUpdate {
+    /// Whether specs generated for the Move file are missing from its spec file.
+    pub fn is_changed(&self) -> bool {
+        self.origin.as_deref().unwrap_or_default() != self.content
+    }
+}
+
+/// The spec file of the Move file `fpath` of `project`, with the specs generated for its modules.
+pub fn spec_file_update(
+    project: &Project,
+    fpath: &Path,
+    refresh: bool,
+) -> Result<SpecFileUpdate, String> {
+    merge_module_specs(fpath, &generate_module_specs(project, fpath), refresh)
+}
+
+/// The spec file of the Move file `fpath`, with the specs `modules` generated for its modules.
+pub fn merge_module_specs(
+    fpath: &Path,
+    modules: &[GeneratedModuleSpec],
+    refresh: bool,
+) -> Result<SpecFileUpdate, String> {
+    let path = spec_file_path(fpath);
+    let source = std::fs::read_to_string(fpath)
+        .map_err(|err| format!("read move file failed,err:{:?}", err))?;
+    if !path.exists() {
+        return Ok(SpecFileUpdate {
+            path,
+            origin: None,
+            content: drop_inline_specs(source.as_str(), modules)
+                .iter()
+                .map(|x| x.to_spec_string())
+                .collect(),
         });
+    }
+    let origin = std::fs::read_to_string(path.as_path())
+        .map_err(|err| format!("read spec file failed,err:{:?}", err))?;
+    let content = merge_spec_file(origin.as_str(), source.as_str(), modules, refresh)
+        .ok_or_else(|| "could not parse the existing spec file.".to_string())?;
+    Ok(SpecFileUpdate {
+        path,
+        origin: Some(origin),
+        content,
+    })
+}
+
+/// The specs generated for the modules of the Move file `fpath`.
+pub fn generate_module_specs(project: &Project, fpath: &Path) -> Vec<GeneratedModuleSpec> {
+    let env = &project.global_env;
+
+    let mut result = ModuleSpecBuilder::new();
 
-    for module_env in get_modules_by_fpath_in_target_modules(&project.global_env, &fpath) {
+    for module_env in get_modules_by_fpath_in_target_modules(&project.global_env, fpath) {
         let using_module_map = collect_use_decl(
             &project.addrname_2_addrnum,
             &module_env,
@@ -86,22 +191,16 @@ where
         );
 
         log::info!("generate spec module: {}", module_env.get_full_name_str());
-        // find module_env's namespace
-        let module_env_full_name = module_env.get_full_name_str();
-        let mut split_iter = module_env_full_name.split("::");
-        if split_iter.clone().count() != 2 {
-            log::error!("module full name's len should be 2");
-            continue;
-        }
-
-        let a = split_iter.next().unwrap();
-        let b = split_iter.next().unwrap();
-        let c = (a.to_string().to_uppercase(), b.to_string());
-        if addr_num_and_module_name_to_addr_name.get(&c).is_none() {
-            log::error!("cound not found address number for {:?}", c);
-            continue;
-        }
-        let addr_name = addr_num_and_module_name_to_addr_name.get(&c).unwrap();
+        let (addr_name, address) = match module_address(env, &module_env) {
+            Some(x) => x,
+            None => {
+                log::error!(
+                    "cound not found address name for {:?}",
+                    module_env.get_full_name_str()
+                );
+                continue;
+            },
+        };
         let module_name = module_env.get_name().display(env).to_string();
 
         // find all available StructEnv and FunctionEnv
@@ -128,61 +227,73 @@ where
         env_item_list.sort_by(|a, b| a.line.cmp(&b.line));
 
         for item in env_item_list {
-            let spec = match item {
+            let (name, spec) = match item {
                 EnvItem {
                     struct_env: Some(struct_env),
                     function_env: None,
                     ..
-                } => genrate_struct_spec(&struct_env),
+                } => {
+                    if let Some(invariant) =
+                        generate_struct_global_invariant(&struct_env, &using_module_map)
+                    {
+                        result.insert_invariant(
+                            AddrAndModuleName::new(
+                                addr_name.clone(),
+                                address.clone(),
+                                module_name.clone(),
+                            ),
+                            invariant,
+                        );
+                    }
+                    (
+                        struct_env.get_name().display(env.symbol_pool()).to_string(),
+                        genrate_struct_spec(&struct_env, &using_module_map),
+                    )
+                },
                 EnvItem {
                     struct_env: None,
                     function_env: Some(f_env),
                     ..
-                } => generate_fun_spec_zx(
-                    &project.global_env,
-                    &module_env,
-                    &f_env,
-                    &using_module_map,
+                } => (
+                    f_env.get_name_str(),
+                    generate_fun_spec_zx(
+                        &project.global_env,
+                        &module_env,
+                        &f_env,
+                        &using_module_map,
+                    ),
                 ),
                 _ => continue,
             };
 
             result.insert(
-                AddrAndModuleName::new(addr_name.clone(), module_name.clone()),
-                spec,
+                AddrAndModuleName::new(addr_name.clone(), address.clone(), module_name.clone()),
+                GeneratedSpec {
+                    name,
+                    content: spec,
+                },
             );
         }
     } // for module_env
 
-    let file_content = result.get_result_string();
-    if is_generate {
-        match std::fs::write(result_file_path.clone(), file_content.clone()) {
-            Ok(_) => {},
-            Err(err) => {
-                send_err(context, format!("write to file failed,err:{:?}", err));
-                return lsp_server::Response {
-                    id: "".to_string().into(),
-                    result: Some(serde_json::json!({"msg": "write to file failed"})),
-                    error: None,
-                };
-            },
-        };
-    }
+    result.into_module_specs()
+}
 
-    let r = Response::new_ok(
-        request.id.clone(),
-        serde_json::to_value(Resp {
-            fpath: result_file_path.to_str().unwrap().to_string(),
+/// The named address `module_env` is declared at, with its value.
+pub(crate) fn module_address(env: &GlobalEnv, module_env: &ModuleEnv) -> Option<(String, String)> {
+    let full_name = module_env.get_full_name_str();
+    let (address, module_name) = full_name.split_once("::")?;
+    env.get_module_idents()
+        .iter()
+        .find_map(|module_ident| match module_ident.address {
+            Address::Numerical(Some(addr_symbol), addr_num)
+                if addr_num.value.to_string().to_uppercase() == address.to_uppercase()
+                    && module_ident.module.to_string() == module_name =>
+            {
+                Some((addr_symbol.value.to_string(), address.to_string()))
+            },
+            _ => None,
         })
-        .unwrap(),
-    );
-    context
-        .connection
-        .sender
-        .send(Message::Response(r))
-        .unwrap();
-
-    Response::new_ok(request.id.clone(), serde_json::json!(file_content))
 }
 
 #[derive(Debug, Clone)]
@@ -214,7 +325,9 @@ impl<'a> PartialOrd for EnvItem<'a> {
 
 #[derive(Default)]
 struct ModuleSpecBuilder {
-    results: HashMap<AddrAndModuleName, Vec<String>>,
+    results: HashMap<AddrAndModuleName, Vec<GeneratedSpec>>,
+    /// The invariants of the `spec module` block of each module.
+    invariants: HashMap<AddrAndModuleName, Vec<String>>,
 }
 
 impl ModuleSpecBuilder {
@@ -222,7 +335,7 @@ impl ModuleSpecBuilder {
         Self::default()
     }
 
-    fn insert(&mut self, k: AddrAndModuleName, v: String) {
+    fn insert(&mut self, k: AddrAndModuleName, v: GeneratedSpec) {
         if let Some(x) = self.results.get_mut(&k) {
             x.push(v);
         } else {
@@ -230,28 +343,38 @@ impl ModuleSpecBuilder {
         }
     }
 
-    fn get_result_string(self) -> String {
-        let mut ret = String::new();
-        for (k, vv) in self.results.into_iter() {
+    fn insert_invariant(&mut self, k: AddrAndModuleName, v: String) {
+        self.invariants.entry(k).or_default().push(v);
+    }
+
+    fn into_module_specs(self) -> Vec<GeneratedModuleSpec> {
+        let Self {
+            results,
+            mut invariants,
+        } = self;
+        let mut ret = vec![];
+        for (k, vv) in results.into_iter() {
             let mut x = String::default();
-            x.push_str(
-                format!(
-                    "spec {}::{} {{\n\n",
-                    &k.addr_name.as_str(),
-                    &k.module_name.as_str()
-                )
-                .as_str(),
-            );
             x.push_str(format!("{}spec module {{\n", indent(1)).as_str());
             x.push_str(format!("{}pragma verify = true;\n", indent(2)).as_str());
             x.push_str(format!("{}pragma aborts_if_is_strict;\n", indent(2)).as_str());
-            x.push_str(format!("{}}}\n", indent(1)).as_str());
-            for v in vv.into_iter() {
+            for v in invariants.remove(&k).unwrap_or_default() {
                 x.push_str(v.as_str());
             }
-            x.push_str("}\n\n");
-            ret.push_str(x.as_str());
+            x.push_str(format!("{}}}\n", indent(1)).as_str());
+            let mut specs = vec![GeneratedSpec {
+                name: "module".to_string(),
+                content: x,
+            }];
+            specs.extend(vv);
+            ret.push(GeneratedModuleSpec {
+                addr_name: k.addr_name,
+                address: k.address,
+                module_name: k.module_name,
+                specs,
+            });
         }
+        ret.sort_by(|a, b| (&a.addr_name, &a.module_name).cmp(&(&b.addr_name, &b.module_name)));
         ret
     }
 }
@@ -259,13 +382,15 @@ impl ModuleSpecBuilder {
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct AddrAndModuleName {
     addr_name: String,
+    address: String,
     module_name: String,
 }
 
 impl AddrAndModuleName {
-    fn new(addr_name: String, module_name: String) -> Self {
+    fn new(addr_name: String, address: String, module_name: String) -> Self {
         Self {
             addr_name,
+            address,
             module_name,
         }
     }
@@ -273,26 +398,15 @@ impl AddrAndModuleName {
 #[derive(Clone, Deserialize)]
 pub struct ReqParameters {
     fpath: String,
+    /// Generate again the marked clauses of the specs already in the spec file.
+    #[serde(default)]
+    refresh: bool,
 }
 
 #[derive(Clone, serde::Serialize)]
 pub struct Resp {
     fpath: String,
-}
-
-impl Resp {
-    fn mk_result_filepath(x: &Path) -> PathBuf {
-        let mut x = x.to_path_buf();
-        let b = x
-            .components()
-            .last()
-            .and_then(|x| x.as_os_str().to_str())
-            .unwrap()
-            .to_string();
-        let index = b.as_str().rfind('.').unwrap();
-        x.pop();
-        let mut ret = x.clone();
-        ret.push(format!("{}{}", &b.as_str()[0..index], ".spec.move"));
-        ret
-    }
+    /// The update of an existing spec file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edit: Option<WorkspaceEdit>,
 }
@@ -0,0 +1,288 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{move_generate_spec::indent, utils::parse_buffer};
+use move_compiler::parser::ast::{Definition, ModuleDefinition, ModuleMember, SpecBlockTarget_};
+use std::path::{Path, PathBuf};
+
+/// Start of the clauses written by the generator in a spec block, the lines up to
+/// `GENERATED_END` are replaced when the spec is refreshed.
+pub const GENERATED_BEGIN: &str = "// auto-generated begin";
+pub const GENERATED_END: &str = "// auto-generated end";
+
+/// The spec block generated for a function or a struct, `module` for the `spec module` block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedSpec {
+    pub name: String,
+    pub content: String,
+}
+
+impl GeneratedSpec {
+    /// Whether the spec has no clauses.
+    pub fn is_empty(&self) -> bool {
+        generated_clauses(self.content.as_str()).trim().is_empty()
+    }
+}
+
+/// The spec blocks generated for the module `addr_name::module_name`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedModuleSpec {
+    pub addr_name: String,
+    /// The value of `addr_name`, `0x..`.
+    pub address: String,
+    pub module_name: String,
+    pub specs: Vec<GeneratedSpec>,
+}
+
+impl GeneratedModuleSpec {
+    /// The `spec addr_name::module_name { .. }` block written in a new spec file.
+    pub fn to_spec_string(&self) -> String {
+        let mut ret = format!("spec {}::{} {{\n\n", self.addr_name, self.module_name);
+        for x in self.specs.iter() {
+            ret.push_str(x.content.as_str());
+        }
+        ret.push_str("}\n\n");
+        ret
+    }
+}
+
+/// `<name>.spec.move`, next to the Move file `x`.
+pub fn spec_file_path(x: &Path) -> PathBuf {
+    let mut x = x.to_path_buf();
+    let b = x
+        .components()
+        .last()
+        .and_then(|x| x.as_os_str().to_str())
+        .unwrap()
+        .to_string();
+    let index = b.as_str().rfind('.').unwrap();
+    x.pop();
+    let mut ret = x.clone();
+    ret.push(format!("{}{}", &b.as_str()[0..index], ".spec.move"));
+    ret
+}
+
+/// `modules` without the spec blocks the Move file `source` already has inline, the modules left
+/// with no spec block dropped.
+pub fn drop_inline_specs(
+    source: &str,
+    modules: &[GeneratedModuleSpec],
+) -> Vec<GeneratedModuleSpec> {
+    let defs = parse_buffer(source).unwrap_or_default();
+    let inline = module_definitions(&defs);
+    modules
+        .iter()
+        .filter_map(|m| {
+            let mut m = m.clone();
+            if let Some((_, module_def)) = inline
+                .iter()
+                .find(|(addr, x)| is_module(addr, x, &m.addr_name, &m.address, &m.module_name))
+            {
+                m.specs
+                    .retain(|x| spec_block_span(module_def, x.name.as_str()).is_none());
+            }
+            if m.specs.is_empty() {
+                None
+            } else {
+                Some(m)
+            }
+        })
+        .collect()
+}
+
+/// `content` of an existing spec file with the spec blocks of `modules` it does not have yet,
+/// neither in the spec file nor inline in the Move file `source`, the clauses of the blocks added
+/// between the generated markers. With `refresh`, the marked clauses of the blocks already in
+/// the spec file are generated again. Hand-written clauses are left alone.
+/// None if `content` does not parse.
+pub fn merge_spec_file(
+    content: &str,
+    source: &str,
+    modules: &[GeneratedModuleSpec],
+    refresh: bool,
+) -> Option<String> {
+    let defs = parse_buffer(content)?;
+    let existing = module_definitions(&defs);
+    // replacements of byte ranges of `content`.
+    let mut edits: Vec<(usize, usize, String)> = vec![];
+    let mut appended = String::new();
+    for m in drop_inline_specs(source, modules).iter() {
+        let module_def = match existing
+            .iter()
+            .find(|(addr, x)| is_module(addr, x, &m.addr_name, &m.address, &m.module_name))
+        {
+            Some((_, x)) => x,
+            None => {
+                let mut m = m.clone();
+                m.specs
+                    .iter_mut()
+                    .for_each(|x| x.content = mark_generated(x.content.as_str()));
+                appended.push_str(m.to_spec_string().as_str());
+                continue;
+            },
+        };
+        let mut missing = String::new();
+        for spec in m.specs.iter() {
+            match spec_block_span(module_def, spec.name.as_str()) {
+                None => missing.push_str(mark_generated(spec.content.as_str()).as_str()),
+                Some((start, end)) if refresh => {
+                    if let Some(x) = refresh_generated(content, start, end, spec.content.as_str()) {
+                        edits.push(x);
+                    }
+                },
+                Some(_) => {},
+            }
+        }
+        if missing.is_empty() {
+            continue;
+        }
+        let close = match content[..module_def.loc.end() as usize].rfind('}') {
+            Some(x) => x,
+            None => continue,
+        };
+        let line_start = content[..close].rfind('\n').map(|x| x + 1).unwrap_or(0);
+        if content[line_start..close].trim().is_empty() {
+            edits.push((line_start, line_start, missing));
+        } else {
+            edits.push((close, close, format!("\n{}", missing)));
+        }
+    }
+    edits.sort_by(|a, b| b.0.cmp(&a.0));
+    let mut ret = content.to_string();
+    for (start, end, text) in edits {
+        ret.replace_range(start..end, text.as_str());
+    }
+    if !appended.is_empty() {
+        if !ret.is_empty() && !ret.ends_with('\n') {
+            ret.push('\n');
+        }
+        ret.push_str(appended.as_str());
+    }
+    Some(ret)
+}
+
+/// Byte range of the spec block of `name` in the module `addr_name::module_name` of `content`,
+/// with `address` the value of `addr_name`, `module` for the `spec module` block.
+pub fn find_spec_block(
+    content: &str,
+    addr_name: &str,
+    address: &str,
+    module_name: &str,
+    name: &str,
+) -> Option<(usize, usize)> {
+    let defs = parse_buffer(content)?;
+    module_definitions(&defs)
+        .into_iter()
+        .filter(|(addr, x)| is_module(addr, x, addr_name, address, module_name))
+        .find_map(|(_, x)| spec_block_span(x, name))
+}
+
+/// The replacement of the marked clauses of the spec block at `start..end` of `content` by the
+/// clauses of `generated`, None if the block has no markers.
+pub fn refresh_generated(
+    content: &str,
+    start: usize,
+    end: usize,
+    generated: &str,
+) -> Option<(usize, usize, String)> {
+    let block = &content[start..end];
+    let begin = block.find(GENERATED_BEGIN)?;
+    let begin = start + begin + block[begin..].find('\n')? + 1;
+    let end_marker = start + block.find(GENERATED_END)?;
+    if end_marker < begin {
+        return None;
+    }
+    let end_line = content[..end_marker].rfind('\n').map(|x| x + 1)?;
+    let clauses = generated_clauses(generated);
+    if content[begin..end_line] == clauses {
+        return None;
+    }
+    Some((begin, end_line, clauses))
+}
+
+/// `spec` generated for a function or a struct, with its clauses between the markers.
+pub fn mark_generated(spec: &str) -> String {
+    let lines: Vec<&str> = spec.lines().collect();
+    if lines.len() < 2 {
+        return spec.to_string();
+    }
+    format!(
+        "{}\n{}{}\n{}{}{}\n{}\n",
+        lines[0],
+        indent(2),
+        GENERATED_BEGIN,
+        generated_clauses(spec),
+        indent(2),
+        GENERATED_END,
+        lines[lines.len() - 1]
+    )
+}
+
+/// The lines of `spec` between the signature and the closing brace.
+fn generated_clauses(spec: &str) -> String {
+    let lines: Vec<&str> = spec.lines().collect();
+    if lines.len() < 2 {
+        return String::new();
+    }
+    lines[1..lines.len() - 1]
+        .iter()
+        .map(|x| format!("{}\n", x))
+        .collect()
+}
+
+/// The modules of `defs`, with the addresses they are written with.
+fn module_definitions(defs: &[Definition]) -> Vec<(String, &ModuleDefinition)> {
+    let mut ret = vec![];
+    for d in defs.iter() {
+        match d {
+            Definition::Module(m) => ret.push((
+                m.address
+                    .as_ref()
+                    .map(|x| x.value.to_string())
+                    .unwrap_or_default(),
+                m,
+            )),
+            Definition::Address(a) => {
+                ret.extend(a.modules.iter().map(|m| (a.addr.value.to_string(), m)))
+            },
+            Definition::Script(_) => {},
+        }
+    }
+    ret
+}
+
+/// Whether the module `module_def`, at the address written `addr`, is `addr_name::module_name`,
+/// with `address` the value of `addr_name`.
+fn is_module(
+    addr: &str,
+    module_def: &ModuleDefinition,
+    addr_name: &str,
+    address: &str,
+    module_name: &str,
+) -> bool {
+    // `0x0001` and `0x1` are the same address.
+    let value = |x: &str| {
+        x.strip_prefix("0x")
+            .map(|x| x.trim_start_matches('0').to_lowercase())
+    };
+    module_def.name.to_string() == module_name
+        && (addr == addr_name || (value(addr).is_some() && value(addr) == value(address)))
+}
+
+fn spec_block_span(module_def: &ModuleDefinition, name: &str) -> Option<(usize, usize)> {
+    module_def.members.iter().find_map(|member| match member {
+        ModuleMember::Spec(spec) => {
+            let found = match &spec.value.target.value {
+                SpecBlockTarget_::Member(target, _) => target.value.as_str() == name,
+                SpecBlockTarget_::Module => name == "module",
+                _ => false,
+            };
+            if found {
+                Some((spec.loc.start() as usize, spec.loc.end() as usize))
+            } else {
+                None
+            }
+        },
+        _ => None,
+    })
+}
@@ -4,18 +4,22 @@
 use crate::{
     context::Context,
     move_generate_spec::{generate_fun_spec_zx, genrate_struct_spec},
+    move_generate_spec_file::module_address,
+    move_generate_spec_merge::{find_spec_block, refresh_generated, spec_file_path},
     project::Project,
-    utils::{collect_use_decl, get_modules_by_fpath_in_target_modules, GetPosition},
+    type_display_for_spec::SpecUseDecls,
+    utils::{collect_use_decl, get_modules_by_fpath_in_target_modules, GetPosition, LineIndex},
 };
 use codespan::Location;
 use lsp_server::{Message, Request, Response, *};
-use move_model::{
-    ast::ModuleName,
-    model::{GlobalEnv, Loc, ModuleEnv},
-    symbol::Symbol as SpecSymbol,
-};
+use lsp_types::{Position, Range, TextEdit, Url, WorkspaceEdit};
+use move_model::model::{GlobalEnv, Loc, ModuleEnv};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 pub fn on_generate_spec_sel(context: &mut Context, request: &Request) -> Response {
     log::info!("on_generate_spec_sel request = {:?}", request);
@@ -46,11 +50,73 @@ pub fn on_generate_spec_sel(context: &mut Context, request: &Request) -> Respons
         },
     };
 
-    let mut insert_pos: (u32, u32) = (0, 0);
-    let mut result_string: String = String::new();
-    let mut is_find = false;
-    for module_env in get_modules_by_fpath_in_target_modules(&project.global_env, &parameters.fpath)
-    {
+    let target =
+        match generate_spec_target(project, &parameters.fpath, parameters.line, parameters.col) {
+            Some(x) => x,
+            None => {
+                send_err(context, request, "spec target not found.".to_string());
+                return lsp_server::Response {
+                    id: "".to_string().into(),
+                    result: Some(serde_json::json!({"msg": "spec target not found"})),
+                    error: None,
+                };
+            },
+        };
+
+    let result = match existing_spec_edit(&parameters.fpath, &target) {
+        Some((edit, pos)) => Some(Resp {
+            line: pos.line,
+            col: pos.character,
+            content: String::new(),
+            edit: Some(edit),
+        }),
+        None => Some(Resp {
+            line: target.insert_pos.0,
+            col: target.insert_pos.1,
+            content: target.content,
+            edit: None,
+        }),
+    };
+
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap());
+    context
+        .connection
+        .sender
+        .send(Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+/// The spec generated for the function or the struct at a position of a Move file.
+#[derive(Clone, Debug, Default)]
+pub struct SpecTarget {
+    /// Where the editor extension inserts `content`.
+    pub insert_pos: (u32, u32),
+    /// The line of the closing brace of the function or the struct.
+    pub end_line: u32,
+    pub addr_name: String,
+    /// The value of `addr_name`.
+    pub address: String,
+    pub module_name: String,
+    pub name: String,
+    pub is_struct: bool,
+    pub content: String,
+}
+
+/// The spec of the function or the struct at `line` and `col` of the Move file `fpath`.
+pub fn generate_spec_target(
+    project: &Project,
+    fpath: &Path,
+    line: u32,
+    col: u32,
+) -> Option<SpecTarget> {
+    let parameters = ReqParametersPath {
+        fpath: fpath.to_path_buf(),
+        line,
+        col,
+    };
+    let mut target = SpecTarget::default();
+    for module_env in get_modules_by_fpath_in_target_modules(&project.global_env, fpath) {
         log::info!("collect_use_decl");
         let using_module_map = collect_use_decl(
             &project.addrname_2_addrnum,
@@ -61,44 +127,113 @@ pub fn on_generate_spec_sel(context: &mut Context, request: &Request) -> Respons
         if handle_struct(
             project,
             &module_env,
+            &using_module_map,
             &parameters,
-            &mut insert_pos,
-            &mut result_string,
+            &mut target,
         ) || handle_function(
             project,
             &module_env,
             using_module_map,
             &parameters,
-            &mut insert_pos,
-            &mut result_string,
+            &mut target,
         ) {
-            is_find = true;
-            break;
+            (target.addr_name, target.address) =
+                written_module_address(&project.global_env, &module_env);
+            target.module_name = module_env
+                .get_name()
+                .display(&project.global_env)
+                .to_string();
+            return Some(target);
         }
     }
+    None
+}
 
-    if !is_find {
-        send_err(context, request, "spec target not found.".to_string());
-        return lsp_server::Response {
-            id: "".to_string().into(),
-            result: Some(serde_json::json!({"msg": "spec target not found"})),
-            error: None,
+/// The function or the struct at `line` and `col` of the Move file `fpath`, as the target of a
+/// spec whose content is not generated.
+pub fn find_spec_target(
+    project: &Project,
+    fpath: &Path,
+    line: u32,
+    col: u32,
+) -> Option<SpecTarget> {
+    let env = &project.global_env;
+    let in_loc = |loc: &Loc| ReqParametersPath::is_linecol_in_loc(line, col, loc, env);
+    for module_env in get_modules_by_fpath_in_target_modules(env, fpath) {
+        let (name, is_struct) = if let Some(x) = module_env
+            .get_structs()
+            .find(|x| !x.is_test_only() && in_loc(&x.get_loc()))
+        {
+            (x.get_name().display(x.symbol_pool()).to_string(), true)
+        } else if let Some(x) = module_env.get_functions().find(|x| in_loc(&x.get_loc())) {
+            (x.get_name_str(), false)
+        } else {
+            continue;
         };
+        let (addr_name, address) = written_module_address(env, &module_env);
+        return Some(SpecTarget {
+            addr_name,
+            address,
+            module_name: module_env.get_name().display(env).to_string(),
+            name,
+            is_struct,
+            ..Default::default()
+        });
     }
+    None
+}
 
-    let result = Some(Resp {
-        line: insert_pos.0,
-        col: insert_pos.1,
-        content: result_string.clone(),
-    });
+/// The address name of `module_env` with its value, a module declared at a number is written
+/// with the number.
+fn written_module_address(env: &GlobalEnv, module_env: &ModuleEnv) -> (String, String) {
+    module_address(env, module_env).unwrap_or_else(|| {
+        let full_name = module_env.get_full_name_str();
+        let address = full_name.split("::").next().unwrap_or_default();
+        (address.to_string(), address.to_string())
+    })
+}
 
-    let r = Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap());
-    context
-        .connection
-        .sender
-        .send(Message::Response(r.clone()))
-        .unwrap();
-    r
+/// The file, the module or its spec file, which has a spec for `target`, with its content and
+/// the byte range of the spec block.
+pub fn find_existing_spec(
+    fpath: &Path,
+    target: &SpecTarget,
+) -> Option<(PathBuf, String, usize, usize)> {
+    for path in [fpath.to_path_buf(), spec_file_path(fpath)] {
+        let content = match std::fs::read_to_string(path.as_path()) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        if let Some((start, end)) = find_spec_block(
+            content.as_str(),
+            target.addr_name.as_str(),
+            target.address.as_str(),
+            target.module_name.as_str(),
+            target.name.as_str(),
+        ) {
+            return Some((path, content, start, end));
+        }
+    }
+    None
+}
+
+/// When the module or its spec file already has a spec for `target`, the refresh of its
+/// generated clauses instead of a new spec block, and where the spec block is.
+pub fn existing_spec_edit(fpath: &Path, target: &SpecTarget) -> Option<(WorkspaceEdit, Position)> {
+    let (path, content, start, end) = find_existing_spec(fpath, target)?;
+    let index = LineIndex::new(content.as_str());
+    let mut edits = vec![];
+    if let Some((begin, end_line, clauses)) =
+        refresh_generated(content.as_str(), start, end, target.content.as_str())
+    {
+        edits.push(TextEdit {
+            range: Range::new(index.position(begin), index.position(end_line)),
+            new_text: clauses,
+        });
+    }
+    let mut changes = HashMap::new();
+    changes.insert(Url::from_file_path(path.as_path()).ok()?, edits);
+    Some((WorkspaceEdit::new(changes), index.position(start)))
 }
 
 fn send_err(context: &Context, requset: &Request, msg: String) {
@@ -113,9 +248,9 @@ fn send_err(context: &Context, requset: &Request, msg: String) {
 fn handle_struct(
     project: &Project,
     module_env: &ModuleEnv,
+    using_module_map: &SpecUseDecls,
     parameters: &ReqParametersPath,
-    insert_pos: &mut (u32, u32),
-    result_string: &mut String,
+    target: &mut SpecTarget,
 ) -> bool {
     for struct_env in module_env.get_structs() {
         if struct_env.is_test_only() {
@@ -142,18 +277,24 @@ fn handle_struct(
         new_parameters.line = end_location.line.0 + 1;
         new_parameters.col = end_location.column.0;
 
-        insert_pos.0 = end_location.line.0;
-        insert_pos.1 = 4;
+        target.end_line = end_location.line.0;
+        target.insert_pos.0 = end_location.line.0;
+        target.insert_pos.1 = 4;
         if ReqParametersPath::is_linecol_in_loc(
             new_parameters.line,
             new_parameters.col,
             &module_env.get_loc(),
             &project.global_env,
         ) {
-            insert_pos.0 += 1;
+            target.insert_pos.0 += 1;
         }
 
-        result_string.push_str(genrate_struct_spec(&struct_env).as_str());
+        target.content = genrate_struct_spec(&struct_env, using_module_map);
+        target.name = struct_env
+            .get_name()
+            .display(struct_env.symbol_pool())
+            .to_string();
+        target.is_struct = true;
 
         return true;
     }
@@ -163,10 +304,9 @@ fn handle_struct(
 fn handle_function(
     project: &Project,
     module_env: &ModuleEnv,
-    using_module_map: HashMap<ModuleName, Vec<SpecSymbol>>,
+    using_module_map: SpecUseDecls,
     parameters: &ReqParametersPath,
-    insert_pos: &mut (u32, u32),
-    result_string: &mut String,
+    target: &mut SpecTarget,
 ) -> bool {
     for func_env in module_env.get_functions() {
         if !ReqParametersPath::is_linecol_in_loc(
@@ -189,8 +329,9 @@ fn handle_function(
         new_parameters.line = end_location.line.0 + 1;
         new_parameters.col = 4;
 
-        insert_pos.0 = end_location.line.0;
-        insert_pos.1 = end_location.column.0;
+        target.end_line = end_location.line.0;
+        target.insert_pos.0 = end_location.line.0;
+        target.insert_pos.1 = end_location.column.0;
 
         if ReqParametersPath::is_linecol_in_loc(
             new_parameters.line,
@@ -198,18 +339,16 @@ fn handle_function(
             &module_env.get_loc(),
             &project.global_env,
         ) {
-            insert_pos.0 += 1;
+            target.insert_pos.0 += 1;
         }
 
-        result_string.push_str(
-            generate_fun_spec_zx(
-                &project.global_env,
-                module_env,
-                &func_env,
-                &using_module_map,
-            )
-            .as_str(),
+        target.content = generate_fun_spec_zx(
+            &project.global_env,
+            module_env,
+            &func_env,
+            &using_module_map,
         );
+        target.name = func_env.get_name_str();
         return true;
     }
     false
@@ -291,4 +430,7 @@ pub struct Resp {
     pub line: u32,
     pub col: u32,
     pub content: String,
+    /// The refresh of the spec already written, `content` is empty then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit: Option<WorkspaceEdit>,
 }
@@ -1,16 +1,17 @@
 // Copyright (c) The BitsLab.MoveBit Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use super::move_generate_spec::FunSpecGenerator;
+use super::move_generate_spec::{FunSpecGenerator, StructSpecGenerator};
+use crate::type_display_for_spec::SpecUseDecls;
 use im::HashSet;
 use move_model::{
     ast::{
         Address as MoveModelAddress, Exp as MoveModelExp, ExpData as MoveModelExpData, ModuleName,
         Operation, Pattern as MoveModelPattern, Value as MoveModelValue,
     },
-    model::{FunctionEnv, GlobalEnv, NodeId},
+    model::{FunctionEnv, GlobalEnv, NodeId, StructEnv},
     symbol::Symbol,
-    ty::Type as MoveModelType,
+    ty::{ReferenceKind, Type as MoveModelType},
 };
 use std::collections::HashMap;
 
@@ -163,7 +164,8 @@ pub(crate) enum SpecExpItem {
     TypeName {
         ty: MoveModelType,
     },
-    BorrowGlobalMut {
+    ResourceAccess {
+        reason: ResourceAccessReason,
         ty: MoveModelType,
         addr: MoveModelExp,
     },
@@ -175,6 +177,30 @@ pub(crate) enum SpecExpItem {
         if_exp: MoveModelExp,
         abort_exp: MoveModelExp,
     },
+    /// A field of a global resource written, `counter.value = 1` or
+    /// `borrow_global_mut<T>(addr).value = 1`.
+    ResourceWrite {
+        resource: ResourceRef,
+        field: Symbol,
+        value: MoveModelExp,
+    },
+    /// `event::emit(msg)`, or `event::emit_event(handle, msg)` for an event handle.
+    Emit {
+        msg: MoveModelExp,
+        handle: Option<MoveModelExp>,
+        resource: Option<ResourceRef>,
+    },
+    /// The value returned by the function, when it is a pure expression.
+    Result {
+        exp: MoveModelExp,
+    },
+    /// Call of a helper function of the same module, with the conditions of its body.
+    Callee {
+        params: Vec<Symbol>,
+        args: Vec<MoveModelExp>,
+        inst: Vec<MoveModelType>,
+        items: Vec<SpecExpItem>,
+    },
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -186,23 +212,100 @@ pub(crate) enum BinOPReason {
     UnderFlow,
 }
 
+/// A global resource written through a reference, `local` when the reference is bound with
+/// `let local = borrow_global_mut<T>(addr);`.
+#[derive(Clone)]
+pub(crate) struct ResourceRef {
+    pub local: Option<Symbol>,
+    pub ty: MoveModelType,
+    pub addr: MoveModelExp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResourceAccessReason {
+    BorrowGlobal,
+    BorrowGlobalMut,
+    MoveFrom,
+    MoveTo,
+}
+
+/// How the expressions of a helper function are written in the spec of its caller: the name of
+/// each parameter with the argument it is called with, and the type arguments of the call.
+#[derive(Clone, Debug, Default)]
+pub struct CalleeScope {
+    pub params: HashMap<String, String>,
+    pub inst: Vec<MoveModelType>,
+}
+
+impl CalleeScope {
+    pub fn instantiate(&self, ty: &MoveModelType) -> MoveModelType {
+        if self.inst.is_empty() {
+            ty.clone()
+        } else {
+            ty.instantiate(&self.inst)
+        }
+    }
+}
+
+/// Replace the parameters in `source` with the arguments in `params`, leaving alone the names
+/// that are qualified, like the module in `signer::address_of`, or selected fields.
+pub fn substitute_params(source: &str, params: &HashMap<String, String>) -> String {
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+    static IDENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap());
+    let mut ret = String::new();
+    let mut last = 0;
+    for m in IDENT.find_iter(source) {
+        let before = &source[..m.start()];
+        if before.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '.' || c == '@')
+            || before.ends_with("::")
+            || source[m.end()..].starts_with("::")
+        {
+            continue;
+        }
+        if let Some(arg) = params.get(m.as_str()) {
+            ret.push_str(&source[last..m.start()]);
+            ret.push_str(arg.as_str());
+            last = m.end();
+        }
+    }
+    ret.push_str(&source[last..]);
+    ret
+}
+
+/// `arg` as it is written in place of a parameter, in parentheses unless it is a single term.
+pub fn param_arg_str(arg: &str) -> String {
+    let arg = arg.trim();
+    let arg = arg
+        .strip_prefix("&mut ")
+        .or_else(|| arg.strip_prefix('&'))
+        .unwrap_or(arg)
+        .trim();
+    let mut depth = 0;
+    let single_term = arg.chars().all(|c| match c {
+        '(' | '[' | '{' => {
+            depth += 1;
+            true
+        },
+        ')' | ']' | '}' => {
+            depth -= 1;
+            true
+        },
+        _ => depth > 0 || c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '@' | '.'),
+    });
+    if single_term {
+        arg.to_string()
+    } else {
+        format!("({})", arg)
+    }
+}
+
 impl FunSpecGenerator {
-    fn collect_spec_exp_op_movefunc(
-        &self,
-        ret: &mut Vec<SpecExpItem>,
-        func_env: &FunctionEnv,
-        vec_exp: &Vec<MoveModelExp>,
-    ) {
+    fn collect_spec_exp_op_movefunc(&self, ret: &mut Vec<SpecExpItem>, func_env: &FunctionEnv) {
         const TYPE_OF: &str = "type_of";
         const TYPE_NAME: &str = "type_name";
         let para = func_env.get_parameters();
         match func_env.get_name_str().as_str() {
-            "borrow_global_mut" if !para.is_empty() && !vec_exp.is_empty() => {
-                ret.push(SpecExpItem::BorrowGlobalMut {
-                    ty: para.get(0).unwrap().1.clone(),
-                    addr: vec_exp.get(0).unwrap().clone(),
-                })
-            },
             TYPE_OF if !para.is_empty() => ret.push(SpecExpItem::TypeOf {
                 ty: para.get(0).unwrap().1.clone(),
             }),
@@ -213,9 +316,193 @@ impl FunSpecGenerator {
         }
     }
 
+    /// Conditions of the body of `callee`, a function of the module whose spec is generated, in
+    /// terms of the arguments it is called with.
+    fn collect_spec_exp_callee(
+        &self,
+        ret: &mut Vec<SpecExpItem>,
+        node_id: NodeId,
+        callee: &FunctionEnv,
+        vec_exp: &[MoveModelExp],
+        env: &GlobalEnv,
+    ) {
+        let callee_id = callee.get_qualified_id();
+        match self.callee_stack.borrow().first() {
+            Some(x) if x.module_id == callee_id.module_id => {},
+            _ => return,
+        }
+        // recursive calls.
+        if self.callee_stack.borrow().contains(&callee_id) {
+            return;
+        }
+        let items = match callee.get_def().as_deref() {
+            Some(exp) => {
+                self.callee_stack.borrow_mut().push(callee_id);
+                let resource_refs = self.resource_refs.take();
+                let items = self.collect_spec_exp_zx(exp, env);
+                *self.resource_refs.borrow_mut() = resource_refs;
+                self.callee_stack.borrow_mut().pop();
+                items
+            },
+            None => return,
+        };
+        let items: Vec<SpecExpItem> = items
+            .into_iter()
+            .filter(|x| {
+                !matches!(
+                    x,
+                    SpecExpItem::TypeOf { .. }
+                        | SpecExpItem::TypeName { .. }
+                        | SpecExpItem::Result { .. }
+                )
+            })
+            .collect();
+        if items.is_empty() {
+            return;
+        }
+        ret.push(SpecExpItem::Callee {
+            params: callee.get_parameters().iter().map(|p| p.0).collect(),
+            args: vec_exp.to_vec(),
+            inst: env.get_node_instantiation(node_id),
+            items,
+        });
+    }
+
+    /// The global resource `exp` is a reference into, through its fields.
+    fn resource_of(&self, exp: &MoveModelExp, env: &GlobalEnv) -> Option<ResourceRef> {
+        match exp.as_ref() {
+            MoveModelExpData::Call(_, Operation::Borrow(_), args)
+            | MoveModelExpData::Call(_, Operation::Select(..), args)
+                if args.len() == 1 =>
+            {
+                self.resource_of(&args[0], env)
+            },
+            MoveModelExpData::LocalVar(_, sym) => self.resource_refs.borrow().get(sym).cloned(),
+            MoveModelExpData::Call(
+                node_id,
+                Operation::BorrowGlobal(ReferenceKind::Mutable),
+                args,
+            ) => Some(ResourceRef {
+                local: None,
+                ty: env.get_node_instantiation(*node_id).first()?.clone(),
+                addr: args.first()?.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// The resource and the field written by `*lhs = ..`.
+    fn written_field(&self, lhs: &MoveModelExp, env: &GlobalEnv) -> Option<(ResourceRef, Symbol)> {
+        match lhs.as_ref() {
+            MoveModelExpData::Call(_, Operation::Borrow(_), args) if args.len() == 1 => {
+                self.written_field(&args[0], env)
+            },
+            MoveModelExpData::Call(_, Operation::Select(mid, sid, fid), args)
+                if args.len() == 1 =>
+            {
+                let resource = self.resource_of(&args[0], env)?;
+                let field = env
+                    .get_module(*mid)
+                    .get_struct(*sid)
+                    .get_field(*fid)
+                    .get_name();
+                Some((resource, field))
+            },
+            _ => None,
+        }
+    }
+
+    fn collect_spec_exp_emit(
+        &self,
+        ret: &mut Vec<SpecExpItem>,
+        callee: &FunctionEnv,
+        vec_exp: &[MoveModelExp],
+        env: &GlobalEnv,
+    ) {
+        if callee.module_env.get_full_name_str() != "0x1::event" {
+            return;
+        }
+        match (callee.get_name_str().as_str(), vec_exp) {
+            ("emit", [msg]) => ret.push(SpecExpItem::Emit {
+                msg: msg.clone(),
+                handle: None,
+                resource: None,
+            }),
+            ("emit_event", [handle, msg]) => ret.push(SpecExpItem::Emit {
+                msg: msg.clone(),
+                handle: Some(handle.clone()),
+                resource: self.resource_of(handle, env),
+            }),
+            _ => {},
+        }
+    }
+
+    /// The expression returned by `body`, if it is made of values, locals bound by the `let`
+    /// of `items` and operators only.
+    fn result_exp(
+        body: &MoveModelExp,
+        items: &[SpecExpItem],
+        env: &GlobalEnv,
+    ) -> Option<MoveModelExp> {
+        // the locals and parameters in the returned expression may have been assigned.
+        let mut has_assign = false;
+        body.visit_pre_order(&mut |e| {
+            if let MoveModelExpData::Assign(..) = e {
+                has_assign = true;
+            }
+            true
+        });
+        if has_assign {
+            return None;
+        }
+        let mut tail = body;
+        loop {
+            tail = match tail.as_ref() {
+                MoveModelExpData::Block(_, _, _, exp) => exp,
+                MoveModelExpData::Sequence(_, vec_exp) => vec_exp.last()?,
+                _ => break,
+            };
+        }
+        if let MoveModelType::Tuple(ts) = env.get_node_type(tail.as_ref().node_id()) {
+            if ts.is_empty() {
+                return None;
+            }
+        }
+        if !FunSpecGenerator::is_support_exp(tail, env) {
+            return None;
+        }
+        let mut is_pure = true;
+        tail.visit_pre_order(&mut |e| {
+            match e {
+                MoveModelExpData::Value(..)
+                | MoveModelExpData::LocalVar(..)
+                | MoveModelExpData::Temporary(..) => {},
+                MoveModelExpData::Call(_, op, _) if !matches!(op, Operation::MoveFunction(..)) => {
+                },
+                _ => is_pure = false,
+            }
+            true
+        });
+        let lets: HashSet<Symbol> = items
+            .iter()
+            .filter_map(|x| match x {
+                SpecExpItem::PatternLet {
+                    left: MoveModelPattern::Var(_, sym),
+                    ..
+                } => Some(*sym),
+                _ => None,
+            })
+            .collect();
+        if !is_pure || tail.free_vars().iter().any(|x| !lets.contains(x)) {
+            return None;
+        }
+        Some(tail.clone())
+    }
+
     fn collect_spec_exp_op(
         &self,
         ret: &mut Vec<SpecExpItem>,
+        node_id: NodeId,
         op: &Operation,
         vec_exp: &Vec<MoveModelExp>,
         env: &GlobalEnv,
@@ -321,12 +608,42 @@ impl FunSpecGenerator {
                     self.collect_spec_exp_(ret, exp, env);
                 }
             },
+            Operation::Select(..) => {
+                for exp in vec_exp.iter() {
+                    self.collect_spec_exp_(ret, exp, env);
+                }
+            },
+            Operation::BorrowGlobal(_) | Operation::MoveFrom | Operation::MoveTo => {
+                for exp in vec_exp.iter() {
+                    self.collect_spec_exp_(ret, exp, env);
+                }
+                let reason = match op {
+                    Operation::BorrowGlobal(ReferenceKind::Mutable) => {
+                        ResourceAccessReason::BorrowGlobalMut
+                    },
+                    Operation::BorrowGlobal(_) => ResourceAccessReason::BorrowGlobal,
+                    Operation::MoveFrom => ResourceAccessReason::MoveFrom,
+                    _ => ResourceAccessReason::MoveTo,
+                };
+                // the address of `move_to` is the one of its signer argument.
+                if let (Some(ty), Some(addr)) =
+                    (env.get_node_instantiation(node_id).first(), vec_exp.first())
+                {
+                    ret.push(SpecExpItem::ResourceAccess {
+                        reason,
+                        ty: ty.clone(),
+                        addr: addr.clone(),
+                    });
+                }
+            },
             Operation::MoveFunction(module_id, func_id) => {
-                self.collect_spec_exp_op_movefunc(
-                    ret,
-                    &env.get_function(module_id.qualified(*func_id)),
-                    vec_exp,
-                );
+                for exp in vec_exp.iter() {
+                    self.collect_spec_exp_(ret, exp, env);
+                }
+                let callee = env.get_function(module_id.qualified(*func_id));
+                self.collect_spec_exp_op_movefunc(ret, &callee);
+                self.collect_spec_exp_emit(ret, &callee, vec_exp, env);
+                self.collect_spec_exp_callee(ret, node_id, &callee, vec_exp, env);
             },
             Operation::Tuple => {
                 for exp in vec_exp.iter() {
@@ -350,6 +667,20 @@ impl FunSpecGenerator {
 
         match e.as_ref() {
             MoveModelExpData::Block(_, p, assign_exp, exp) => {
+                // `let local = borrow_global_mut<T>(addr);`
+                if let (MoveModelPattern::Var(_, sym), Some(x)) = (p, assign_exp) {
+                    if let MoveModelExpData::Call(_, Operation::BorrowGlobal(_), _) = x.as_ref() {
+                        if let Some(resource) = self.resource_of(x, env) {
+                            self.resource_refs.borrow_mut().insert(
+                                *sym,
+                                ResourceRef {
+                                    local: Some(*sym),
+                                    ..resource
+                                },
+                            );
+                        }
+                    }
+                }
                 if let MoveModelPattern::Var(_, _) = p {
                     match assign_exp {
                         Some(x) => {
@@ -380,9 +711,16 @@ impl FunSpecGenerator {
             MoveModelExpData::Mutate(_, exp_left, exp_right) => {
                 self.collect_spec_exp_(ret, exp_left, env);
                 self.collect_spec_exp_(ret, exp_right, env);
+                if let Some((resource, field)) = self.written_field(exp_left, env) {
+                    ret.push(SpecExpItem::ResourceWrite {
+                        resource,
+                        field,
+                        value: exp_right.clone(),
+                    });
+                }
             },
-            MoveModelExpData::Call(_, op, vec_exp) => {
-                self.collect_spec_exp_op(ret, op, vec_exp, env);
+            MoveModelExpData::Call(node_id, op, vec_exp) => {
+                self.collect_spec_exp_op(ret, *node_id, op, vec_exp, env);
             },
             MoveModelExpData::IfElse(_, if_exp, if_do_exp, else_do_exp) => {
                 // if if_do_exp is null and else_do_exp is abort, the source code is assert!()
@@ -422,6 +760,9 @@ impl FunSpecGenerator {
     ) -> Vec<SpecExpItem> {
         let mut ret: Vec<SpecExpItem> = Vec::new();
         self.collect_spec_exp_(&mut ret, exp, env);
+        if let Some(result) = FunSpecGenerator::result_exp(exp, &ret, env) {
+            ret.push(SpecExpItem::Result { exp: result });
+        }
         let (ret_after, _) = FunSpecGenerator::handle_unused_pattern(&ret, env);
         ret_after
     }
@@ -495,6 +836,25 @@ impl FunSpecGenerator {
                         used_local_var.insert(*sym);
                     });
                 },
+                SpecExpItem::ResourceAccess { addr, .. } => {
+                    addr.free_vars().iter().for_each(|sym| {
+                        used_local_var.insert(*sym);
+                    });
+                },
+                SpecExpItem::Callee { args, .. } => {
+                    for arg in args.iter() {
+                        arg.free_vars().iter().for_each(|sym| {
+                            used_local_var.insert(*sym);
+                        });
+                    }
+                },
+                SpecExpItem::ResourceWrite { value: exp, .. }
+                | SpecExpItem::Emit { msg: exp, .. }
+                | SpecExpItem::Result { exp } => {
+                    exp.free_vars().iter().for_each(|sym| {
+                        used_local_var.insert(*sym);
+                    });
+                },
                 SpecExpItem::PatternLet { left, right } => {
                     let _left_node_id = left.node_id();
                     let _left_node_loc = env.get_node_loc(_left_node_id);
@@ -661,3 +1021,351 @@ impl FunSpecGenerator {
         )
     }
 }
+
+/// A property of a field that holds for every value of a struct packed in its module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum FieldInvariant {
+    /// `field op bound`, or `len(field) op bound` when `is_len`.
+    Bound {
+        field: Symbol,
+        is_len: bool,
+        op: String,
+        bound: String,
+    },
+    /// `option::is_some(field)`, with `module` the `option` module.
+    IsSome { field: Symbol, module: ModuleName },
+}
+
+impl FieldInvariant {
+    pub(crate) fn field(&self) -> Symbol {
+        match self {
+            FieldInvariant::Bound { field, .. } | FieldInvariant::IsSome { field, .. } => *field,
+        }
+    }
+
+    /// The invariant, with the field read through `field` and the functions it calls named the
+    /// way the `use` declarations of the module allow.
+    pub(crate) fn display(
+        &self,
+        field: &str,
+        env: &GlobalEnv,
+        using_module_map: &SpecUseDecls,
+    ) -> String {
+        match self {
+            FieldInvariant::Bound {
+                is_len, op, bound, ..
+            } => {
+                if *is_len {
+                    format!("len({}) {} {}", field, op, bound)
+                } else {
+                    format!("{} {} {}", field, op, bound)
+                }
+            },
+            FieldInvariant::IsSome { module, .. } => {
+                let pool = env.symbol_pool();
+                let is_some = pool.make("is_some");
+                if let Some(x) = using_module_map.members.get(&(module.clone(), is_some)) {
+                    format!("{}({})", x.display(pool), field)
+                } else if let Some(x) = using_module_map.modules.get(module) {
+                    format!("{}::is_some({})", x.display(pool), field)
+                } else {
+                    format!("{}::is_some({})", module.display_full(env), field)
+                }
+            },
+        }
+    }
+}
+
+/// A local, or the length of a local vector, compared with a bound in an `assert!`.
+struct AssertedBound {
+    local: Symbol,
+    is_len: bool,
+    op: String,
+    bound: String,
+}
+
+impl StructSpecGenerator {
+    /// The invariants of the fields of `s` which every function of its module packing `s`
+    /// asserts for the values it is packed with, each with whether it is known to hold.
+    ///
+    /// An invariant is only known to hold when its field can not be changed after the pack: the
+    /// module never borrows the field, or the resource, mutably, and every assert it comes from
+    /// is always run before the pack on a local which is not changed in between.
+    pub(crate) fn collect_field_invariants(s: &StructEnv) -> Vec<(FieldInvariant, bool)> {
+        let env = s.module_env.env;
+        let mutated = StructSpecGenerator::mutated_fields(s);
+        let mut ret: Option<Vec<(FieldInvariant, bool)>> = None;
+        for fun_env in s.module_env.get_functions() {
+            if fun_env.is_test_only() {
+                continue;
+            }
+            let body = match fun_env.get_def() {
+                Some(x) => x,
+                None => continue,
+            };
+            let pos = |node_id: NodeId| env.get_node_loc(node_id).span().start();
+            let mut bounds = vec![];
+            let mut packs = vec![];
+            let mut changed_locals = std::collections::BTreeSet::new();
+            body.visit_pre_order(&mut |e| {
+                match e {
+                    MoveModelExpData::IfElse(node_id, cond, if_do_exp, else_do_exp)
+                        if StructSpecGenerator::is_assert(if_do_exp, else_do_exp) =>
+                    {
+                        if let Some(x) = StructSpecGenerator::asserted_bound(cond, env) {
+                            bounds.push((x, pos(*node_id)));
+                        }
+                    },
+                    MoveModelExpData::Call(node_id, Operation::Pack(mid, sid), args)
+                        if *mid == s.module_env.get_id() && *sid == s.get_id() =>
+                    {
+                        packs.push((args.clone(), pos(*node_id)));
+                    },
+                    MoveModelExpData::Assign(_, pattern, _) => {
+                        changed_locals.extend(pattern.vars().into_iter().map(|(_, x)| x));
+                    },
+                    MoveModelExpData::Call(_, Operation::Borrow(ReferenceKind::Mutable), args) => {
+                        if let Some(MoveModelExpData::LocalVar(_, sym)) =
+                            args.first().map(|x| x.as_ref())
+                        {
+                            changed_locals.insert(*sym);
+                        }
+                    },
+                    _ => {},
+                }
+                true
+            });
+            let mut straight_line = vec![];
+            StructSpecGenerator::straight_line_asserts(&body, env, &mut straight_line);
+            for (args, pack_pos) in packs {
+                let mut found: Vec<(FieldInvariant, bool)> = vec![];
+                let mut add = |invariant: FieldInvariant, holds: bool| match found
+                    .iter_mut()
+                    .find(|(x, _)| *x == invariant)
+                {
+                    Some((_, x)) => *x |= holds,
+                    None => found.push((invariant, holds)),
+                };
+                for (field_env, arg) in s.get_fields().zip(args.iter()) {
+                    let field = field_env.get_name();
+                    match arg.as_ref() {
+                        MoveModelExpData::LocalVar(_, sym) => {
+                            for (x, assert_pos) in bounds.iter().filter(|(x, _)| x.local == *sym) {
+                                let holds = straight_line.contains(assert_pos)
+                                    && *assert_pos < pack_pos
+                                    && !changed_locals.contains(sym);
+                                add(
+                                    FieldInvariant::Bound {
+                                        field,
+                                        is_len: x.is_len,
+                                        op: x.op.clone(),
+                                        bound: x.bound.clone(),
+                                    },
+                                    holds,
+                                );
+                            }
+                        },
+                        MoveModelExpData::Call(_, Operation::MoveFunction(mid, fid), _) => {
+                            let callee = env.get_function(mid.qualified(*fid));
+                            if callee.module_env.get_full_name_str() != "0x1::option"
+                                || callee.get_name_str() != "some"
+                            {
+                                continue;
+                            }
+                            add(
+                                FieldInvariant::IsSome {
+                                    field,
+                                    module: callee.module_env.get_name().clone(),
+                                },
+                                true,
+                            );
+                        },
+                        _ => {},
+                    }
+                }
+                ret = Some(match ret {
+                    None => found,
+                    Some(x) => x
+                        .into_iter()
+                        .filter_map(|(x, holds)| {
+                            let other = found.iter().find(|(y, _)| *y == x)?.1;
+                            Some((x, holds && other))
+                        })
+                        .collect(),
+                });
+            }
+        }
+        ret.unwrap_or_default()
+            .into_iter()
+            .map(|(x, holds)| {
+                let holds = holds
+                    && !mutated
+                        .as_ref()
+                        .map(|fields| fields.contains(&x.field()))
+                        .unwrap_or(true);
+                (x, holds)
+            })
+            .collect()
+    }
+
+    /// The fields of `s` its module may change after the pack: those it borrows mutably or
+    /// writes through a reference. None when it borrows the resource mutably, which may change
+    /// all of them.
+    fn mutated_fields(s: &StructEnv) -> Option<std::collections::BTreeSet<Symbol>> {
+        let env = s.module_env.env;
+        let is_field_of_s = |e: &MoveModelExp| -> Option<Symbol> {
+            match e.as_ref() {
+                MoveModelExpData::Call(_, Operation::Select(mid, sid, fid), _)
+                    if *mid == s.module_env.get_id() && *sid == s.get_id() =>
+                {
+                    Some(s.get_field(*fid).get_name())
+                },
+                _ => None,
+            }
+        };
+        let mut ret = std::collections::BTreeSet::new();
+        let mut whole = false;
+        for fun_env in s.module_env.get_functions() {
+            let body = match fun_env.get_def() {
+                Some(x) => x,
+                None => continue,
+            };
+            body.visit_pre_order(&mut |e| {
+                match e {
+                    MoveModelExpData::Call(
+                        node_id,
+                        Operation::BorrowGlobal(ReferenceKind::Mutable),
+                        _,
+                    ) => {
+                        if let Some(MoveModelType::Struct(mid, sid, _)) =
+                            env.get_node_instantiation(*node_id).first()
+                        {
+                            whole |= *mid == s.module_env.get_id() && *sid == s.get_id();
+                        }
+                    },
+                    // `&mut x.f`, `vector::push_back(&mut x.f, ..)`.
+                    MoveModelExpData::Call(_, Operation::Borrow(ReferenceKind::Mutable), args) => {
+                        ret.extend(args.first().and_then(is_field_of_s));
+                    },
+                    // `x.f = ..`
+                    MoveModelExpData::Mutate(_, lhs, _) => {
+                        ret.extend(is_field_of_s(lhs));
+                    },
+                    _ => {},
+                }
+                true
+            });
+        }
+        if whole {
+            None
+        } else {
+            Some(ret)
+        }
+    }
+
+    /// Positions of the asserts of `exp` which are always run, those out of branches and loops.
+    fn straight_line_asserts(
+        exp: &MoveModelExp,
+        env: &GlobalEnv,
+        ret: &mut Vec<codespan::ByteIndex>,
+    ) {
+        match exp.as_ref() {
+            MoveModelExpData::Sequence(_, es) => {
+                for e in es {
+                    StructSpecGenerator::straight_line_asserts(e, env, ret);
+                }
+            },
+            MoveModelExpData::Block(_, _, binding, body) => {
+                if let Some(x) = binding {
+                    StructSpecGenerator::straight_line_asserts(x, env, ret);
+                }
+                StructSpecGenerator::straight_line_asserts(body, env, ret);
+            },
+            MoveModelExpData::IfElse(node_id, _, if_do_exp, else_do_exp)
+                if StructSpecGenerator::is_assert(if_do_exp, else_do_exp) =>
+            {
+                ret.push(env.get_node_loc(*node_id).span().start());
+            },
+            _ => {},
+        }
+    }
+
+    /// `if (cond) () else abort code`, which is how `assert!(cond, code)` is compiled.
+    fn is_assert(if_do_exp: &MoveModelExp, else_do_exp: &MoveModelExp) -> bool {
+        matches!(if_do_exp.as_ref(), MoveModelExpData::Call(_, _, args) if args.is_empty())
+            && matches!(
+                else_do_exp.as_ref(),
+                MoveModelExpData::Call(_, Operation::Abort, args) if args.len() == 1
+            )
+    }
+
+    /// `local op bound` or `vector::length(&local) op bound` where `bound` is a constant,
+    /// compared the other way round too.
+    fn asserted_bound(cond: &MoveModelExp, env: &GlobalEnv) -> Option<AssertedBound> {
+        let (op, left, right) = match cond.as_ref() {
+            MoveModelExpData::Call(_, op, args) if args.len() == 2 => (op, &args[0], &args[1]),
+            _ => return None,
+        };
+        let (op, flipped) = match op {
+            Operation::Lt => ("<", ">"),
+            Operation::Le => ("<=", ">="),
+            Operation::Gt => (">", "<"),
+            Operation::Ge => (">=", "<="),
+            _ => return None,
+        };
+        let constant = |e: &MoveModelExp| -> Option<String> {
+            if !e.free_vars().is_empty() || !FunSpecGenerator::is_support_exp(e, env) {
+                return None;
+            }
+            let source = env
+                .get_source(&env.get_node_loc(e.as_ref().node_id()))
+                .ok()?;
+            Some(param_arg_str(source))
+        };
+        let ((local, is_len), op, bound) = match (
+            StructSpecGenerator::bounded_local(left, env),
+            constant(right),
+        ) {
+            (Some(x), Some(bound)) => (x, op, bound),
+            _ => (
+                StructSpecGenerator::bounded_local(right, env)?,
+                flipped,
+                constant(left)?,
+            ),
+        };
+        Some(AssertedBound {
+            local,
+            is_len,
+            op: op.to_string(),
+            bound,
+        })
+    }
+
+    /// The local `exp` reads, and whether it is read through `vector::length`.
+    fn bounded_local(exp: &MoveModelExp, env: &GlobalEnv) -> Option<(Symbol, bool)> {
+        match exp.as_ref() {
+            MoveModelExpData::LocalVar(_, sym) => Some((*sym, false)),
+            MoveModelExpData::Call(_, Operation::MoveFunction(mid, fid), args)
+                if args.len() == 1 =>
+            {
+                let callee = env.get_function(mid.qualified(*fid));
+                if callee.module_env.get_full_name_str() != "0x1::vector"
+                    || callee.get_name_str() != "length"
+                {
+                    return None;
+                }
+                let vector = match args[0].as_ref() {
+                    MoveModelExpData::Call(_, Operation::Borrow(_), args) if args.len() == 1 => {
+                        &args[0]
+                    },
+                    _ => &args[0],
+                };
+                match vector.as_ref() {
+                    MoveModelExpData::LocalVar(_, sym) => Some((*sym, true)),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+}
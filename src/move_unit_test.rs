@@ -0,0 +1,591 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs the unit tests of a Move package inside the language server.
+//!
+//! The tests are executed on a worker thread through `move-cli`, the progress of the run is
+//! streamed to the client with `$/progress`, and the textual report of the test runner is parsed
+//! into `UnitTestReport` which is handed back to the main loop so that the failures can be
+//! published as diagnostics.
+
+use crate::{
+    context::Context, multiproject::MultiProject, progress::ProgressReporter, project::Project,
+    utils::discover_manifest_and_kind,
+};
+use aptos_gas_schedule::{MiscGasParameters, NativeGasParameters, LATEST_GAS_FEATURE_VERSION};
+use aptos_types::on_chain_config::{Features, TimedFeaturesBuilder};
+use aptos_vm::natives;
+use crossbeam::channel::Sender;
+use lsp_server::{ErrorCode, Message, Request, Response};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, ExecuteCommandParams, Location,
+    Position, Range,
+};
+use move_cli::base::test::{run_move_unit_tests, UnitTestResult};
+use move_core_types::effects::ChangeSet;
+use move_model::{
+    ast::Value,
+    model::{GlobalEnv, ModuleEnv},
+};
+use move_unit_test::UnitTestingConfig;
+use move_vm_runtime::native_functions::NativeFunctionTable;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tempfile::tempdir;
+use url::Url;
+
+/// `workspace/executeCommand` command which runs the unit tests of a package.
+pub const RUN_TESTS_COMMAND: &str = "aptos-move-analyzer.run_tests";
+
+/// Maximum number of instructions a single test may execute.
+const INSTRUCTION_EXECUTION_BOUND: u64 = 100_000;
+
+pub type TestSender = Arc<Mutex<Sender<UnitTestReport>>>;
+
+#[derive(Clone, Deserialize)]
+pub struct RunTestsArgs {
+    /// Any file inside the package whose tests should be run.
+    pub fpath: String,
+    /// Only run tests whose fully qualified name contains this string.
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+/// One failing test as reported by the test runner.
+#[derive(Clone, Debug, Default)]
+pub struct UnitTestFailure {
+    /// Module of the test, like `0xcafe::m`.
+    pub module_name: String,
+    pub test_name: String,
+    /// Abort location, 0-based line and column.
+    pub abort_location: Option<(PathBuf, u32, u32)>,
+    pub message: String,
+}
+
+impl UnitTestFailure {
+    pub fn abort_range(&self) -> Option<(PathBuf, Range)> {
+        self.abort_location.as_ref().map(|(path, line, col)| {
+            (
+                path.clone(),
+                Range::new(Position::new(*line, *col), Position::new(*line, *col + 1)),
+            )
+        })
+    }
+}
+
+/// Abort codes mentioned by the test runner for a failed test.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AbortCodes {
+    /// Code from `#[expected_failure(abort_code = ...)]`.
+    pub expected: Option<u64>,
+    /// Code the test actually aborted with.
+    pub actual: Option<u64>,
+    /// Module where the abort happened, like `0xcafe::m`.
+    pub module_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UnitTestReport {
+    pub manifest: PathBuf,
+    /// A source file of the package, used to find the project.
+    pub fpath: PathBuf,
+    pub passed: Vec<String>,
+    pub failures: Vec<UnitTestFailure>,
+    /// Set when the package could not be built or the runner failed.
+    pub error: Option<String>,
+}
+
+pub fn on_run_tests(
+    context: &Context,
+    request: &Request,
+    parameters: ExecuteCommandParams,
+    test_sender: TestSender,
+) {
+    let work_done_token = parameters.work_done_progress_params.work_done_token;
+    let args = match parameters
+        .arguments
+        .into_iter()
+        .next()
+        .map(serde_json::from_value::<RunTestsArgs>)
+    {
+        Some(Ok(x)) => x,
+        _ => {
+            send_err(
+                context,
+                request,
+                "expected arguments {fpath, filter}.".to_string(),
+            );
+            return;
+        },
+    };
+    let fpath = PathBuf::from_str(args.fpath.as_str()).unwrap();
+    let (mani, _) = match discover_manifest_and_kind(&fpath) {
+        Some(x) => x,
+        None => {
+            send_err(context, request, "not a move project.".to_string());
+            return;
+        },
+    };
+
+    let sender = context.connection.sender.clone();
+    let id = request.id.clone();
+    std::thread::spawn(move || {
+        let progress =
+            ProgressReporter::begin(&sender, work_done_token, "move/test", "Move unit tests");
+        let mut writer = ProgressWriter {
+            progress: progress.clone(),
+            output: Vec::new(),
+            line: Vec::new(),
+        };
+        let run = run_tests(mani.as_path(), args.filter.clone(), &mut writer);
+        let output = String::from_utf8_lossy(&writer.output).to_string();
+        let mut report = parse_test_output(output.as_str());
+        report.manifest = mani;
+        report.fpath = fpath;
+        if let Err(err) = run {
+            report.error = Some(format!("{:?}", err));
+        }
+        progress.end(Some(format!(
+            "{} passed, {} failed",
+            report.passed.len(),
+            report.failures.len()
+        )));
+        let r = Response::new_ok(
+            id,
+            serde_json::json!({
+                "passed": report.passed.len(),
+                "failed": report.failures.len(),
+                "error": report.error,
+                "output": output,
+            }),
+        );
+        let _ = sender.send(Message::Response(r));
+        // the main loop is gone when the server shuts down during the run.
+        let sent = match test_sender.lock() {
+            Ok(x) => x.send(report).map_err(|err| format!("{:?}", err)),
+            Err(err) => Err(format!("{:?}", err)),
+        };
+        if let Err(err) = sent {
+            log::error!("send the test report failed,err:{}", err);
+        }
+    });
+}
+
+pub fn run_tests<W: Write + Send>(
+    pkg_path: &Path,
+    filter: Option<String>,
+    writer: &mut W,
+) -> anyhow::Result<UnitTestResult> {
+    // removed when dropped, it has to outlive the build.
+    let install_dir = tempdir()?;
+    let build_config = move_package::BuildConfig {
+        test_mode: true,
+        install_dir: Some(install_dir.path().to_path_buf()),
+        skip_fetch_latest_git_deps: true,
+        ..Default::default()
+    };
+    let unit_test_config = UnitTestingConfig {
+        filter,
+        ..UnitTestingConfig::default_with_bound(Some(INSTRUCTION_EXECUTION_BOUND))
+    };
+    run_move_unit_tests(
+        pkg_path,
+        build_config,
+        unit_test_config,
+        aptos_test_natives(),
+        ChangeSet::new(),
+        None,
+        false,
+        writer,
+    )
+}
+
+/// The natives of the Aptos framework and of the standard library, with the debug natives, as
+/// `aptos move test` has them.
+fn aptos_test_natives() -> NativeFunctionTable {
+    // also installs the native context extensions of the framework, like the one of `event`.
+    natives::configure_for_unit_test();
+    natives::aptos_natives(
+        LATEST_GAS_FEATURE_VERSION,
+        NativeGasParameters::zeros(),
+        MiscGasParameters::zeros(),
+        TimedFeaturesBuilder::enable_all().build(),
+        Features::default(),
+    )
+}
+
+/// Collects the output of the test runner and reports every finished test as progress.
+struct ProgressWriter {
+    progress: ProgressReporter,
+    output: Vec<u8>,
+    line: Vec<u8>,
+}
+
+impl Write for ProgressWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        for b in buf {
+            if *b != b'\n' {
+                self.line.push(*b);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.line).to_string();
+            if let Some((_, name)) = parse_test_status_line(line.as_str()) {
+                self.progress.report(line.trim().to_string(), None);
+                log::trace!("test finished: {}", name);
+            }
+            self.line.clear();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Parse `[ PASS    ] 0x1::m::test` into `("PASS", "0x1::m::test")`.
+fn parse_test_status_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let rest = line.strip_prefix('[')?;
+    let end = rest.find(']')?;
+    let status = rest[..end].trim();
+    if !matches!(status, "PASS" | "FAIL" | "TIMEOUT") {
+        return None;
+    }
+    Some((status, rest[end + 1..].trim()))
+}
+
+/// Split `0x1::m::test` into `("0x1::m", "test")`.
+fn split_test_name(full_name: &str) -> (String, String) {
+    match full_name.rfind("::") {
+        Some(index) => (
+            full_name[..index].to_string(),
+            full_name[index + 2..].to_string(),
+        ),
+        None => (String::new(), full_name.to_string()),
+    }
+}
+
+/// Parse the textual report written by the Move unit test runner.
+pub fn parse_test_output(output: &str) -> UnitTestReport {
+    let mut report = UnitTestReport::default();
+    let mut failed: Vec<(String, String)> = vec![];
+    for line in output.lines() {
+        match parse_test_status_line(line) {
+            Some(("PASS", name)) => report.passed.push(name.to_string()),
+            Some((_, name)) => failed.push(split_test_name(name)),
+            None => {},
+        }
+    }
+
+    // Failure details are printed in boxes like
+    // ┌── test_name ──────
+    // │ error[E11001]: test failure
+    // │    ┌─ /path/sources/m.move:12:9
+    // ...
+    // └──────────────────
+    let mut current_module = String::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(m) = trimmed.strip_prefix("Failures in ") {
+            current_module = m.trim_end_matches(':').to_string();
+            continue;
+        }
+        if let Some(title) = trimmed.strip_prefix("┌──") {
+            let name = title.trim().trim_end_matches('─').trim().to_string();
+            current = Some((name, vec![]));
+            continue;
+        }
+        if trimmed.starts_with("└──") {
+            if let Some((name, body)) = current.take() {
+                report
+                    .failures
+                    .push(parse_failure_body(&current_module, name, &body));
+            }
+            continue;
+        }
+        if let Some((_, body)) = current.as_mut() {
+            body.push(
+                line.trim_start()
+                    .strip_prefix('│')
+                    .unwrap_or(line)
+                    .to_string(),
+            );
+        }
+    }
+
+    // Tests reported as failed without a detailed box still deserve a diagnostic.
+    for (module_name, test_name) in failed {
+        if !report
+            .failures
+            .iter()
+            .any(|x| x.test_name == test_name && is_same_module(&module_name, &x.module_name))
+        {
+            report.failures.push(UnitTestFailure {
+                module_name,
+                test_name,
+                abort_location: None,
+                message: "test failed".to_string(),
+            });
+        }
+    }
+    report
+}
+
+fn parse_failure_body(module_name: &str, test_name: String, body: &[String]) -> UnitTestFailure {
+    let mut abort_location = None;
+    let mut labels: Vec<String> = vec![];
+    for line in body.iter() {
+        let trimmed = line.trim();
+        if let Some(loc_str) = trimmed.strip_prefix("┌─") {
+            if abort_location.is_none() {
+                abort_location = parse_path_line_col(loc_str.trim());
+            }
+            continue;
+        }
+        // The primary label follows the `^^^^` marker of codespan, right after the gutter.
+        if let Some(marker) = trimmed.strip_prefix('│').map(|x| x.trim_start()) {
+            if marker.starts_with('^') {
+                let label = marker.trim_start_matches('^').trim();
+                if !label.is_empty() {
+                    labels.push(label.to_string());
+                }
+            }
+        }
+    }
+    let message = if labels.is_empty() {
+        body.iter()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        labels.join("\n")
+    };
+    UnitTestFailure {
+        module_name: module_name.to_string(),
+        test_name,
+        abort_location,
+        message,
+    }
+}
+
+/// Parse `/path/to/file.move:12:9` to a path and a 0-based line and column.
+fn parse_path_line_col(s: &str) -> Option<(PathBuf, u32, u32)> {
+    let mut it = s.rsplitn(3, ':');
+    let col = it.next()?.trim().parse::<u32>().ok()?;
+    let line = it.next()?.trim().parse::<u32>().ok()?;
+    let path = it.next()?;
+    Some((
+        PathBuf::from(path),
+        line.saturating_sub(1),
+        col.saturating_sub(1),
+    ))
+}
+
+/// Extract the expected and actual abort codes from a failure message like
+/// `... Expected test to abort with code 2, but instead it aborted with code 1 originating in
+/// the module 0xcafe::m rooted here`.
+pub fn parse_abort_codes(message: &str) -> AbortCodes {
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+    static EXPECTED: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"Expected test to abort with code (\d+)").unwrap());
+    static ACTUAL: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"(?:instead it|but it) aborted with code (\d+)(?: originating in the module (\S+))?",
+        )
+        .unwrap()
+    });
+    let mut ret = AbortCodes::default();
+    if let Some(c) = EXPECTED.captures(message) {
+        ret.expected = c.get(1).and_then(|x| x.as_str().parse().ok());
+    }
+    if let Some(c) = ACTUAL.captures(message) {
+        ret.actual = c.get(1).and_then(|x| x.as_str().parse().ok());
+        ret.module_name = c.get(2).map(|x| x.as_str().to_string());
+    }
+    ret
+}
+
+/// Categories of `std::error`, an Aptos abort code is `category << 16 | reason`.
+const ERROR_CATEGORIES: [&str; 13] = [
+    "INVALID_ARGUMENT",
+    "OUT_OF_RANGE",
+    "INVALID_STATE",
+    "UNAUTHENTICATED",
+    "PERMISSION_DENIED",
+    "NOT_FOUND",
+    "ABORTED",
+    "ALREADY_EXISTS",
+    "RESOURCE_EXHAUSTED",
+    "CANCELLED",
+    "INTERNAL",
+    "NOT_IMPLEMENTED",
+    "UNAVAILABLE",
+];
+
+/// Render an abort code with the name of the constant it comes from, looked up in the module
+/// where the abort happened, e.g. `0x50001 (PERMISSION_DENIED: E_NOT_OWNER)`.
+fn describe_abort_code(
+    project: Option<&Project>,
+    module_name: Option<&String>,
+    code: u64,
+) -> String {
+    let category = code >> 16;
+    let (category_name, reason) = if (1..=ERROR_CATEGORIES.len() as u64).contains(&category) {
+        (Some(ERROR_CATEGORIES[category as usize - 1]), code & 0xFFFF)
+    } else {
+        (None, code)
+    };
+    let const_name = project.zip(module_name).and_then(|(project, module_name)| {
+        let env = &project.global_env;
+        let module_env = find_module(env, module_name)?;
+        let const_env = module_env
+            .get_named_constants()
+            .find(|c| match c.get_value() {
+                Value::Number(n) => n.to_string() == reason.to_string(),
+                _ => false,
+            })?;
+        Some(const_env.get_name().display(env.symbol_pool()).to_string())
+    });
+    let code_str = if category_name.is_some() {
+        format!("{:#x}", code)
+    } else {
+        code.to_string()
+    };
+    match (category_name, const_name) {
+        (Some(c), Some(n)) => format!("{} ({}: {})", code_str, c, n),
+        (Some(c), None) => format!("{} ({}: {})", code_str, c, reason),
+        (None, Some(n)) => format!("{} ({})", code_str, n),
+        (None, None) => code_str,
+    }
+}
+
+/// The module `addr::name` as the test runner prints it, the address in hex with or without
+/// leading zeros.
+fn find_module<'a>(env: &'a GlobalEnv, full_name: &str) -> Option<ModuleEnv<'a>> {
+    env.get_modules()
+        .find(|m| is_same_module(m.get_full_name_str().as_str(), full_name))
+}
+
+/// Whether the module names `a` and `b`, both `addr::name`, are the same module.
+pub fn is_same_module(a: &str, b: &str) -> bool {
+    let split = |x: &str| -> Option<(String, String)> {
+        let (addr, name) = x.split_once("::")?;
+        let addr = addr.strip_prefix("0x").unwrap_or(addr);
+        Some((
+            addr.trim_start_matches('0').to_lowercase(),
+            name.to_string(),
+        ))
+    };
+    match (split(a), split(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Location of the identifier of a test function.
+fn test_function_location(project: &Project, failure: &UnitTestFailure) -> Option<Location> {
+    let env = &project.global_env;
+    let module_env = find_module(env, failure.module_name.as_str())?;
+    let fun_env = module_env
+        .get_functions()
+        .find(|f| f.get_name_str() == failure.test_name)?;
+    let loc = fun_env.get_id_loc();
+    let url = Url::from_file_path(PathBuf::from(env.get_file(loc.file_id()))).ok()?;
+    Some(Location::new(url, project.loc_to_range(&loc)))
+}
+
+/// Convert the failures of a test run to diagnostics. Every failure gets a diagnostic on the test
+/// function, and one more on the abort site in the code under test when the runner knows it.
+pub fn test_failure_diagnostics(
+    projects: &MultiProject,
+    report: &UnitTestReport,
+) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut result: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    let project = projects.get_project(&report.fpath);
+    for failure in report.failures.iter() {
+        let codes = parse_abort_codes(failure.message.as_str());
+        let mut message = format!("test {}::{} failed", failure.module_name, failure.test_name);
+        match (codes.expected, codes.actual) {
+            (Some(expected), Some(actual)) => message.push_str(
+                format!(
+                    "\nexpected abort code: {}\nactual abort code: {}",
+                    describe_abort_code(
+                        project,
+                        codes.module_name.as_ref().or(Some(&failure.module_name)),
+                        expected
+                    ),
+                    describe_abort_code(project, codes.module_name.as_ref(), actual),
+                )
+                .as_str(),
+            ),
+            (None, Some(actual)) => message.push_str(
+                format!(
+                    "\naborted with code: {}",
+                    describe_abort_code(project, codes.module_name.as_ref(), actual),
+                )
+                .as_str(),
+            ),
+            _ => {},
+        }
+        message.push_str(format!("\n{}", failure.message).as_str());
+
+        let fun_location = project.and_then(|p| test_function_location(p, failure));
+        let abort_location = failure.abort_range().and_then(|(path, range)| {
+            Url::from_file_path(path.as_path())
+                .ok()
+                .map(|url| Location::new(url, range))
+        });
+        if fun_location.is_none() && abort_location.is_none() {
+            log::error!(
+                "could not locate failed test {}::{}",
+                failure.module_name,
+                failure.test_name
+            );
+            continue;
+        }
+        let mk_diag = |location: &Location, related: Option<(&Location, &str)>| Diagnostic {
+            range: location.range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("move-test".to_string()),
+            message: message.clone(),
+            related_information: related.map(|(l, m)| {
+                vec![DiagnosticRelatedInformation {
+                    location: l.clone(),
+                    message: m.to_string(),
+                }]
+            }),
+            ..Default::default()
+        };
+        if let Some(l) = fun_location.as_ref() {
+            let d = mk_diag(l, abort_location.as_ref().map(|x| (x, "aborted here")));
+            result.entry(l.uri.clone()).or_default().push(d);
+        }
+        if let Some(l) = abort_location.as_ref() {
+            if fun_location.as_ref() != Some(l) {
+                let d = mk_diag(l, fun_location.as_ref().map(|x| (x, "failed test")));
+                result.entry(l.uri.clone()).or_default().push(d);
+            }
+        }
+    }
+    result
+}
+
+fn send_err(context: &Context, request: &Request, msg: String) {
+    let r = Response::new_err(request.id.clone(), ErrorCode::UnknownErrorCode as i32, msg);
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
@@ -1,9 +1,26 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::context::*;
+use crate::{
+    context::*,
+    multiproject::send_show_message,
+    utils::{
+        discover_manifest_and_kind, lexer_for_buffer, parse_buffer, parse_buffer_with_diags,
+        path_concat, LineIndex,
+    },
+};
 use lsp_server::*;
 use lsp_types::*;
+use move_compiler::parser::ast::{Definition, ModuleMember};
+use move_package::source_package::layout::SourcePackageLayout;
+use similar::{DiffTag, TextDiff};
+use std::path::Path;
+
+/// Formatting config file of a package, next to `Move.toml`.
+pub const MOVEFMT_CONFIG_NAME: &str = "movefmt.toml";
+
+/// Table of `Move.toml` holding the formatting config, when there is no `movefmt.toml`.
+pub const FORMATTING_TABLE_NAME: &str = "formatting";
 
 #[allow(unused)]
 #[derive(Clone, Copy, serde::Deserialize, Debug)]
@@ -47,35 +64,309 @@ pub fn on_movefmt_request(
         .expect("could not deserialize Reference request");
     let fpath = parameters.text_document.uri.to_file_path().unwrap();
 
-    let content_origin = std::fs::read_to_string(&fpath).unwrap();
-    let mut movefmt_cfg = commentfmt::Config::default();
-    movefmt_cfg.set().max_width(fmt_cfg.max_width as usize);
-    movefmt_cfg.set().indent_size(fmt_cfg.indent_size as usize);
-    let content_format =
-        movefmt::core::fmt::format_entry(content_origin.clone(), movefmt_cfg).unwrap();
-
-    let result_line =
-        if content_format.clone().lines().count() >= content_origin.clone().lines().count() {
-            content_format.clone().lines().count()
-        } else {
-            content_origin.clone().lines().count()
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let content_origin = match context.projects.get_document(&fpath) {
+        Some(x) => x,
+        None => return send_response(context, request, None),
+    };
+    match format_buffer(&content_origin, &fpath, fmt_cfg) {
+        Ok(content_format) => {
+            let result = Some(text_edits(&content_origin, &content_format, None));
+            send_response(context, request, result)
+        },
+        Err(err) => send_format_err(context, request, &fpath, err),
+    }
+}
+
+/// Handles textDocument/rangeFormatting, only the top level items overlapping the range are
+/// formatted.
+pub fn on_movefmt_range_request(
+    context: &Context,
+    request: &Request,
+    fmt_cfg: &FmtConfig,
+) -> lsp_server::Response {
+    log::info!("on_movefmt_range_request request = {:?}", request);
+    let parameters =
+        serde_json::from_value::<DocumentRangeFormattingParams>(request.params.clone())
+            .expect("could not deserialize range formatting request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let content_origin = match context.projects.get_document(&fpath) {
+        Some(x) => x,
+        None => return send_response(context, request, None),
+    };
+    let lines = item_lines(&content_origin, parameters.range);
+    match format_buffer(&content_origin, &fpath, fmt_cfg) {
+        Ok(content_format) => {
+            let result = Some(text_edits(&content_origin, &content_format, Some(lines)));
+            send_response(context, request, result)
+        },
+        Err(err) => send_format_err(context, request, &fpath, err),
+    }
+}
+
+/// Handles textDocument/onTypeFormatting, the top level item just edited is formatted when `}`
+/// or `;` is typed.
+pub fn on_movefmt_on_type_request(
+    context: &Context,
+    request: &Request,
+    fmt_cfg: &FmtConfig,
+) -> lsp_server::Response {
+    log::info!("on_movefmt_on_type_request request = {:?}", request);
+    let parameters =
+        serde_json::from_value::<DocumentOnTypeFormattingParams>(request.params.clone())
+            .expect("could not deserialize on type formatting request");
+    let fpath = parameters
+        .text_document_position
+        .text_document
+        .uri
+        .to_file_path()
+        .unwrap();
+    let pos = parameters.text_document_position.position;
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let content_origin = match context.projects.get_document(&fpath) {
+        Some(x) => x,
+        None => return send_response(context, request, None),
+    };
+    let lines = item_lines(&content_origin, Range::new(pos, pos));
+    match format_buffer(&content_origin, &fpath, fmt_cfg) {
+        Ok(content_format) => {
+            let result = Some(text_edits(&content_origin, &content_format, Some(lines)));
+            send_response(context, request, result)
+        },
+        Err(err) => {
+            // the code is usually incomplete while typing, don't bother the user.
+            log::info!("on type formatting skipped,err:{}", err);
+            send_response(context, request, None)
+        },
+    }
+}
+
+/// Why a buffer was not formatted.
+#[derive(Clone, Debug)]
+pub struct FmtError {
+    pub message: String,
+    /// Where in the original buffer.
+    pub range: Option<Range>,
+}
+
+impl std::fmt::Display for FmtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.range {
+            Some(range) => write!(
+                f,
+                "{}:{}: {}",
+                range.start.line + 1,
+                range.start.character + 1,
+                self.message
+            ),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+fn format_buffer(content: &str, fpath: &Path, fmt_cfg: &FmtConfig) -> Result<String, FmtError> {
+    format_and_verify(content, movefmt_config(fpath, fmt_cfg))
+}
+
+/// Format `content`, refusing files which do not parse, and formatted output whose tokens differ
+/// from the original, so formatting never changes the meaning of the code.
+pub fn format_and_verify(
+    content: &str,
+    movefmt_cfg: commentfmt::Config,
+) -> Result<String, FmtError> {
+    let index = LineIndex::new(content);
+    if let Err(diags) = parse_buffer_with_diags(content) {
+        let mut err = FmtError {
+            message: "syntax error".to_string(),
+            range: None,
         };
+        if let Some((_, msg, (loc, label), _, _)) = diags.into_codespan_format().into_iter().next()
+        {
+            err.message = format!("{}, {}", msg, label);
+            err.range = Some(index.range(&loc));
+        }
+        return Err(err);
+    }
+
+    let origin = content.to_string();
+    let formatted = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+        movefmt::core::fmt::format_entry(origin, movefmt_cfg)
+    })) {
+        Ok(Ok(x)) => x,
+        Ok(Err(err)) => {
+            return Err(FmtError {
+                message: format!("movefmt failed, {:?}", err),
+                range: None,
+            })
+        },
+        Err(_) => {
+            return Err(FmtError {
+                message: "movefmt panicked".to_string(),
+                range: None,
+            })
+        },
+    };
 
-    let result = Some(vec![TextEdit {
-        range: lsp_types::Range {
-            start: Position {
-                line: 0,
-                character: 0,
+    // the token streams, which leave out whitespace and comments, must be the same.
+    let origin_tokens = lexer_for_buffer(content);
+    let formatted_tokens = lexer_for_buffer(&formatted);
+    let token_text = |buffer: &str, (start, end): (usize, usize)| buffer[start..end].to_string();
+    for i in 0..origin_tokens.len().max(formatted_tokens.len()) {
+        let same = match (origin_tokens.get(i), formatted_tokens.get(i)) {
+            (Some((t1, r1)), Some((t2, r2))) => {
+                t1 == t2 && token_text(content, *r1) == token_text(&formatted, *r2)
             },
-            end: Position {
-                line: result_line as u32,
-                character: 0,
+            _ => false,
+        };
+        if !same {
+            return Err(FmtError {
+                message: "formatted code is not equivalent to the original, nothing changed"
+                    .to_string(),
+                range: origin_tokens
+                    .get(i)
+                    .or(origin_tokens.last())
+                    .map(|(_, (start, end))| {
+                        Range::new(index.position(*start), index.position(*end))
+                    }),
+            });
+        }
+    }
+    Ok(formatted)
+}
+
+fn send_format_err(
+    context: &Context,
+    request: &Request,
+    fpath: &Path,
+    err: FmtError,
+) -> lsp_server::Response {
+    log::error!("format {:?} failed,err:{}", fpath, err);
+    let msg = format!("format {} failed: {}", fpath.display(), err);
+    send_show_message(&context.connection, MessageType::ERROR, msg.clone());
+    let r = Response::new_err(request.id.clone(), ErrorCode::UnknownErrorCode as i32, msg);
+    context
+        .connection
+        .sender
+        .send(Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+/// Formatting config of the package `fpath` belongs to. A `movefmt.toml` next to `Move.toml`,
+/// or a `[formatting]` table in `Move.toml`, wins over the settings pushed by the client, so the
+/// result is the same in every editor and in CI.
+pub fn movefmt_config(fpath: &Path, fmt_cfg: &FmtConfig) -> commentfmt::Config {
+    let mut ret = discover_manifest_and_kind(fpath)
+        .and_then(|(mani, _)| load_project_fmt_config(&mani))
+        .unwrap_or_default();
+    if !ret.was_set().max_width() {
+        ret.set().max_width(fmt_cfg.max_width as usize);
+    }
+    if !ret.was_set().indent_size() {
+        ret.set().indent_size(fmt_cfg.indent_size as usize);
+    }
+    ret
+}
+
+fn load_project_fmt_config(mani: &Path) -> Option<commentfmt::Config> {
+    let path = mani.join(MOVEFMT_CONFIG_NAME);
+    if path.exists() {
+        return match commentfmt::Config::from_toml_path(&path) {
+            Ok(x) => Some(x),
+            Err(err) => {
+                log::error!("load {:?} failed,err:{:?}", path, err);
+                None
             },
+        };
+    }
+
+    // `[formatting]` of Move.toml.
+    let manifest = std::fs::read_to_string(mani.join(SourcePackageLayout::Manifest.path())).ok()?;
+    let table = match toml::from_str::<toml::Value>(&manifest) {
+        Ok(x) => x.get(FORMATTING_TABLE_NAME)?.clone(),
+        Err(_) => return None,
+    };
+    // the same loader as movefmt.toml, which validates the options, from the table in memory.
+    match commentfmt::Config::from_toml(toml::to_string(&table).ok()?.as_str(), mani) {
+        Ok(x) => Some(x),
+        Err(err) => {
+            log::error!("load [formatting] of {:?} failed,err:{:?}", mani, err);
+            None
         },
-        new_text: content_format,
-    }]);
-    let r: Response = Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap());
+    }
+}
+
+/// Lines (inclusive) of the top level items overlapping `range`, or the lines of `range` itself
+/// if it is outside of any item.
+pub fn item_lines(content: &str, range: Range) -> (u32, u32) {
+    let index = LineIndex::new(content);
+    let (mut start, mut end) = (range.start.line, range.end.line);
+    let mut locs = vec![];
+    for d in parse_buffer(content).unwrap_or_default().iter() {
+        let modules = match d {
+            Definition::Module(m) => vec![m],
+            Definition::Address(a) => a.modules.iter().collect(),
+            Definition::Script(s) => {
+                locs.push(s.loc);
+                vec![]
+            },
+        };
+        for m in modules {
+            for member in m.members.iter() {
+                match member {
+                    ModuleMember::Function(x) => locs.push(x.loc),
+                    ModuleMember::Struct(x) => locs.push(x.loc),
+                    ModuleMember::Constant(x) => locs.push(x.loc),
+                    ModuleMember::Spec(x) => locs.push(x.loc),
+                    _ => {},
+                }
+            }
+        }
+    }
+    for loc in locs.iter() {
+        let item = index.range(loc);
+        if item.start.line <= range.end.line && range.start.line <= item.end.line {
+            start = start.min(item.start.line);
+            end = end.max(item.end.line);
+        }
+    }
+    (start, end)
+}
+
+/// Minimal edits turning `origin` into `formatted`, so the cursor and the undo history of the
+/// untouched lines survive. With `lines`, only the edits inside these lines are kept.
+pub fn text_edits(origin: &str, formatted: &str, lines: Option<(u32, u32)>) -> Vec<TextEdit> {
+    let diff = TextDiff::from_lines(origin, formatted);
+    let new_lines = diff.new_slices();
+    let mut ret = vec![];
+    for op in diff.ops() {
+        if op.tag() == DiffTag::Equal {
+            continue;
+        }
+        let old = op.old_range();
+        if let Some((start, end)) = lines {
+            if (old.start as u32) < start || (old.end as u32) > end + 1 {
+                continue;
+            }
+        }
+        ret.push(TextEdit {
+            range: Range::new(
+                Position::new(old.start as u32, 0),
+                Position::new(old.end as u32, 0),
+            ),
+            new_text: new_lines[op.new_range()].concat(),
+        });
+    }
+    ret
+}
 
+fn send_response(
+    context: &Context,
+    request: &Request,
+    result: Option<Vec<TextEdit>>,
+) -> lsp_server::Response {
+    let r: Response = Response::new_ok(request.id.clone(), serde_json::to_value(result).unwrap());
     context
         .connection
         .sender
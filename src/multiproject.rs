@@ -1,31 +1,182 @@
 // Copyright (c) The BitsLab.MoveBit Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{analyzer_handler::*, project::*, utils::*};
+use crate::{
+    analyzer_handler::*,
+    dependency_cache::DependencyCache,
+    index_cache::{smallest_containing, IndexedSymbol, PackageIndex},
+    project::*,
+    utils::*,
+};
 use im::HashSet;
 use lsp_server::Connection;
-use lsp_types::{notification::Notification, MessageType};
+use lsp_types::{notification::Notification, FileChangeType, MessageType, Position};
 use move_command_line_common::files::FileHash;
 use move_ir_types::location::Loc;
 use std::{
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     path::{Path, PathBuf},
     rc::Rc,
 };
 
 impl_convert_loc!(MultiProject);
 
+/// Projects kept loaded. Beyond this the least recently used projects without open documents
+/// are dropped, and loaded again when one of their files is opened.
+pub const MAX_LOADED_PROJECTS: usize = 8;
+
 #[derive(Default)]
 pub struct MultiProject {
     pub projects: HashMap<HashSet<PathBuf>, Project>,
     pub hash_file: Rc<RefCell<PathBufHashMap>>,
     pub file_line_mapping: Rc<RefCell<FileLineMapping>>,
+    /// Content of the documents opened in the editor, which may not be saved yet.
+    pub documents: HashMap<PathBuf, String>,
+    /// Packages to load when the server is idle, those of the opened documents served from their
+    /// index, those the indexer found without an up to date index and those created on disk.
+    pub pending_manifests: VecDeque<PathBuf>,
+    /// Dependency resolutions and sources shared by the projects. Each project still compiles
+    /// its dependencies into its own `GlobalEnv`, see `dependency_cache`.
+    pub dependency_cache: Rc<RefCell<DependencyCache>>,
+    /// Last use of the loaded projects, by root manifest.
+    last_used: HashMap<PathBuf, u64>,
+    use_counter: u64,
+    /// Indexes of the packages, from the cache until their projects are loaded.
+    pub indexes: HashMap<PathBuf, PackageIndex>,
+    /// Build settings of the projects.
+    pub config: ProjectConfig,
 }
 
 impl MultiProject {
     pub fn insert_project(&mut self, p: Project) {
+        if let Some(root) = p.manifest_paths.first() {
+            self.use_counter += 1;
+            self.last_used.insert(root.clone(), self.use_counter);
+        }
+        self.update_indexes(&p);
         self.projects.insert(p.mk_multi_project_key(), p);
+        let evicted = self.evict_idle_projects(MAX_LOADED_PROJECTS);
+        if !evicted.is_empty() {
+            log::info!("unload idle projects {:?}", evicted);
+        }
+    }
+
+    /// Index the packages of a freshly loaded project and write the indexes to the cache.
+    fn update_indexes(&mut self, p: &Project) {
+        for (i, package) in p.manifest_paths.iter().enumerate() {
+            // dependencies rarely change, the root package is reindexed on every load.
+            let known = self
+                .indexes
+                .get(package)
+                .map_or(false, |x| x.up_to_date(&self.config));
+            if i > 0 && known {
+                continue;
+            }
+            let index = PackageIndex::build(p, package);
+            if let Err(err) = index.save(&self.config) {
+                log::error!("save index of {:?} failed,err:{:?}", package, err);
+            }
+            self.indexes.insert(package.clone(), index);
+        }
+    }
+
+    /// Load the cached indexes of the package at `mani` and of its dependencies, returns false
+    /// if the package has no up to date index.
+    pub fn load_cached_index(&mut self, mani: &Path) -> bool {
+        if self.indexes.contains_key(mani) {
+            return true;
+        }
+        let index = match PackageIndex::load(mani, &self.config) {
+            Some(x) => x,
+            None => return false,
+        };
+        let dependencies = index.dependencies.clone();
+        self.indexes.insert(mani.to_path_buf(), index);
+        for dep in dependencies.iter() {
+            self.load_cached_index(dep);
+        }
+        true
+    }
+
+    /// The symbol referred to or defined at `pos` of `fpath`, from the indexes.
+    pub fn index_symbol_at(&self, fpath: &Path, pos: &Position) -> Option<&IndexedSymbol> {
+        let references = self
+            .indexes
+            .values()
+            .flat_map(|x| x.references.iter())
+            .filter(|x| x.file == fpath)
+            .map(|x| (&x.range, x));
+        if let Some(reference) = smallest_containing(references, pos) {
+            return self
+                .indexes
+                .values()
+                .flat_map(|x| x.symbols.iter())
+                .find(|x| x.qualified_name() == reference.target);
+        }
+        let symbols = self
+            .indexes
+            .values()
+            .flat_map(|x| x.symbols.iter())
+            .filter(|x| x.file == fpath)
+            .map(|x| (&x.range, x));
+        smallest_containing(symbols, pos)
+    }
+
+    /// Symbols of the indexes whose name contains `query`, ignoring case.
+    pub fn index_symbols(&self, query: &str) -> Vec<&IndexedSymbol> {
+        let query = query.to_lowercase();
+        let mut ret: Vec<&IndexedSymbol> = self
+            .indexes
+            .values()
+            .flat_map(|x| x.symbols.iter())
+            .filter(|x| x.name.to_lowercase().contains(query.as_str()))
+            .collect();
+        ret.sort_by_key(|x| (x.name.len(), x.qualified_name()));
+        ret
+    }
+
+    /// Mark the projects containing `x` as used.
+    pub fn touch_project(&mut self, x: &Path) {
+        let manifest = match discover_manifest_and_kind(x) {
+            Some((x, _)) => x,
+            None => return,
+        };
+        self.use_counter += 1;
+        for (k, v) in self.projects.iter() {
+            if k.contains(&manifest) {
+                if let Some(root) = v.manifest_paths.first() {
+                    self.last_used.insert(root.clone(), self.use_counter);
+                }
+            }
+        }
+    }
+
+    /// Drop the least recently used projects until at most `max` are loaded. Projects with
+    /// documents open in the editor are kept. Returns the root manifests of dropped projects.
+    pub fn evict_idle_projects(&mut self, max: usize) -> Vec<PathBuf> {
+        let opened: Vec<PathBuf> = self
+            .documents
+            .keys()
+            .filter_map(|x| discover_manifest_and_kind(x).map(|(x, _)| x))
+            .collect();
+        let mut evicted = Vec::new();
+        while self.projects.len() > max {
+            let idle = self
+                .projects
+                .iter()
+                .filter(|(k, _)| !opened.iter().any(|x| k.contains(x)))
+                .filter_map(|(k, v)| Some((k.clone(), v.manifest_paths.first()?.clone())))
+                .min_by_key(|(_, root)| self.last_used.get(root).copied().unwrap_or(0));
+            let (k, root) = match idle {
+                Some(x) => x,
+                None => break,
+            };
+            self.projects.remove(&k);
+            self.last_used.remove(&root);
+            evicted.push(root);
+        }
+        evicted
     }
 
     pub fn load_projects(
@@ -72,9 +223,12 @@ impl MultiProject {
                 return anyhow::Result::Err(anyhow::anyhow!("fetch deps failed"));
             }
         }
-        Project::new(mani, |msg: String| {
-            send_show_message(sender, MessageType::ERROR, msg)
-        })
+        Project::new_with_cache(
+            mani,
+            |msg: String| send_show_message(sender, MessageType::ERROR, msg),
+            self.dependency_cache.clone(),
+            self.config.clone(),
+        )
     }
 
     pub fn new() -> MultiProject {
@@ -119,6 +273,124 @@ impl MultiProject {
         ret
     }
 
+    /// Content of a document, from the editor if it is opened, otherwise from the disk.
+    pub fn get_document(&self, x: &Path) -> Option<String> {
+        match self.documents.get(x) {
+            Some(content) => Some(content.clone()),
+            None => std::fs::read_to_string(x).ok(),
+        }
+    }
+
+    /// Take the indexes built by the indexer. The packages of the loaded projects keep the
+    /// indexes of the model in use.
+    pub fn insert_indexes(&mut self, indexes: Vec<PackageIndex>) {
+        for index in indexes {
+            if self.projects.keys().any(|k| k.contains(&index.package)) {
+                continue;
+            }
+            self.indexes.insert(index.package.clone(), index);
+        }
+    }
+
+    /// Forget the packages of a workspace folder removed from the workspace.
+    pub fn remove_workspace_folder(&mut self, folder: &Path) {
+        self.pending_manifests.retain(|x| !x.starts_with(folder));
+        self.indexes.retain(|x, _| !x.starts_with(folder));
+        self.projects.retain(|_, v| {
+            !v.manifest_paths
+                .first()
+                .map(|x| x.starts_with(folder))
+                .unwrap_or(false)
+        });
+    }
+
+    /// Load the next queued package, returns its manifest.
+    ///
+    /// A package which is already part of a loaded project, for example as the dependency of
+    /// another package of the workspace, is served by that project and not loaded again, so
+    /// packages sharing the same dependencies share their analysis.
+    pub fn load_next_pending(&mut self, connection: &Connection) -> Option<PathBuf> {
+        let mani = self.pending_manifests.pop_front()?;
+        if self.projects.keys().any(|k| k.contains(&mani)) {
+            log::info!(
+                "{:?} is loaded by another project, skipped.",
+                mani.as_path()
+            );
+            return Some(mani);
+        }
+        match self.load_projects(connection, &mani) {
+            Ok(p) => self.insert_project(p),
+            Err(err) => log::error!("load project {:?} failed,err:{:?}", mani.as_path(), err),
+        }
+        Some(mani)
+    }
+
+    /// Bring the projects up to date with files created, changed or deleted on disk, by the
+    /// user or by a branch switch. Returns the root manifests of the reloaded projects.
+    pub fn on_watched_files_changed(
+        &mut self,
+        connection: &Connection,
+        changes: &[(PathBuf, FileChangeType)],
+    ) -> Vec<PathBuf> {
+        let mut reloads: Vec<(HashSet<PathBuf>, PathBuf)> = Vec::new();
+        for (fpath, typ) in changes.iter() {
+            let is_manifest = fpath.file_name().and_then(|x| x.to_str()) == Some(PROJECT_FILE_NAME);
+            // the editor keeps the content of open documents up to date.
+            let opened = self.documents.contains_key(fpath);
+            if !is_manifest && *typ == FileChangeType::CHANGED && opened {
+                continue;
+            }
+            let mani = if is_manifest {
+                match fpath.parent() {
+                    Some(x) => normal_path(x),
+                    None => continue,
+                }
+            } else {
+                match discover_manifest_and_kind(fpath) {
+                    Some((x, _)) => x,
+                    None => continue,
+                }
+            };
+            if is_manifest || *typ != FileChangeType::CHANGED {
+                self.dependency_cache.borrow_mut().invalidate(&mani);
+            }
+            let loaded = self.projects.keys().any(|k| k.contains(&mani));
+            if is_manifest && *typ == FileChangeType::CREATED && !loaded {
+                self.pending_manifests.push_back(mani);
+                continue;
+            }
+            if is_manifest && *typ == FileChangeType::DELETED {
+                self.projects
+                    .retain(|_, v| v.manifest_paths.first() != Some(&mani));
+                continue;
+            }
+            for (k, v) in self.projects.iter() {
+                if k.contains(&mani) && !reloads.iter().any(|(x, _)| x == k) {
+                    reloads.push((k.clone(), v.manifest_paths.first().cloned().unwrap()));
+                }
+            }
+        }
+
+        let mut ret = Vec::new();
+        for (k, root_manifest) in reloads.into_iter() {
+            log::info!("reload {:?} for changed files.", root_manifest.as_path());
+            match Project::new_with_cache(
+                root_manifest.clone(),
+                |msg| send_show_message(connection, MessageType::ERROR, msg),
+                self.dependency_cache.clone(),
+                self.config.clone(),
+            ) {
+                Ok(x) => {
+                    let _ = self.projects.remove(&k);
+                    self.insert_project(x);
+                },
+                Err(err) => log::error!("reload project failed,err:{:?}", err),
+            }
+            ret.push(root_manifest);
+        }
+        ret
+    }
+
     pub fn update_defs(&mut self, file_path: PathBuf, content: String) {
         match super::utils::discover_manifest_and_kind(file_path.as_path()) {
             Some(_) => {},
@@ -128,12 +400,48 @@ impl MultiProject {
             },
         };
 
+        self.touch_project(&file_path);
         self.get_projects_mut(&file_path)
             .into_iter()
             .for_each(|x| x.update_defs(&file_path, content.clone()));
     }
 
-    pub fn try_reload_projects(&mut self, connection: &Connection) {
+    /// Apply new build settings, every loaded project is built again with them. Returns the
+    /// root manifests of the reloaded projects.
+    pub fn set_config(&mut self, connection: &Connection, config: ProjectConfig) -> Vec<PathBuf> {
+        if self.config == config {
+            return vec![];
+        }
+        for err in config.check() {
+            send_show_message(connection, MessageType::ERROR, err);
+        }
+        self.config = config;
+        // resolutions and indexes depend on the settings.
+        *self.dependency_cache.borrow_mut() = DependencyCache::new();
+        let indexed: Vec<PathBuf> = self.indexes.keys().cloned().collect();
+        self.indexes.clear();
+        for mani in indexed.iter() {
+            self.load_cached_index(mani);
+        }
+        let roots: Vec<PathBuf> = self
+            .projects
+            .values()
+            .filter_map(|x| x.manifest_paths.first().cloned())
+            .collect();
+        self.projects.clear();
+        for root in roots.iter() {
+            match self.load_projects(connection, root) {
+                Ok(x) => self.insert_project(x),
+                Err(err) => log::error!("reload project failed,err:{:?}", err),
+            }
+        }
+        roots
+    }
+
+    /// Reload the projects whose manifests were modified or whose missing dependencies showed
+    /// up. Returns the root manifests which were tried.
+    pub fn try_reload_projects(&mut self, connection: &Connection) -> Vec<PathBuf> {
+        let mut tried = Vec::new();
         let mut all = Vec::new();
         let not_founds = {
             let mut x = Vec::new();
@@ -171,13 +479,17 @@ impl MultiProject {
                 continue;
             }
             log::info!("reload  {:?}", root_manifest.as_path());
-            let x = match Project::new(root_manifest, |msg| {
-                send_show_message(connection, MessageType::ERROR, msg)
-            }) {
+            tried.push(root_manifest.clone());
+            let x = match Project::new_with_cache(
+                root_manifest,
+                |msg| send_show_message(connection, MessageType::ERROR, msg),
+                self.dependency_cache.clone(),
+                self.config.clone(),
+            ) {
                 Ok(x) => x,
                 Err(_) => {
                     log::error!("reload project failed");
-                    return;
+                    return tried;
                 },
             };
             all.push((k, x));
@@ -188,9 +500,13 @@ impl MultiProject {
                 MessageType::INFO,
                 format!("trying reload {:?}.", root_manifest.as_path()),
             );
-            let x = match Project::new(root_manifest, |msg| {
-                send_show_message(connection, MessageType::ERROR, msg);
-            }) {
+            tried.push(root_manifest.clone());
+            let x = match Project::new_with_cache(
+                root_manifest,
+                |msg| send_show_message(connection, MessageType::ERROR, msg),
+                self.dependency_cache.clone(),
+                self.config.clone(),
+            ) {
                 Ok(x) => x,
                 Err(err) => {
                     send_show_message(
@@ -207,6 +523,7 @@ impl MultiProject {
             let _ = self.projects.remove(&k);
             self.insert_project(v);
         }
+        tried
     }
 }
 
@@ -0,0 +1,89 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crossbeam::channel::Sender;
+use lsp_server::{Message, Notification, Request};
+use lsp_types::{
+    notification::{Notification as _, Progress},
+    request::{Request as _, WorkDoneProgressCreate},
+    NumberOrString, ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
+};
+
+/// Reports a long running server side operation through `$/progress`.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    sender: Sender<Message>,
+    token: ProgressToken,
+}
+
+impl ProgressReporter {
+    /// Begin a new progress. If the client did not hand out a `work_done_token` with its request,
+    /// ask it to create one for us first.
+    pub fn begin(
+        sender: &Sender<Message>,
+        client_token: Option<ProgressToken>,
+        token: &str,
+        title: &str,
+    ) -> Self {
+        let token = match client_token {
+            Some(x) => x,
+            None => {
+                let token = NumberOrString::String(token.to_string());
+                let _ = sender.send(Message::Request(Request {
+                    id: format!("progress/{}", token_str(&token)).into(),
+                    method: WorkDoneProgressCreate::METHOD.to_string(),
+                    params: serde_json::to_value(WorkDoneProgressCreateParams {
+                        token: token.clone(),
+                    })
+                    .unwrap(),
+                }));
+                token
+            },
+        };
+        let ret = Self {
+            sender: sender.clone(),
+            token,
+        };
+        ret.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            cancellable: Some(false),
+            message: None,
+            percentage: None,
+        }));
+        ret
+    }
+
+    pub fn report(&self, message: String, percentage: Option<u32>) {
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(message),
+            percentage,
+        }));
+    }
+
+    pub fn end(self, message: Option<String>) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd { message }));
+    }
+
+    fn send(&self, value: WorkDoneProgress) {
+        let params = ProgressParams {
+            token: self.token.clone(),
+            value: ProgressParamsValue::WorkDone(value),
+        };
+        if let Err(err) = self.sender.send(Message::Notification(Notification {
+            method: Progress::METHOD.to_string(),
+            params: serde_json::to_value(params).unwrap(),
+        })) {
+            log::error!("send progress failed,err:{:?}", err);
+        }
+    }
+}
+
+fn token_str(token: &ProgressToken) -> String {
+    match token {
+        NumberOrString::Number(x) => x.to_string(),
+        NumberOrString::String(x) => x.clone(),
+    }
+}
@@ -2,16 +2,158 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::utils::*;
-use crate::analyzer_handler::*;
+use crate::{analyzer_handler::*, dependency_cache::DependencyCache};
 use move_compiler::shared::PackagePaths;
+use move_core_types::account_address::AccountAddress;
+use move_model::{
+    metadata::{CompilerVersion, LanguageVersion},
+    options::ModelBuilderOptions,
+};
+use once_cell::sync::Lazy;
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
     rc::Rc,
+    str::FromStr,
+    sync::{Arc, Mutex},
     time::SystemTime,
 };
 
+/// Directory the builds of the package at `package` with the settings of `config` are written to.
+/// It is under the cache directory of the user so it outlives the build, and there is one per
+/// package and settings so the builds of packages with the same name, or of a package in dev and
+/// in release mode, do not overwrite each other.
+pub fn build_dir(package: &Path, config: &ProjectConfig) -> PathBuf {
+    let package = short_hash(normal_path(package).to_string_lossy().as_ref());
+    dirs_next::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("aptos-move-analyzer")
+        .join("build")
+        .join(format!("{}-{}", package, config.cache_key()))
+}
+
+static BUILD_DIR_LOCKS: Lazy<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = Lazy::new(Default::default);
+
+/// Lock of the build directory `dir`. A build holds it while it writes to the directory, so the
+/// builds of the same package started by the server threads or the tests do not interleave.
+pub fn build_dir_lock(dir: &Path) -> Arc<Mutex<()>> {
+    BUILD_DIR_LOCKS
+        .lock()
+        .unwrap_or_else(|x| x.into_inner())
+        .entry(dir.to_path_buf())
+        .or_default()
+        .clone()
+}
+
+/// How the packages are built, set by the client with `move/lsp/project/config`.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// Use the `[dev-addresses]` of the manifests. Test mode uses them as well.
+    pub dev_mode: bool,
+    /// Analyze `#[test_only]` code and `#[test]` functions.
+    pub test_mode: bool,
+    /// Addresses of the named addresses the manifests leave unassigned, like `"std": "0x1"`.
+    pub named_addresses: BTreeMap<String, String>,
+    /// Like `2.0`, the compiler default when not set. The move-model in use has no enum types or
+    /// `match` expressions, so those Move 2 features are not supported whatever the version.
+    pub language_version: Option<String>,
+    /// Like `2.0`, the compiler default when not set.
+    pub compiler_version: Option<String>,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self {
+            dev_mode: false,
+            test_mode: true,
+            named_addresses: Default::default(),
+            language_version: None,
+            compiler_version: None,
+        }
+    }
+}
+
+impl ProjectConfig {
+    /// Errors of the settings, which are ignored when building.
+    pub fn check(&self) -> Vec<String> {
+        let mut ret = Vec::new();
+        for (name, addr) in self.named_addresses.iter() {
+            if AccountAddress::from_hex_literal(addr).is_err() {
+                ret.push(format!(
+                    "invalid address '{}' for named address '{}'",
+                    addr, name
+                ));
+            }
+        }
+        if let Some(Err(err)) = self
+            .language_version
+            .as_deref()
+            .map(LanguageVersion::from_str)
+        {
+            ret.push(format!("invalid language version, {}", err));
+        }
+        if let Some(Err(err)) = self
+            .compiler_version
+            .as_deref()
+            .map(CompilerVersion::from_str)
+        {
+            ret.push(format!("invalid compiler version, {}", err));
+        }
+        ret
+    }
+
+    pub fn language_version(&self) -> Option<LanguageVersion> {
+        LanguageVersion::from_str(self.language_version.as_deref()?).ok()
+    }
+
+    pub fn compiler_version(&self) -> Option<CompilerVersion> {
+        CompilerVersion::from_str(self.compiler_version.as_deref()?).ok()
+    }
+
+    /// Short hash of the settings, part of the key of what is cached for them.
+    pub fn cache_key(&self) -> String {
+        short_hash(format!("{:?}", self).as_str())
+    }
+
+    pub fn additional_named_addresses(&self) -> BTreeMap<String, AccountAddress> {
+        self.named_addresses
+            .iter()
+            .filter_map(|(name, addr)| {
+                Some((name.clone(), AccountAddress::from_hex_literal(addr).ok()?))
+            })
+            .collect()
+    }
+
+    /// `BuildConfig` of the package system for these settings, to build the package at `package`.
+    pub fn build_config(&self, package: &Path) -> move_package::BuildConfig {
+        let mut build_config = move_package::BuildConfig {
+            dev_mode: self.dev_mode,
+            test_mode: self.test_mode,
+            install_dir: Some(build_dir(package, self)),
+            skip_fetch_latest_git_deps: true,
+            additional_named_addresses: self.additional_named_addresses(),
+            ..Default::default()
+        };
+        build_config.compiler_config.language_version = self.language_version();
+        build_config.compiler_config.compiler_version = self.compiler_version();
+        build_config
+    }
+
+    /// `ModelBuilderOptions` of the model builder for these settings.
+    pub fn model_builder_options(&self) -> ModelBuilderOptions {
+        let mut options = ModelBuilderOptions {
+            compile_via_model: true,
+            ..Default::default()
+        };
+        if let Some(x) = self.language_version() {
+            options.language_version = x;
+        }
+        options
+    }
+}
+
 /// Project
 pub struct Project {
     pub(crate) modules: HashMap<
@@ -31,6 +173,10 @@ pub struct Project {
     pub(crate) dependents: Vec<PackagePaths<std::string::String, std::string::String>>,
     pub(crate) addrname_2_addrnum: std::collections::HashMap<String, String>,
     pub err_diags: String,
+    /// The errors and warnings of the model, read before `err_diags` is rendered.
+    pub model_diags: Vec<(PathBuf, lsp_types::Diagnostic)>,
+    pub(crate) dependency_cache: Rc<RefCell<DependencyCache>>,
+    pub(crate) config: ProjectConfig,
 }
 
 impl Project {
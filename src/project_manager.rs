@@ -2,12 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::utils::*;
-use crate::{analyzer_handler::*, project::Project};
+use crate::{
+    analyzer_handler::*,
+    dependency_cache::{DependencyCache, ResolvedDependencies},
+    project::{Project, ProjectConfig},
+    spec_diagnostics::env_diagnostics,
+};
 use anyhow::{Ok, Result};
 use codespan_reporting::diagnostic::Severity;
 use move_compiler::shared::{NumericalAddress, PackagePaths};
+use move_compiler::Flags;
 use move_core_types::account_address::*;
-use move_model::{options::ModelBuilderOptions, run_model_builder_with_options};
+use move_model::run_model_builder_with_options_and_compilation_flags;
 use move_package::source_package::{layout::SourcePackageLayout, manifest_parser::*};
 use num_bigint::BigUint;
 use std::{
@@ -17,7 +23,6 @@ use std::{
     path::{Path, PathBuf},
     rc::Rc,
 };
-use tempfile::tempdir;
 use walkdir::WalkDir;
 
 // Determines the base of the number literal, depending on the prefix
@@ -109,6 +114,22 @@ impl Project {
     pub fn new(
         root_dir: impl Into<PathBuf>,
         report_err: impl FnMut(String) + Clone,
+    ) -> Result<Self> {
+        Self::new_with_cache(
+            root_dir,
+            report_err,
+            Rc::new(RefCell::new(DependencyCache::new())),
+            ProjectConfig::default(),
+        )
+    }
+
+    /// Load the project at `root_dir` with the build settings of `config`, reusing the
+    /// dependencies already resolved and scanned by other projects sharing `dependency_cache`.
+    pub fn new_with_cache(
+        root_dir: impl Into<PathBuf>,
+        mut report_err: impl FnMut(String) + Clone,
+        dependency_cache: Rc<RefCell<DependencyCache>>,
+        config: ProjectConfig,
     ) -> Result<Self> {
         let working_dir = root_dir.into();
         log::info!("scan modules at {:?}", &working_dir);
@@ -127,32 +148,79 @@ impl Project {
             dependents: vec![],
             addrname_2_addrnum: Default::default(),
             err_diags: String::default(),
+            model_diags: vec![],
+            dependency_cache: dependency_cache.clone(),
+            config: config.clone(),
         };
 
         let mut targets_paths: Vec<PathBuf> = Vec::new();
-        let mut dependents_paths: Vec<PathBuf> = Vec::new();
-        new_project.load_project(
-            &working_dir,
-            report_err,
-            true,
-            &mut targets_paths,
-            &mut dependents_paths,
-        )?;
-        log::info!("targets_paths.len() = {:?}", targets_paths.len());
-        log::info!("dependents_paths.len() = {:?}", dependents_paths.len());
+        new_project.load_project(&working_dir, report_err.clone(), true, &mut targets_paths)?;
 
-        let build_config = move_package::BuildConfig {
-            test_mode: true,
-            install_dir: Some(tempdir().unwrap().path().to_path_buf()),
-            skip_fetch_latest_git_deps: true,
-            ..Default::default()
+        let cached = dependency_cache.borrow().resolution(&working_dir);
+        let resolved = match cached {
+            Some(x) => Some(x),
+            // Resolving clones the git and node dependencies missing from the local package
+            // cache (`~/.move`), which must not happen while loading. The missing ones are
+            // reported by the diagnostics of Move.toml instead.
+            None if !new_project.manifest_not_exists.is_empty() => {
+                let mut missing: Vec<_> = new_project.manifest_not_exists.iter().collect();
+                missing.sort();
+                report_err(format!(
+                    "dependencies of '{:?}' are not in the local package cache: {:?}\nMaybe you need execute 'aptos move compile --fetch-deps-only' yourself.",
+                    working_dir, missing
+                ));
+                None
+            },
+            None => {
+                let build_config = config.build_config(&working_dir);
+                // The resolution graph knows where git and node dependencies live in the local
+                // package cache (`~/.move`), including the transitive ones reached through
+                // substitutions. Every dependency reachable from the manifests is in the cache,
+                // and with `skip_fetch_latest_git_deps` the cached ones are not updated, so
+                // resolving does not touch the network.
+                match build_config.resolution_graph_for_package(&working_dir, &mut Vec::new()) {
+                    std::result::Result::Ok(resolution_graph) => {
+                        let packages = resolution_graph
+                            .package_table
+                            .values()
+                            .map(|x| normal_path(&x.package_path))
+                            .filter(|x| *x != normal_path(&working_dir))
+                            .collect();
+                        let named_addresses = resolution_graph
+                            .extract_named_address_mapping()
+                            .map(|(name, addr)| format!("{}={}", name.as_str(), addr))
+                            .collect();
+                        Some(dependency_cache.borrow_mut().insert_resolution(
+                            &working_dir,
+                            ResolvedDependencies::new(&working_dir, packages, named_addresses),
+                        ))
+                    },
+                    std::result::Result::Err(err) => {
+                        log::error!("resolve dependencies failed,err:{:?}", err);
+                        report_err(format!(
+                            "resolve dependencies of '{:?}' failed, some dependencies may not be in the local package cache.\nMaybe you need execute 'aptos move compile --fetch-deps-only' yourself.\n{:?}",
+                            working_dir, err
+                        ));
+                        None
+                    },
+                }
+            },
         };
-        let resolution_graph =
-            build_config.resolution_graph_for_package(&working_dir, &mut Vec::new())?;
-        let named_address_mapping: Vec<_> = resolution_graph
-            .extract_named_address_mapping()
-            .map(|(name, addr)| format!("{}={}", name.as_str(), addr))
-            .collect();
+        let named_address_mapping = match resolved {
+            Some(resolved) => {
+                for package in resolved.packages.iter() {
+                    new_project.load_project(
+                        package,
+                        report_err.clone(),
+                        false,
+                        &mut targets_paths,
+                    )?;
+                }
+                resolved.named_addresses.clone()
+            },
+            None => new_project.manifest_named_addresses(),
+        };
+        log::info!("targets_paths.len() = {:?}", targets_paths.len());
         let addrs = parse_addresses_from_options(named_address_mapping)?;
 
         let targets = vec![PackagePaths {
@@ -164,26 +232,36 @@ impl Project {
             named_address_map: addrs.clone(),
         }];
 
-        let dependents = vec![PackagePaths {
-            name: None,
-            paths: dependents_paths
-                .into_iter()
-                .map(|p| p.to_string_lossy().to_string())
-                .collect::<Vec<_>>(),
-            named_address_map: addrs.clone(),
-        }];
+        // the root package is the first manifest, the others are dependencies. The model builder
+        // takes the packages by value, the cached source lists are copied into it.
+        let dependents: Vec<_> = new_project
+            .manifest_paths
+            .iter()
+            .skip(1)
+            .map(|package| {
+                let package_paths =
+                    dependency_cache
+                        .borrow_mut()
+                        .package_paths(package, &addrs, || Self::package_source_files(package));
+                (*package_paths).clone()
+            })
+            .collect();
+        log::info!(
+            "dependents.len() = {:?}, cached dependencies = {:?}",
+            dependents.len(),
+            dependency_cache.borrow().package_count()
+        );
 
         let attributes: BTreeSet<String> = Default::default();
         new_project.targets = targets.clone();
         new_project.dependents = dependents.clone();
-        new_project.global_env = run_model_builder_with_options(
+        new_project.global_env = run_model_builder_with_options_and_compilation_flags(
             targets,
             dependents,
-            ModelBuilderOptions {
-                compile_via_model: true,
-                ..Default::default()
-            },
-            false,
+            config.model_builder_options(),
+            Flags::model_compilation()
+                .set_skip_attribute_checks(false)
+                .set_keep_testing_functions(config.test_mode),
             &attributes,
         )
         .expect("Failed to create GlobalEnv!");
@@ -200,6 +278,7 @@ impl Project {
         }
 
         new_project.addrname_2_addrnum = helper;
+        new_project.model_diags = env_diagnostics(&new_project.global_env);
         new_project
             .global_env
             .report_diag(&mut error_writer, Severity::Error);
@@ -218,7 +297,12 @@ impl Project {
             },
         };
 
-        let new_project = match Project::new(root_dir.clone(), |msg| log::info!("{}", msg)) {
+        let new_project = match Project::new_with_cache(
+            root_dir.clone(),
+            |msg| log::info!("{}", msg),
+            self.dependency_cache.clone(),
+            self.config.clone(),
+        ) {
             Ok(x) => x,
             Err(_) => {
                 log::error!("reload project failed");
@@ -229,6 +313,7 @@ impl Project {
         self.current_modifing_file_content = content;
         self.targets = new_project.targets.clone();
         self.dependents = new_project.dependents.clone();
+        self.model_diags = new_project.model_diags;
         self.global_env = new_project.global_env;
         log::info!(
             "env.get_module_count() = {:?}",
@@ -241,14 +326,14 @@ impl Project {
         self.err_diags = String::from_utf8_lossy(&error_writer.into_inner()).to_string();
     }
 
-    /// Load a Move.toml project.
+    /// Load a Move.toml project. The sources of the root package are collected into
+    /// `targets_paths`, those of the dependencies are shared through the dependency cache.
     pub(crate) fn load_project(
         &mut self,
         manifest_path: &Path,
         mut report_err: impl FnMut(String) + Clone,
         is_main_source: bool,
         targets_paths: &mut Vec<PathBuf>,
-        dependents_paths: &mut Vec<PathBuf>,
     ) -> Result<()> {
         let manifest_path = normal_path(manifest_path);
         if self.modules.get(&manifest_path).is_some() {
@@ -263,20 +348,8 @@ impl Project {
         self.manifest_paths.push(manifest_path.clone());
         log::trace!("load manifest file at {:?}", &manifest_path);
 
-        let source_paths1 =
-            self.load_layout_files_v2(&manifest_path, SourcePackageLayout::Sources)?;
-        let source_paths2 =
-            self.load_layout_files_v2(&manifest_path, SourcePackageLayout::Tests)?;
-        let source_paths3 =
-            self.load_layout_files_v2(&manifest_path, SourcePackageLayout::Scripts)?;
         if is_main_source {
-            targets_paths.extend(source_paths1);
-            targets_paths.extend(source_paths2);
-            targets_paths.extend(source_paths3);
-        } else {
-            dependents_paths.extend(source_paths1);
-            dependents_paths.extend(source_paths2);
-            dependents_paths.extend(source_paths3);
+            targets_paths.extend(Self::package_source_files(&manifest_path));
         }
 
         if !manifest_path.exists() {
@@ -295,9 +368,8 @@ impl Project {
             std::result::Result::Ok(x) => x,
             std::result::Result::Err(err) => {
                 report_err(format!(
-                    "parse manifest '{:?} 'failed.\n addr must exactly 32 length or start with '0x' like '0x2'\n{:?}",
-                    manifest_path,
-                    err
+                    "parse manifest '{:?}' failed, see the diagnostics of Move.toml.\n{:?}",
+                    manifest_path, err
                 ));
                 log::error!("parse_move_manifest_from_file failed,err:{:?}", err);
                 self.manifest_load_failures.insert(manifest_path.clone());
@@ -319,22 +391,60 @@ impl Project {
                 &manifest_path,
                 dep_name
             );
-            self.load_project(
-                &p,
-                report_err.clone(),
-                false,
-                targets_paths,
-                dependents_paths,
-            )?;
+            self.load_project(&p, report_err.clone(), false, targets_paths)?;
         }
         Ok(())
     }
 
+    /// Named addresses assigned by the loaded manifests and the settings, used when the
+    /// dependencies could not be resolved.
+    fn manifest_named_addresses(&self) -> Vec<String> {
+        let use_dev_addresses = self.config.dev_mode || self.config.test_mode;
+        let mut ret = BTreeMap::new();
+        for manifest in self.manifests.iter() {
+            if let Some(addresses) = manifest.addresses.as_ref() {
+                for (name, addr) in addresses.iter() {
+                    if let Some(addr) = addr {
+                        ret.insert(name.to_string(), addr.to_hex_literal());
+                    }
+                }
+            }
+            if let Some(addresses) = manifest
+                .dev_address_assignments
+                .as_ref()
+                .filter(|_| use_dev_addresses)
+            {
+                for (name, addr) in addresses.iter() {
+                    ret.entry(name.to_string())
+                        .or_insert_with(|| addr.to_hex_literal());
+                }
+            }
+        }
+        for (name, addr) in self.config.additional_named_addresses() {
+            ret.entry(name).or_insert_with(|| addr.to_hex_literal());
+        }
+        ret.into_iter()
+            .map(|(name, addr)| format!("{}={}", name, addr))
+            .collect()
+    }
+
+    /// Source files of the package at `manifest_path`, tests and scripts included.
+    pub(crate) fn package_source_files(manifest_path: &Path) -> Vec<PathBuf> {
+        let mut ret = Vec::new();
+        for kind in [
+            SourcePackageLayout::Sources,
+            SourcePackageLayout::Tests,
+            SourcePackageLayout::Scripts,
+        ] {
+            ret.extend(Self::load_layout_files_v2(manifest_path, kind));
+        }
+        ret
+    }
+
     pub(crate) fn load_layout_files_v2(
-        &mut self,
         manifest_path: &Path,
         kind: SourcePackageLayout,
-    ) -> Result<Vec<PathBuf>> {
+    ) -> Vec<PathBuf> {
         let mut ret_paths = Vec::new();
         let mut p = manifest_path.to_path_buf();
         p.push(kind.location_str());
@@ -361,7 +471,7 @@ impl Project {
                 ret_paths.push(file.path().to_path_buf());
             }
         }
-        Ok(ret_paths)
+        ret_paths
     }
 
     pub(crate) fn manifest_beed_modified(&self) -> bool {
@@ -4,7 +4,7 @@
 use crate::{
     analyzer_handler::*,
     context::*,
-    utils::{path_concat, FileRange},
+    utils::{call_capture_loc, path_concat, FileRange},
 };
 use codespan::{ByteIndex, ByteOffset};
 use lsp_server::*;
@@ -691,6 +691,11 @@ impl Handler {
                 self.collect_local_var_in_pattern(pattern);
                 true
             }
+            Lambda(_, pattern, _) => {
+                self.collect_local_var_in_pattern(pattern);
+                self.process_pattern(env, pattern);
+                true
+            }
             _ => {
                 log::trace!("________________");
                 true
@@ -726,12 +731,15 @@ impl Handler {
                 self.process_expr(env, exp);
             }
 
-            let this_call_loc = env.get_node_loc(*node_id);
-            if this_call_loc.span().start() < self.mouse_span.end()
-                && self.mouse_span.end() < this_call_loc.span().end()
-            {
-                let called_module = env.get_module(*mid);
-                let called_fun = called_module.get_function(*fid);
+            let called_module = env.get_module(*mid);
+            let called_fun = called_module.get_function(*fid);
+            if let Some(this_call_loc) = call_capture_loc(
+                env,
+                &env.get_node_loc(*node_id),
+                args_vec,
+                &called_fun.get_name_str(),
+                self.mouse_span.end(),
+            ) {
                 self.process_fn_name(env, &this_call_loc, &called_fun);
             }
         }
@@ -0,0 +1,241 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Selection ranges (expand/shrink selection) of a Move file.
+//!
+//! The parser AST is walked from the top down collecting every span containing the cursor: the
+//! module, the member, the blocks, statements and expressions, down to the token under the cursor.
+
+use crate::{
+    context::Context,
+    utils::{lexer_for_buffer, parse_buffer, path_concat, LineIndex},
+};
+use lsp_server::{Request, Response};
+use lsp_types::{Position, Range, SelectionRange, SelectionRangeParams};
+use move_compiler::parser::ast::{
+    Definition, Exp, Exp_, FunctionBody_, ModuleDefinition, ModuleMember, Sequence, SequenceItem_,
+    SpecBlock, SpecBlockMember_,
+};
+use move_ir_types::location::Loc;
+
+/// Handles selection range request of the language server.
+pub fn on_selection_range_request(context: &Context, request: &Request) -> Response {
+    log::info!("on_selection_range_request request = {:?}", request);
+    let parameters = serde_json::from_value::<SelectionRangeParams>(request.params.clone())
+        .expect("could not deserialize selection range request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let fpath = path_concat(std::env::current_dir().unwrap().as_path(), fpath.as_path());
+    let ranges = match context.projects.get_document(&fpath) {
+        Some(content) => selection_ranges(content.as_str(), &parameters.positions),
+        None => parameters
+            .positions
+            .iter()
+            .map(|pos| SelectionRange {
+                range: Range::new(*pos, *pos),
+                parent: None,
+            })
+            .collect(),
+    };
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(ranges).unwrap());
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(r.clone()))
+        .unwrap();
+    r
+}
+
+/// One `SelectionRange` for each position, as the protocol requires.
+pub fn selection_ranges(content: &str, positions: &[Position]) -> Vec<SelectionRange> {
+    let index = LineIndex::new(content);
+    let defs = parse_buffer(content).unwrap_or_default();
+    let tokens = lexer_for_buffer(content);
+    positions
+        .iter()
+        .map(|pos| {
+            let mut collector = SelectionCollector {
+                offset: index.offset(pos),
+                locs: vec![],
+            };
+            for d in defs.iter() {
+                collector.visit_definition(d);
+            }
+            // a cursor on the boundary of two siblings may enter both, keep ranges nested.
+            let mut locs: Vec<Loc> = vec![];
+            for loc in collector.locs.iter() {
+                match locs.last() {
+                    Some(last) if loc.start() < last.start() || loc.end() > last.end() => {},
+                    _ => locs.push(*loc),
+                }
+            }
+            let mut ranges: Vec<Range> = locs.iter().map(|x| index.range(x)).collect();
+            if let Some((_, (start, end))) = tokens
+                .iter()
+                .find(|(_, (start, end))| *start <= collector.offset && collector.offset <= *end)
+                .filter(|(_, (start, end))| {
+                    locs.last().map_or(true, |x| {
+                        x.start() as usize <= *start && *end <= x.end() as usize
+                    })
+                })
+            {
+                ranges.push(Range::new(index.position(*start), index.position(*end)));
+            }
+            ranges.dedup();
+            let mut ret: Option<SelectionRange> = None;
+            for range in ranges.into_iter() {
+                ret = Some(SelectionRange {
+                    range,
+                    parent: ret.map(Box::new),
+                });
+            }
+            ret.unwrap_or(SelectionRange {
+                range: Range::new(*pos, *pos),
+                parent: None,
+            })
+        })
+        .collect()
+}
+
+/// Collects the spans containing `offset`, from the outermost to the innermost.
+struct SelectionCollector {
+    offset: usize,
+    locs: Vec<Loc>,
+}
+
+impl SelectionCollector {
+    fn enter(&mut self, loc: &Loc) -> bool {
+        let inside = (loc.start() as usize) <= self.offset && self.offset <= (loc.end() as usize);
+        if inside {
+            self.locs.push(*loc);
+        }
+        inside
+    }
+
+    fn visit_definition(&mut self, d: &Definition) {
+        match d {
+            Definition::Module(m) => self.visit_module(m),
+            Definition::Address(a) => {
+                if self.enter(&a.loc) {
+                    for m in a.modules.iter() {
+                        self.visit_module(m);
+                    }
+                }
+            },
+            Definition::Script(s) => {
+                if self.enter(&s.loc) {
+                    if self.enter(&s.function.loc) {
+                        if let FunctionBody_::Defined(seq) = &s.function.body.value {
+                            self.visit_sequence(&s.function.body.loc, seq);
+                        }
+                    }
+                    for spec in s.specs.iter() {
+                        self.visit_spec(spec);
+                    }
+                }
+            },
+        }
+    }
+
+    fn visit_module(&mut self, m: &ModuleDefinition) {
+        if !self.enter(&m.loc) {
+            return;
+        }
+        for member in m.members.iter() {
+            match member {
+                ModuleMember::Function(f) => {
+                    if self.enter(&f.loc) {
+                        if let FunctionBody_::Defined(seq) = &f.body.value {
+                            self.visit_sequence(&f.body.loc, seq);
+                        }
+                    }
+                },
+                ModuleMember::Struct(s) => {
+                    self.enter(&s.loc);
+                },
+                ModuleMember::Constant(c) => {
+                    if self.enter(&c.loc) {
+                        self.visit_exp(&c.value);
+                    }
+                },
+                ModuleMember::Spec(s) => self.visit_spec(s),
+                _ => {},
+            }
+        }
+    }
+
+    fn visit_spec(&mut self, spec: &SpecBlock) {
+        if !self.enter(&spec.loc) {
+            return;
+        }
+        for member in spec.value.members.iter() {
+            if self.enter(&member.loc) {
+                if let SpecBlockMember_::Condition { exp, .. } = &member.value {
+                    self.visit_exp(exp);
+                }
+            }
+        }
+    }
+
+    fn visit_sequence(&mut self, loc: &Loc, seq: &Sequence) {
+        if !self.enter(loc) {
+            return;
+        }
+        for item in seq.1.iter() {
+            if !self.enter(&item.loc) {
+                continue;
+            }
+            match &item.value {
+                SequenceItem_::Seq(e) => self.visit_exp(e),
+                SequenceItem_::Bind(.., e) => self.visit_exp(e),
+                SequenceItem_::Declare(..) => {},
+            }
+        }
+        if let Some(e) = &*seq.3 {
+            self.visit_exp(e);
+        }
+    }
+
+    fn visit_exp(&mut self, e: &Exp) {
+        if !self.enter(&e.loc) {
+            return;
+        }
+        match &e.value {
+            Exp_::Call(.., args) | Exp_::Vector(.., args) => self.visit_exps(args.value.iter()),
+            Exp_::Pack(.., fields) => self.visit_exps(fields.iter().map(|(_, e)| e)),
+            Exp_::ExpList(es) => self.visit_exps(es.iter()),
+            Exp_::IfElse(c, t, f) => {
+                self.visit_exp(c);
+                self.visit_exp(t);
+                if let Some(f) = f {
+                    self.visit_exp(f);
+                }
+            },
+            Exp_::While(.., c, body) => {
+                self.visit_exp(c);
+                self.visit_exp(body);
+            },
+            Exp_::Loop(.., body) => self.visit_exp(body),
+            Exp_::Block(seq) => self.visit_sequence(&e.loc, seq),
+            Exp_::Assign(l, r) | Exp_::BinopExp(l, _, r) | Exp_::Index(l, r) => {
+                self.visit_exp(l);
+                self.visit_exp(r);
+            },
+            Exp_::Return(Some(x))
+            | Exp_::Abort(x)
+            | Exp_::Dereference(x)
+            | Exp_::UnaryExp(_, x)
+            | Exp_::Borrow(_, x)
+            | Exp_::Dot(x, _)
+            | Exp_::Cast(x, _)
+            | Exp_::Annotate(x, _) => self.visit_exp(x),
+            Exp_::Spec(spec) => self.visit_spec(spec),
+            _ => {},
+        }
+    }
+
+    fn visit_exps<'a>(&mut self, es: impl Iterator<Item = &'a Exp>) {
+        for e in es {
+            self.visit_exp(e);
+        }
+    }
+}
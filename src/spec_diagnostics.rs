@@ -0,0 +1,275 @@
+// Copyright (c) The BitsLab.MoveBit Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Diagnostics of the spec blocks of a project.
+//!
+//! The errors and warnings of the model builder are read from the model, and those pointing into
+//! a `spec` block get `spec` as source while the others get `move`, so a broken spec is told apart
+//! from broken code. Pragmas which look like typos of a known pragma are reported too.
+
+use crate::{
+    move_generate_spec_merge::spec_file_path,
+    project::Project,
+    utils::{lexer_for_buffer, LineIndex},
+};
+use codespan::FileId;
+use codespan_reporting::{
+    diagnostic::{LabelStyle, Severity},
+    term::termcolor::Buffer,
+};
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+use move_compiler::parser::lexer::Tok;
+use move_model::model::GlobalEnv;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// Source of the diagnostics found in spec blocks.
+pub const SPEC_SOURCE: &str = "spec";
+/// Source of the diagnostics found in Move code.
+pub const MOVE_SOURCE: &str = "move";
+
+/// The pragmas the prover knows about.
+const KNOWN_PRAGMAS: &[&str] = &[
+    "verify",
+    "intrinsic",
+    "timeout",
+    "verify_duration_estimate",
+    "seed",
+    "aborts_if_is_strict",
+    "aborts_if_is_partial",
+    "emits_is_strict",
+    "emits_is_partial",
+    "requires_if_aborts",
+    "opaque",
+    "addition_overflow_unchecked",
+    "delegate_invariants_to_caller",
+    "disable_invariants_in_body",
+    "friend",
+    "bv",
+    "bv_ret",
+    "unroll",
+];
+
+/// Diagnostics of the model of `project`, and of the pragma typos of `fpath` and of its spec
+/// file, by the files they are in.
+pub fn model_diagnostics(project: &Project, fpath: &Path) -> HashMap<Url, Vec<Diagnostic>> {
+    let mut result: HashMap<Url, Vec<Diagnostic>> = HashMap::new();
+    // the root package is the first manifest, the others are dependencies.
+    let dependencies: Vec<&PathBuf> = project.manifest_paths.iter().skip(1).collect();
+    for (path, d) in project.model_diags.iter() {
+        // the diagnostics of the dependencies are not for the user to fix.
+        if dependencies.iter().any(|x| path.starts_with(x)) {
+            continue;
+        }
+        if let Ok(url) = Url::from_file_path(path.as_path()) {
+            result.entry(url).or_default().push(d.clone());
+        }
+    }
+    tag_sources(&mut result);
+
+    for path in [fpath.to_path_buf(), spec_file_path(fpath)] {
+        let content = std::fs::read_to_string(path.as_path()).unwrap_or_default();
+        let typos = pragma_typos(content.as_str());
+        if typos.is_empty() {
+            continue;
+        }
+        if let Ok(url) = Url::from_file_path(path.as_path()) {
+            let ds = result.entry(url).or_default();
+            for typo in typos {
+                // the model only says the pragma is not valid, keep the more helpful one.
+                ds.retain(|x| {
+                    x.range.start.line != typo.range.start.line || !x.message.contains("pragma")
+                });
+                ds.push(typo);
+            }
+        }
+    }
+    result
+}
+
+/// Sets `spec` as source of the diagnostics of `result` which are in spec blocks, and `move` as
+/// source of the others.
+pub fn tag_sources(result: &mut HashMap<Url, Vec<Diagnostic>>) {
+    for (url, ds) in result.iter_mut() {
+        let ranges = url
+            .to_file_path()
+            .ok()
+            .and_then(|x| std::fs::read_to_string(x).ok())
+            .map(|x| spec_ranges(x.as_str()))
+            .unwrap_or_default();
+        for d in ds.iter_mut() {
+            let in_spec = ranges.iter().any(|r| range_contains(r, &d.range.start));
+            d.source = Some(if in_spec { SPEC_SOURCE } else { MOVE_SOURCE }.to_string());
+        }
+    }
+}
+
+/// The diagnostics of the model builder in `env`, errors and warnings, with the files their
+/// primary labels point at. They must be read before `report_diag`, which leaves out the
+/// diagnostics it reported before.
+pub fn env_diagnostics(env: &GlobalEnv) -> Vec<(PathBuf, Diagnostic)> {
+    let mut diags = vec![];
+    env.report_diag_with_filter(&mut Buffer::no_color(), |d| {
+        diags.push(d.clone());
+        false
+    });
+    let mut indexes: HashMap<FileId, Option<LineIndex>> = HashMap::new();
+    let mut ret = vec![];
+    for d in diags {
+        let label = match d
+            .labels
+            .iter()
+            .find(|x| x.style == LabelStyle::Primary)
+            .or_else(|| d.labels.first())
+        {
+            Some(x) => x,
+            None => continue,
+        };
+        let path = PathBuf::from(env.get_file(label.file_id));
+        let index = indexes.entry(label.file_id).or_insert_with(|| {
+            std::fs::read_to_string(path.as_path())
+                .ok()
+                .map(|x| LineIndex::new(x.as_str()))
+        });
+        let index = match index {
+            Some(x) => x,
+            None => continue,
+        };
+        let severity = match d.severity {
+            Severity::Bug | Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Note => DiagnosticSeverity::INFORMATION,
+            Severity::Help => DiagnosticSeverity::HINT,
+        };
+        ret.push((
+            path,
+            Diagnostic {
+                range: Range::new(
+                    index.position(label.range.start),
+                    index.position(label.range.end),
+                ),
+                severity: Some(severity),
+                message: d.message.clone(),
+                ..Default::default()
+            },
+        ));
+    }
+    ret
+}
+
+/// Ranges of the `spec` blocks of `content`, the spec blocks inside functions included.
+pub fn spec_ranges(content: &str) -> Vec<Range> {
+    let index = LineIndex::new(content);
+    let tokens = lexer_for_buffer(content);
+    let mut ret = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].0 != Tok::Spec {
+            i += 1;
+            continue;
+        }
+        let start = tokens[i].1 .0;
+        let mut depth = 0;
+        let mut end = None;
+        let mut j = i + 1;
+        while j < tokens.len() {
+            match tokens[j].0 {
+                Tok::LBrace => depth += 1,
+                Tok::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(tokens[j].1 .1);
+                        break;
+                    }
+                },
+                _ => {},
+            }
+            j += 1;
+        }
+        match end {
+            Some(end) => {
+                ret.push(Range::new(index.position(start), index.position(end)));
+                i = j + 1;
+            },
+            None => break,
+        }
+    }
+    ret
+}
+
+/// Warnings of the pragmas of `content` whose names are close to, but not, a known pragma.
+pub fn pragma_typos(content: &str) -> Vec<Diagnostic> {
+    let index = LineIndex::new(content);
+    let tokens = lexer_for_buffer(content);
+    let text = |i: usize| &content[tokens[i].1 .0..tokens[i].1 .1];
+    let mut ret = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].0 != Tok::Identifier || text(i) != "pragma" {
+            i += 1;
+            continue;
+        }
+        // `pragma a, b = 1, c;`
+        let mut expect_name = true;
+        i += 1;
+        while i < tokens.len() && tokens[i].0 != Tok::Semicolon && tokens[i].0 != Tok::RBrace {
+            match tokens[i].0 {
+                Tok::Identifier if expect_name => {
+                    let name = text(i);
+                    if let Some(x) = suggest_pragma(name) {
+                        ret.push(Diagnostic {
+                            range: Range::new(
+                                index.position(tokens[i].1 .0),
+                                index.position(tokens[i].1 .1),
+                            ),
+                            severity: Some(DiagnosticSeverity::WARNING),
+                            source: Some(SPEC_SOURCE.to_string()),
+                            message: format!("unknown pragma `{}`, did you mean `{}`?", name, x),
+                            ..Default::default()
+                        });
+                    }
+                    expect_name = false;
+                },
+                Tok::Comma => expect_name = true,
+                _ => expect_name = false,
+            }
+            i += 1;
+        }
+    }
+    ret
+}
+
+/// The known pragma `name` is a typo of, None if `name` is known or nothing is close to it.
+fn suggest_pragma(name: &str) -> Option<&'static str> {
+    if KNOWN_PRAGMAS.contains(&name) || name.len() < 3 {
+        return None;
+    }
+    KNOWN_PRAGMAS
+        .iter()
+        .map(|x| (edit_distance(name, x), *x))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, x)| x)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let x = if ca == *cb { prev[j] } else { prev[j] + 1 };
+            cur.push(x.min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+fn range_contains(r: &Range, pos: &Position) -> bool {
+    (r.start.line, r.start.character) <= (pos.line, pos.character)
+        && (pos.line, pos.character) < (r.end.line, r.end.character)
+}
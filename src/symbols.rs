@@ -48,7 +48,10 @@
 
 use crate::{context::Context, project::Project, utils::get_modules_by_fpath_in_all_modules};
 use lsp_server::Request;
-use lsp_types::{DocumentSymbol, DocumentSymbolParams, SymbolKind};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, Location, SymbolInformation, SymbolKind,
+    WorkspaceSymbolParams,
+};
 use move_model::{
     ast::SpecBlockTarget,
     model::{ModuleEnv, StructEnv},
@@ -162,6 +165,36 @@ pub fn on_document_symbol_request(context: &Context, request: &Request) -> lsp_s
     response
 }
 
+/// Handles workspace symbol request of the language server, answered from the package indexes.
+#[allow(deprecated)]
+pub fn on_workspace_symbol_request(context: &Context, request: &Request) -> lsp_server::Response {
+    log::info!("on_workspace_symbol_request");
+    let parameters = serde_json::from_value::<WorkspaceSymbolParams>(request.params.clone())
+        .expect("could not deserialize workspace symbol request");
+    let result: Vec<SymbolInformation> = context
+        .projects
+        .index_symbols(parameters.query.as_str())
+        .into_iter()
+        .filter_map(|x| {
+            Some(SymbolInformation {
+                name: x.name.clone(),
+                kind: x.kind,
+                tags: None,
+                deprecated: None,
+                location: Location::new(url::Url::from_file_path(&x.file).ok()?, x.range),
+                container_name: Some(x.container.clone()).filter(|x| !x.is_empty()),
+            })
+        })
+        .collect();
+    let response = lsp_server::Response::new_ok(request.id.clone(), serde_json::json!(result));
+    context
+        .connection
+        .sender
+        .send(lsp_server::Message::Response(response.clone()))
+        .unwrap();
+    response
+}
+
 /// Helper function to handle Spec function in the document symbols
 #[allow(deprecated)]
 fn handle_document_symbols_spec_function(
@@ -298,13 +331,16 @@ fn handle_document_symbols_struct_fields(
     children: &mut Vec<DocumentSymbol>,
 ) {
     for field_env in struct_env.get_fields() {
+        let name = field_env
+            .get_name()
+            .display(struct_env.symbol_pool())
+            .to_string();
+        // the fields of a positional struct are named after their position, the model locates
+        // them at their type.
         let field_range = project.loc_to_range(field_env.get_loc());
 
         children.push(DocumentSymbol {
-            name: field_env
-                .get_name()
-                .display(struct_env.symbol_pool())
-                .to_string(),
+            name,
             detail: None,
             kind: SymbolKind::FIELD,
             range: field_range,
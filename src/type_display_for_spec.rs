@@ -6,6 +6,10 @@
 //! `TypeDisplay` structure so that when printing a struct, it only displays `struct_name`
 //! for structs within this module. For structs from other modules, it prints in the form
 //! "module_name::struct_name" instead of displaying all structs as "module_name::struct_name".
+//!
+//! Structs are printed with the shortest name the spec file of the module can use: the bare
+//! name in the same module, the name given by a `use` of the struct or of its module, and the
+//! name with the module address otherwise.
 
 use move_model::{
     ast::ModuleName,
@@ -14,10 +18,20 @@ use move_model::{
     ty::{ReferenceKind, Type, TypeDisplayContext},
 };
 use std::{collections::HashMap, fmt, fmt::Formatter};
+
+/// The names the `use` declarations of a module give to the modules and the structs it uses.
+#[derive(Clone, Debug, Default)]
+pub struct SpecUseDecls {
+    /// `use a::m;`, `use a::m as n;` or `use a::m::{Self as n};`.
+    pub modules: HashMap<ModuleName, Symbol>,
+    /// `use a::m::S;` or `use a::m::{S as T};`.
+    pub members: HashMap<(ModuleName, Symbol), Symbol>,
+}
+
 pub struct TypeDisplayForSpec<'a> {
     pub type_: &'a Type,
     pub context: &'a TypeDisplayContext<'a>,
-    pub using_module_map: &'a HashMap<ModuleName, Vec<Symbol>>,
+    pub using_module_map: &'a SpecUseDecls,
     pub module_env: &'a ModuleEnv<'a>,
 }
 
@@ -69,9 +83,15 @@ impl<'a> fmt::Display for TypeDisplayForSpec<'a> {
             },
             Fun(a, t) => {
                 f.write_str("|")?;
-                write!(f, "{}", self.make(a))?;
+                match a.as_ref() {
+                    Tuple(ts) => comma_list(f, ts)?,
+                    _ => write!(f, "{}", self.make(a))?,
+                }
                 f.write_str("|")?;
-                write!(f, "{}", self.make(t))
+                match t.as_ref() {
+                    Tuple(ts) if ts.is_empty() => Ok(()),
+                    _ => write!(f, " {}", self.make(t)),
+                }
             },
             Struct(mid, sid, ts) => {
                 write!(f, "{}", self.struct_str(*mid, *sid))?;
@@ -128,16 +148,26 @@ impl<'a> TypeDisplayForSpec<'a> {
                 return struct_env_name.display(env.symbol_pool()).to_string();
             }
 
-            if let Some(members) = self.using_module_map.get(struct_module_env_name) {
-                let a = members.iter().find(|&&x| x == struct_env_name);
-                if let Some(x) = a {
-                    return x.display(env.symbol_pool()).to_string();
-                }
+            if let Some(x) = self
+                .using_module_map
+                .members
+                .get(&(struct_module_env_name.clone(), struct_env_name))
+            {
+                return x.display(env.symbol_pool()).to_string();
+            }
+
+            if let Some(x) = self.using_module_map.modules.get(struct_module_env_name) {
+                return format!(
+                    "{}::{}",
+                    x.display(env.symbol_pool()),
+                    struct_env_name.display(env.symbol_pool())
+                );
             }
 
+            // not used by the module, the address makes the name valid anywhere.
             format!(
                 "{}::{}",
-                struct_module_env_name.display(env),
+                struct_module_env.get_full_name_str(),
                 struct_env_name.display(env.symbol_pool())
             )
         }
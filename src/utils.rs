@@ -1,16 +1,29 @@
 // Copyright (c) The BitsLab.MoveBit Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::type_display_for_spec::SpecUseDecls;
 use codespan::FileId;
 use codespan_reporting::files::{Files, SimpleFiles};
 use lsp_types::{Command, Location, Position};
 use move_command_line_common::files::FileHash;
-use move_compiler::parser::lexer::{Lexer, Tok};
+use move_compiler::{
+    diagnostics::Diagnostics,
+    parser::{
+        ast::Definition,
+        lexer::{Lexer, Tok},
+        syntax::parse_file_string,
+    },
+    shared::{CompilationEnv, Flags},
+};
 use move_ir_types::location::*;
-use move_model::{ast::ModuleName, symbol::Symbol as SpecSymbol};
+use move_model::ast::ModuleName;
 use move_package::source_package::layout::SourcePackageLayout;
 use move_symbol_pool::Symbol;
-use std::{collections::HashMap, path::*, vec};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::*,
+    vec,
+};
 
 /// Converts a location from the byte index format to the line/character (Position) format, where
 /// line/character are 0-based.
@@ -179,6 +192,98 @@ impl std::fmt::Display for FileRange {
     }
 }
 
+/// Line starts of a buffer, to convert between byte offsets and positions of the buffer. The
+/// characters of a position are UTF-16 code units, as the LSP counts them.
+pub struct LineIndex {
+    content: String,
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(content: &str) -> Self {
+        let mut starts = vec![0];
+        for (index, b) in content.as_bytes().iter().enumerate() {
+            if *b == b'\n' {
+                starts.push(index + 1);
+            }
+        }
+        Self {
+            content: content.to_string(),
+            starts,
+        }
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.content.len());
+        let line = match self.starts.binary_search(&offset) {
+            Ok(x) => x,
+            Err(x) => x - 1,
+        };
+        let start = self.starts[line];
+        let character: usize = self.content[start..]
+            .char_indices()
+            .take_while(|(i, _)| start + i < offset)
+            .map(|(_, c)| c.len_utf16())
+            .sum();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, pos: &Position) -> usize {
+        let start = match self.starts.get(pos.line as usize) {
+            Some(x) => *x,
+            None => return self.content.len(),
+        };
+        let mut character = 0;
+        for (i, c) in self.content[start..].char_indices() {
+            if character >= pos.character as usize || c == '\n' {
+                return start + i;
+            }
+            character += c.len_utf16();
+        }
+        self.content.len()
+    }
+
+    pub fn range(&self, loc: &Loc) -> lsp_types::Range {
+        lsp_types::Range::new(
+            self.position(loc.start() as usize),
+            self.position(loc.end() as usize),
+        )
+    }
+}
+
+/// Parse a buffer into the parser AST, this works even when the buffer does not type check.
+pub fn parse_buffer(content: &str) -> Option<Vec<Definition>> {
+    parse_buffer_with_diags(content).ok()
+}
+
+/// Like `parse_buffer`, with the syntax errors when the buffer does not parse.
+pub fn parse_buffer_with_diags(content: &str) -> Result<Vec<Definition>, Diagnostics> {
+    let mut env = CompilationEnv::new(Flags::testing(), BTreeSet::new());
+    parse_file_string(&mut env, FileHash::new(content), content).map(|(defs, _)| defs)
+}
+
+/// Directories of the Move packages under `folder`, build outputs and hidden directories
+/// skipped.
+pub fn discover_manifests(folder: &Path) -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    let walker = walkdir::WalkDir::new(folder).into_iter().filter_entry(|x| {
+        let name = x.file_name().to_str().unwrap_or_default();
+        x.depth() == 0
+            || !(x.file_type().is_dir()
+                && (name.starts_with('.')
+                    || name == SourcePackageLayout::Build.location_str()
+                    || name == "node_modules"))
+    });
+    for item in walker.flatten() {
+        if item.file_type().is_file() && item.file_name() == PROJECT_FILE_NAME {
+            if let Some(dir) = item.path().parent() {
+                ret.push(normal_path(dir));
+            }
+        }
+    }
+    ret
+}
+
 /// Path concat from
 pub fn path_concat(p1: &Path, p2: &Path) -> PathBuf {
     let p2: Vec<_> = p2.components().collect();
@@ -236,6 +341,14 @@ pub(crate) fn normal_path(p: &Path) -> PathBuf {
     normal_path_components(&x)
 }
 
+/// Hex of the first 8 bytes of the sha256 of `s`, to name the files cached for a path.
+pub fn short_hash(s: &str) -> String {
+    FileHash::new(s).0[..8]
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
+
 pub trait GetPosition {
     fn get_position(&self) -> (PathBuf, u32 /* line */, u32 /* col */);
     fn in_range(x: &impl GetPosition, range: &FileRange) -> bool {
@@ -467,37 +580,40 @@ pub fn collect_use_decl(
     addrname_2_addrnum: &std::collections::HashMap<String, String>,
     module_env: &ModuleEnv,
     global_env: &GlobalEnv,
-) -> HashMap<ModuleName, Vec<SpecSymbol>> {
-    let mut result: HashMap<ModuleName, Vec<SpecSymbol>> = Default::default();
+) -> SpecUseDecls {
+    let mut result = SpecUseDecls::default();
     for using_decl in module_env.get_use_decls() {
         let using_decl_name = using_decl.module_name.display_full(global_env).to_string();
+        log::trace!("full module name {}", using_decl_name);
 
+        // the module names of the model use the address numbers.
         let before_after = using_decl_name.split("::").collect::<Vec<_>>();
-        if before_after.len() != 2 {
-            log::error!("use decl module name len should be 2");
-            continue;
-        }
-
-        let addr_name = before_after[0];
-        log::trace!("full module name {}", using_decl_name);
-        let addr_num = match addrname_2_addrnum.get(addr_name) {
-            Some(x) => x,
-            None => {
-                log::error!("use decl module name not found in project");
-                continue;
-            },
+        let module_name = match before_after
+            .first()
+            .filter(|_| before_after.len() == 2)
+            .and_then(|x| addrname_2_addrnum.get(*x))
+        {
+            Some(addr_num) => ModuleName::from_str(addr_num, using_decl.module_name.name()),
+            None => using_decl.module_name.clone(),
         };
 
-        let addr_addrnum_with_module_name =
-            ModuleName::from_str(addr_num, using_decl.module_name.name());
-        let mut using_decl_member: Vec<SpecSymbol> = vec![];
-        for (_, acc_symbol, may_sym) in using_decl.members.clone().into_iter() {
-            match may_sym {
-                Some(x) => using_decl_member.push(x),
-                None => using_decl_member.push(acc_symbol),
+        if using_decl.members.is_empty() {
+            result.modules.insert(
+                module_name.clone(),
+                using_decl.alias.unwrap_or_else(|| module_name.name()),
+            );
+        }
+        for (_, acc_symbol, may_sym) in using_decl.members.iter() {
+            if acc_symbol.display(global_env.symbol_pool()).to_string() == "Self" {
+                let local = may_sym.unwrap_or_else(|| module_name.name());
+                result.modules.insert(module_name.clone(), local);
+            } else {
+                let local = may_sym.unwrap_or(*acc_symbol);
+                result
+                    .members
+                    .insert((module_name.clone(), *acc_symbol), local);
             }
         }
-        result.insert(addr_addrnum_with_module_name, using_decl_member);
     }
     result
 }
@@ -546,3 +662,69 @@ pub fn lexer_for_buffer(buffer: &str) -> Vec<(Tok, (usize, usize))> {
     }
     ids
 }
+
+/// The token following the first argument of a call to the function `name` with the arguments
+/// `args`, which tells how the call is written: the function name of a receiver style call like
+/// `v.push_back(1)`, or the opening bracket of an index expression like `v[i]`. `None` for calls
+/// written `vector::push_back(v, 1)`. The token is looked for between the locations of the first
+/// argument and of the next one, which leaves out the receiver and the arguments, and the lexer
+/// skips the comments.
+pub fn call_operator_loc(
+    env: &GlobalEnv,
+    call_loc: &move_model::model::Loc,
+    args: &[move_model::ast::Exp],
+    name: &str,
+) -> Option<move_model::model::Loc> {
+    let first = env.get_node_loc(args.first()?.node_id());
+    let end = match args.get(1) {
+        Some(x) => env.get_node_loc(x.node_id()).span().start(),
+        None => call_loc.span().end(),
+    };
+    if first.file_id() != call_loc.file_id() || first.span().end() >= end {
+        return None;
+    }
+    let gap = move_model::model::Loc::new(
+        call_loc.file_id(),
+        codespan::Span::new(first.span().end(), end),
+    );
+    let source = env.get_source(&gap).ok()?;
+    let tokens = lexer_for_buffer(source);
+    let (start, end) = match tokens.as_slice() {
+        [(Tok::LBracket, x), ..] => *x,
+        [(Tok::Period, _), (Tok::Identifier, x), ..] if &source[x.0..x.1] == name => *x,
+        _ => return None,
+    };
+    Some(move_model::model::Loc::new(
+        call_loc.file_id(),
+        codespan::Span::new(
+            gap.span().start() + codespan::ByteOffset(start as i64),
+            gap.span().start() + codespan::ByteOffset(end as i64),
+        ),
+    ))
+}
+
+/// The location a call to the function `name` with the arguments `args` is captured with when the
+/// mouse is at `mouse`: the whole call, or only the function name of a receiver style call, its
+/// receiver and arguments being expressions of their own. The `vector::borrow` call of an index
+/// expression like `v[i]` is captured at its opening bracket only, so the vector and the index
+/// resolve on their own.
+pub fn call_capture_loc(
+    env: &GlobalEnv,
+    call_loc: &move_model::model::Loc,
+    args: &[move_model::ast::Exp],
+    name: &str,
+    mouse: codespan::ByteIndex,
+) -> Option<move_model::model::Loc> {
+    let span = call_loc.span();
+    if mouse <= span.start() || span.end() <= mouse {
+        return None;
+    }
+    let name_loc = match call_operator_loc(env, call_loc, args, name) {
+        Some(x) => x,
+        None => return Some(call_loc.clone()),
+    };
+    if mouse < name_loc.span().start() || name_loc.span().end() < mouse {
+        return None;
+    }
+    Some(name_loc)
+}
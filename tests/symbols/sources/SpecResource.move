@@ -0,0 +1,76 @@
+module Symbols::M4 {
+    use std::signer;
+
+    struct Counter has key {
+        value: u64,
+    }
+
+    fun init(account: &signer) {
+        move_to(account, Counter { value: 0 });
+    }
+
+    fun get(addr: address): u64 acquires Counter {
+        borrow_global<Counter>(addr).value
+    }
+
+    fun bump(addr: address) acquires Counter {
+        let counter = borrow_global_mut<Counter>(addr);
+        counter.value = 1;
+    }
+
+    fun reset(account: &signer) acquires Counter {
+        bump(signer::address_of(account));
+    }
+
+    fun remove(account: &signer): u64 acquires Counter {
+        let Counter { value } = move_from<Counter>(signer::address_of(account));
+        value
+    }
+
+    fun checked_add(a: u64, b: u64): u64 {
+        let sum = a + b;
+        assert!(sum < 100, 1);
+        sum
+    }
+
+    fun add_twice(x: u64): u64 {
+        checked_add(x * 2, 1)
+    }
+
+    const MAX_ITEMS: u64 = 10;
+
+    struct Inventory has key {
+        items: vector<u64>,
+        owner: std::option::Option<address>,
+    }
+
+    fun new_inventory(items: vector<u64>, owner: address): Inventory {
+        assert!(std::vector::length(&items) <= MAX_ITEMS, 2);
+        Inventory { items, owner: std::option::some(owner) }
+    }
+
+    fun set_twice(addr: address, x: u64) acquires Counter {
+        let counter = borrow_global_mut<Counter>(addr);
+        counter.value = 1;
+        counter.value = x;
+    }
+
+    fun copy_value(addr: address) acquires Counter {
+        let counter = borrow_global_mut<Counter>(addr);
+        let v = counter.value;
+        counter.value = v + 1;
+    }
+
+    struct Bag has key {
+        items: vector<u64>,
+    }
+
+    fun new_bag(items: vector<u64>): Bag {
+        assert!(std::vector::length(&items) <= MAX_ITEMS, 2);
+        Bag { items }
+    }
+
+    fun add_to_bag(bag: &mut Bag, x: u64) {
+        std::vector::push_back(&mut bag.items, x);
+    }
+}
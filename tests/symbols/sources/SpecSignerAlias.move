@@ -0,0 +1,15 @@
+module Symbols::M6 {
+    use std::signer::address_of;
+
+    struct Flag has key {
+        on: bool,
+    }
+
+    fun init(account: &signer) {
+        move_to(account, Flag { on: true });
+    }
+
+    fun is_on(account: &signer): bool {
+        exists<Flag>(address_of(account))
+    }
+}
@@ -0,0 +1,28 @@
+module Symbols::M5 {
+    use std::option as opt;
+    use std::string::{String as Text};
+    use std::ascii::{Self};
+    use Symbols::M4::Inventory;
+
+    struct Wrapper<T> has drop {
+        value: T,
+    }
+
+    fun module_alias(_x: opt::Option<u64>) {
+    }
+
+    fun member_alias(_x: Text) {
+    }
+
+    fun self_member(_x: ascii::String) {
+    }
+
+    fun nested(_x: vector<opt::Option<Wrapper<std::fixed_point32::FixedPoint32>>>) {
+    }
+
+    fun references(_x: &mut vector<Text>, _y: &Inventory) {
+    }
+
+    inline fun fun_param(_f: |u64, u64| u64, _g: |u64|) {
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+
+    use aptos_move_analyzer::{
+        code_action::{
+            on_code_action_request, on_code_action_resolve_request, resolve_code_action,
+            spec_code_actions,
+        },
+        context::*,
+        multiproject::MultiProject,
+        utils::{discover_manifest_and_kind, path_concat},
+    };
+    use lsp_server::*;
+    use lsp_types::{CodeAction, CodeActionKind, DocumentChanges, Position, Range, TextEdit, Url};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn prepare_project(context: &mut Context, fpath: PathBuf) {
+        let (mani, _) = match discover_manifest_and_kind(&fpath) {
+            Some(x) => x,
+            None => {
+                log::error!("not move project.");
+                return;
+            },
+        };
+        if context.projects.get_project(&fpath).is_some() {
+            return;
+        }
+        let p = match context.projects.load_projects(&context.connection, &mani) {
+            anyhow::Result::Ok(x) => x,
+            anyhow::Result::Err(e) => {
+                log::error!("load project failed,err:{:?}", e);
+                return;
+            },
+        };
+        context.projects.insert_project(p);
+    }
+
+    fn new_context() -> (Context, PathBuf) {
+        let (connection, _) = Connection::stdio();
+        let mut context = Context {
+            projects: MultiProject::new(),
+            connection,
+            diag_version: FileDiags::new(),
+        };
+        let fpath = path_concat(
+            std::env::current_dir().unwrap().as_path(),
+            PathBuf::from("tests/symbols/sources/SpecResource.move").as_path(),
+        );
+        prepare_project(&mut context, fpath.clone());
+        (context, fpath)
+    }
+
+    /// Spec of the struct under the cursor, and spec file of the module.
+    #[test]
+    fn test_code_action_001() {
+        let (context, fpath) = new_context();
+        let project = context
+            .projects
+            .get_project(&fpath)
+            .expect("the project of the test file loads");
+        let actions = spec_code_actions(project, &fpath, Position::new(41, 5));
+        assert_eq!(actions.len(), 2);
+        // the edits are generated when an action is resolved.
+        assert!(actions.iter().all(|x| x.edit.is_none() && x.data.is_some()));
+
+        assert_eq!(actions[0].title, "Generate spec for struct `Inventory`");
+        assert_eq!(actions[0].kind, Some(CodeActionKind::REFACTOR));
+        let resolved = resolve_code_action(project, actions[0].clone());
+        let changes = resolved.edit.unwrap().changes.unwrap();
+        assert_eq!(
+            changes.get(&Url::from_file_path(&fpath).unwrap()),
+            Some(&vec![TextEdit {
+                range: Range::new(Position::new(45, 0), Position::new(45, 0)),
+                new_text: String::from("    spec Inventory{\n        invariant len(items) <= MAX_ITEMS;\n        invariant 0x1::option::is_some(owner);\n    }\n"),
+            }])
+        );
+
+        assert_eq!(actions[1].title, "Generate spec file for this module");
+        let resolved = resolve_code_action(project, actions[1].clone());
+        match resolved.edit.unwrap().document_changes {
+            Some(DocumentChanges::Operations(x)) => assert_eq!(x.len(), 2),
+            _ => panic!("the spec file should be created"),
+        }
+    }
+
+    /// Nothing but refactorings is offered.
+    #[test]
+    fn test_code_action_002() {
+        let (context, fpath) = new_context();
+        assert!(
+            context.projects.get_project(&fpath).is_some(),
+            "the project of the test file loads"
+        );
+        let request = Request {
+            id: "code_action_002".to_string().into(),
+            method: String::from("textDocument/codeAction"),
+            params: json!({
+                "textDocument": {
+                    "uri": Url::from_file_path(&fpath).unwrap(),
+                },
+                "range": {
+                    "start": { "line": 41, "character": 5 },
+                    "end": { "line": 41, "character": 5 },
+                },
+                "context": {
+                    "diagnostics": [],
+                    "only": ["quickfix"],
+                },
+            }),
+        };
+        let actual = on_code_action_request(&context, &request, true);
+        assert!(actual.error.is_none());
+        assert_eq!(actual.result, Some(json!([])));
+    }
+
+    /// The action the client picked is resolved with its edit.
+    #[test]
+    fn test_code_action_resolve_003() {
+        let (context, fpath) = new_context();
+        let project = context
+            .projects
+            .get_project(&fpath)
+            .expect("the project of the test file loads");
+        let action = spec_code_actions(project, &fpath, Position::new(41, 5)).remove(0);
+        let request = Request {
+            id: "code_action_resolve_003".to_string().into(),
+            method: String::from("codeAction/resolve"),
+            params: serde_json::to_value(&action).unwrap(),
+        };
+        let actual = on_code_action_resolve_request(&context, &request);
+        assert!(actual.error.is_none());
+        let resolved: CodeAction = serde_json::from_value(actual.result.unwrap()).unwrap();
+        assert_eq!(resolved.title, action.title);
+        assert!(resolved.edit.unwrap().changes.is_some());
+    }
+
+    /// A client which can not resolve the edits gets the actions with them.
+    #[test]
+    fn test_code_action_004() {
+        let (context, fpath) = new_context();
+        let project = context
+            .projects
+            .get_project(&fpath)
+            .expect("the project of the test file loads");
+        let actions = spec_code_actions(project, &fpath, Position::new(41, 5), false);
+        assert_eq!(actions.len(), 2);
+        assert!(actions.iter().all(|x| x.edit.is_some()));
+        let resolved = resolve_code_action(project, actions[0].clone());
+        assert_eq!(resolved.edit, actions[0].edit);
+    }
+}
@@ -0,0 +1,24 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::move_coverage::uncovered_ranges;
+    use lsp_types::{Position, Range};
+    use move_coverage::source_coverage::StringSegment;
+
+    #[test]
+    fn test_uncovered_ranges_001() {
+        let segments = vec![
+            StringSegment::Covered("    if (x > 0) ".to_string()),
+            StringSegment::Uncovered("{ abort ".to_string()),
+            StringSegment::Uncovered("1 } ".to_string()),
+            StringSegment::Covered("else ".to_string()),
+            StringSegment::Uncovered("   ".to_string()),
+        ];
+        assert_eq!(
+            uncovered_ranges(3, &segments),
+            vec![Range::new(Position::new(3, 15), Position::new(3, 26))]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{
+        dependency_cache::{DependencyCache, ResolvedDependencies},
+        project::{Project, ProjectConfig},
+    };
+    use move_compiler::shared::{NumberFormat, NumericalAddress};
+    use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+    fn addresses(value: u8) -> BTreeMap<String, NumericalAddress> {
+        let mut bytes = [0u8; 32];
+        bytes[31] = value;
+        let mut ret = BTreeMap::new();
+        ret.insert(
+            "std".to_string(),
+            NumericalAddress::new(bytes, NumberFormat::Hex),
+        );
+        ret
+    }
+
+    #[test]
+    fn test_dependency_sources_shared_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdlib = dir.path().join("stdlib");
+        std::fs::create_dir_all(stdlib.join("sources")).unwrap();
+        std::fs::write(stdlib.join("sources/vector.move"), "module std::vector {}").unwrap();
+
+        let mut cache = DependencyCache::new();
+        let mut scans = 0;
+        let first = cache.package_paths(&stdlib, &addresses(1), || {
+            scans += 1;
+            vec![stdlib.join("sources/vector.move")]
+        });
+        let second = cache.package_paths(&stdlib, &addresses(1), || {
+            scans += 1;
+            vec![]
+        });
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(first.paths.len(), 1);
+        // another assignment of the named addresses is another compilation.
+        let other = cache.package_paths(&stdlib, &addresses(2), || vec![]);
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert_eq!(scans, 1);
+
+        cache.invalidate(&stdlib);
+        assert_eq!(cache.package_count(), 0);
+    }
+
+    /// Roots assigning their own named addresses differently share the sources of a dependency.
+    #[test]
+    fn test_dependency_sources_shared_002() {
+        let dir = tempfile::tempdir().unwrap();
+        let stdlib = dir.path().join("stdlib");
+        std::fs::create_dir_all(stdlib.join("sources")).unwrap();
+        std::fs::write(
+            stdlib.join("Move.toml"),
+            "[package]\nname = \"MoveStdlib\"\nversion = \"1.0.0\"\n\n[addresses]\nstd = \"0x1\"\n",
+        )
+        .unwrap();
+
+        let mut cache = DependencyCache::new();
+        let mut first_addresses = addresses(1);
+        first_addresses.insert("app".to_string(), addresses(3)["std"]);
+        let mut second_addresses = addresses(1);
+        second_addresses.insert("app".to_string(), addresses(4)["std"]);
+        let first = cache.package_paths(&stdlib, &first_addresses, Vec::new);
+        let second = cache.package_paths(&stdlib, &second_addresses, Vec::new);
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(first.named_address_map, addresses(1));
+    }
+
+    #[test]
+    fn test_resolution_outdated_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        let dep = dir.path().join("stdlib");
+        for p in [&root, &dep] {
+            std::fs::create_dir_all(p).unwrap();
+            std::fs::write(p.join("Move.toml"), "").unwrap();
+        }
+        let mut cache = DependencyCache::new();
+        cache.insert_resolution(
+            &root,
+            ResolvedDependencies::new(&root, vec![dep.clone()], vec!["std=0x1".to_string()]),
+        );
+        assert_eq!(cache.resolution(&root).unwrap().packages, vec![dep.clone()]);
+        // a manifest read by the resolution changed.
+        std::fs::remove_file(dep.join("Move.toml")).unwrap();
+        assert!(cache.resolution(&root).is_none());
+    }
+
+    /// A git dependency missing from the local package cache is reported, not fetched.
+    #[test]
+    fn test_missing_git_dependency_not_fetched_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("app");
+        std::fs::create_dir_all(root.join("sources")).unwrap();
+        std::fs::write(
+            root.join("Move.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.0.0\"\n\n[dependencies]\nMissing = { git = \"https://example.invalid/missing.git\", rev = \"main\" }\n",
+        )
+        .unwrap();
+        let cache = Rc::new(RefCell::new(DependencyCache::new()));
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let report = errors.clone();
+        let project = Project::new_with_cache(
+            &root,
+            move |x| report.borrow_mut().push(x),
+            cache.clone(),
+            ProjectConfig::default(),
+        )
+        .unwrap();
+        assert!(!project.load_ok());
+        assert!(errors
+            .borrow()
+            .iter()
+            .any(|x| x.contains("not in the local package cache")));
+        assert!(cache.borrow().resolution(&root).is_none());
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{folding_range::folding_ranges, utils::LineIndex};
+    use lsp_types::{FoldingRangeKind, Position};
+
+    const SOURCE: &str = r#"/// A module
+/// with a long doc.
+module 0x1::m {
+    use std::vector;
+    use std::signer;
+
+    struct S has key {
+        v: vector<u8>,
+    }
+
+    /* a block
+       comment */
+    public fun f(): vector<u8> {
+        b"http://not/a/comment"
+    }
+
+    spec f {
+        aborts_if false;
+    }
+}
+"#;
+
+    #[test]
+    fn test_folding_ranges_001() {
+        let ranges: Vec<_> = folding_ranges(SOURCE)
+            .into_iter()
+            .map(|x| (x.start_line, x.end_line, x.kind.unwrap()))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 1, FoldingRangeKind::Comment),
+                (2, 19, FoldingRangeKind::Region),
+                (3, 4, FoldingRangeKind::Imports),
+                (6, 8, FoldingRangeKind::Region),
+                (10, 11, FoldingRangeKind::Comment),
+                (12, 14, FoldingRangeKind::Region),
+                (16, 18, FoldingRangeKind::Region),
+            ]
+        );
+    }
+
+    /// Characters of positions are UTF-16 code units, `𝄞` takes two of them and four bytes.
+    #[test]
+    fn test_line_index_utf16() {
+        let content = "// 𝄞 é\nfun f() {}\n";
+        let index = LineIndex::new(content);
+        let after = content.find(" é").unwrap();
+        assert_eq!(index.position(after), Position::new(0, 5));
+        assert_eq!(index.offset(&Position::new(0, 5)), after);
+        let fun = content.find("fun").unwrap();
+        assert_eq!(index.position(fun + 4), Position::new(1, 4));
+        assert_eq!(index.offset(&Position::new(0, 100)), fun - 1);
+    }
+}
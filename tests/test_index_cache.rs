@@ -0,0 +1,160 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{
+        index_cache::{
+            index_path, source_hashes, IndexedReference, IndexedSymbol, PackageIndex, INDEX_VERSION,
+        },
+        multiproject::MultiProject,
+        project::ProjectConfig,
+    };
+    use lsp_types::{Position, Range, SymbolKind};
+    use std::path::PathBuf;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    fn symbol(name: &str, file: &str, range: Range) -> IndexedSymbol {
+        IndexedSymbol {
+            name: name.to_string(),
+            container: "0x1::vector".to_string(),
+            kind: SymbolKind::FUNCTION,
+            file: PathBuf::from(file),
+            range,
+            detail: format!("public fun {}()", name),
+        }
+    }
+
+    #[test]
+    fn test_index_lookup_001() {
+        let mut projects = MultiProject::new();
+        projects.indexes.insert(
+            PathBuf::from("/stdlib"),
+            PackageIndex {
+                version: INDEX_VERSION,
+                package: PathBuf::from("/stdlib"),
+                symbols: vec![
+                    symbol("push_back", "/stdlib/sources/vector.move", range(10, 4, 40)),
+                    symbol("pop_back", "/stdlib/sources/vector.move", range(20, 4, 40)),
+                ],
+                ..Default::default()
+            },
+        );
+        projects.indexes.insert(
+            PathBuf::from("/app"),
+            PackageIndex {
+                version: INDEX_VERSION,
+                package: PathBuf::from("/app"),
+                references: vec![IndexedReference {
+                    file: PathBuf::from("/app/sources/m.move"),
+                    range: range(3, 8, 25),
+                    target: "0x1::vector::push_back".to_string(),
+                }],
+                ..Default::default()
+            },
+        );
+
+        let app = PathBuf::from("/app/sources/m.move");
+        let found = projects
+            .index_symbol_at(&app, &Position::new(3, 12))
+            .unwrap();
+        assert_eq!(found.range, range(10, 4, 40));
+        assert!(projects
+            .index_symbol_at(&app, &Position::new(4, 12))
+            .is_none());
+        // on a definition.
+        let stdlib = PathBuf::from("/stdlib/sources/vector.move");
+        let found = projects
+            .index_symbol_at(&stdlib, &Position::new(20, 10))
+            .unwrap();
+        assert_eq!(found.name, "pop_back");
+
+        let names: Vec<_> = projects
+            .index_symbols("BACK")
+            .into_iter()
+            .map(|x| x.name.clone())
+            .collect();
+        assert_eq!(names, vec!["pop_back", "push_back"]);
+    }
+
+    #[test]
+    fn test_index_up_to_date_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().to_path_buf();
+        std::fs::create_dir_all(package.join("sources")).unwrap();
+        std::fs::write(package.join("Move.toml"), "").unwrap();
+        std::fs::write(package.join("sources/m.move"), "module 0x1::m {}").unwrap();
+        let config = ProjectConfig::default();
+        let index = PackageIndex {
+            version: INDEX_VERSION,
+            package: package.clone(),
+            config: config.cache_key(),
+            files: source_hashes(&package),
+            ..Default::default()
+        };
+        // the manifest and the source file.
+        assert_eq!(index.files.len(), 2);
+        assert!(index.up_to_date(&config));
+        std::fs::write(
+            package.join("sources/m.move"),
+            "module 0x1::m { fun f() {} }",
+        )
+        .unwrap();
+        assert!(!index.up_to_date(&config));
+    }
+
+    /// An index is stale once the settings or a dependency change.
+    #[test]
+    fn test_index_up_to_date_002() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("app");
+        let dependency = dir.path().join("lib");
+        for x in [&package, &dependency] {
+            std::fs::create_dir_all(x.join("sources")).unwrap();
+            std::fs::write(x.join("Move.toml"), "").unwrap();
+        }
+        std::fs::write(dependency.join("sources/l.move"), "module 0x1::l {}").unwrap();
+        let config = ProjectConfig::default();
+        let index = PackageIndex {
+            version: INDEX_VERSION,
+            package: package.clone(),
+            config: config.cache_key(),
+            files: source_hashes(&package),
+            dependencies: vec![dependency.clone()],
+            dependency_files: source_hashes(&dependency),
+            ..Default::default()
+        };
+        assert!(index.up_to_date(&config));
+        let dev = ProjectConfig {
+            dev_mode: true,
+            ..Default::default()
+        };
+        assert!(!index.up_to_date(&dev));
+        std::fs::write(dependency.join("Move.toml"), "[package]").unwrap();
+        assert!(!index.up_to_date(&config));
+    }
+
+    /// The index of a package is found again by later runs, whatever the toolchain, and the
+    /// indexes built with other settings are kept apart.
+    #[test]
+    fn test_index_path_stable_001() {
+        let config = ProjectConfig::default();
+        let path = index_path(&PathBuf::from("/work/app"), &config).expect("no cache directory");
+        assert_eq!(
+            path.file_name().unwrap().to_str().unwrap(),
+            format!("app-70467eff2e0a2364-{}.json", config.cache_key())
+        );
+        assert_eq!(
+            index_path(&PathBuf::from("/work/./app"), &config),
+            Some(path.clone())
+        );
+        let dev = ProjectConfig {
+            dev_mode: true,
+            ..Default::default()
+        };
+        assert_ne!(index_path(&PathBuf::from("/work/app"), &dev), Some(path));
+    }
+}
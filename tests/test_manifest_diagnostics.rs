@@ -0,0 +1,108 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{
+        manifest_diagnostics::{key_range, manifest_diagnostics},
+        project::ProjectConfig,
+    };
+    use lsp_types::{Position, Range};
+    use url::Url;
+
+    const MANIFEST: &str = r#"[package]
+name = "root"
+version = "0.0.0"
+
+[addresses]
+root = "_"
+std = "0x1"
+
+[dependencies]
+Missing = { local = "../missing" }
+
+[dependencies.Other]
+local = "../other"
+"#;
+
+    #[test]
+    fn test_key_range_001() {
+        assert_eq!(
+            key_range(MANIFEST, &["dependencies"], "Missing"),
+            Range::new(Position::new(9, 0), Position::new(9, 34))
+        );
+        assert_eq!(
+            key_range(MANIFEST, &["dependencies"], "Other").start.line,
+            11
+        );
+        assert_eq!(key_range(MANIFEST, &["addresses"], "root").start.line, 5);
+    }
+
+    #[test]
+    fn test_manifest_diagnostics_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let other = dir.path().join("other");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(root.join("Move.toml"), MANIFEST).unwrap();
+        std::fs::write(
+            other.join("Move.toml"),
+            "[package]\nname = \"other\"\nversion = \"0.0.0\"\n\n[addresses]\nstd = \"0x2\"\n",
+        )
+        .unwrap();
+
+        let result = manifest_diagnostics(&root, &ProjectConfig::default());
+        let root_diags = &result[&Url::from_file_path(root.join("Move.toml")).unwrap()];
+        let mut lines: Vec<_> = root_diags.iter().map(|x| x.range.start.line).collect();
+        lines.sort();
+        // `root` unassigned, `std` conflicting, `Missing` not found.
+        assert_eq!(lines, vec![5, 6, 9]);
+        let other_diags = &result[&Url::from_file_path(other.join("Move.toml")).unwrap()];
+        assert_eq!(other_diags.len(), 1);
+        assert!(other_diags[0].message.contains("'std'"));
+    }
+
+    /// The named addresses are assigned the way the package is built.
+    #[test]
+    fn test_manifest_diagnostics_config_002() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("Move.toml"),
+            "[package]\nname = \"root\"\nversion = \"0.0.0\"\n\n[addresses]\nroot = \"_\"\nother = \"_\"\n\n[dev-addresses]\nroot = \"0x2\"\n",
+        )
+        .unwrap();
+        let unassigned = |config: &ProjectConfig| -> Vec<u32> {
+            let mut ret: Vec<u32> = manifest_diagnostics(&root, config)
+                .into_values()
+                .flatten()
+                .map(|x| x.range.start.line)
+                .collect();
+            ret.sort();
+            ret
+        };
+        assert_eq!(unassigned(&ProjectConfig::default()), vec![6]);
+        let release = ProjectConfig {
+            test_mode: false,
+            ..Default::default()
+        };
+        assert_eq!(unassigned(&release), vec![5, 6]);
+        let mut named = release.clone();
+        named
+            .named_addresses
+            .insert("other".to_string(), "0x3".to_string());
+        assert_eq!(unassigned(&named), vec![5]);
+    }
+
+    /// The columns are in UTF-16 code units.
+    #[test]
+    fn test_key_range_utf16_002() {
+        let manifest = "[package]\nname = \"日本\"\n\n[addresses]\n  \"日\" = \"0x1\"\n";
+        assert_eq!(
+            key_range(manifest, &["addresses"], "日"),
+            Range::new(Position::new(4, 2), Position::new(4, 13))
+        );
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{project::ProjectConfig, utils::call_operator_loc};
+    use move_compiler::{shared::PackagePaths, Flags};
+    use move_model::{
+        ast::{ExpData::*, Operation::*},
+        model::GlobalEnv,
+        run_model_builder_with_options_and_compilation_flags,
+    };
+    use std::collections::{BTreeMap, BTreeSet};
+
+    fn build_env(source: &str) -> GlobalEnv {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("m.move");
+        std::fs::write(&file, source).unwrap();
+        let config = ProjectConfig {
+            language_version: Some("2.0".to_string()),
+            ..Default::default()
+        };
+        let env = run_model_builder_with_options_and_compilation_flags(
+            vec![PackagePaths {
+                name: None,
+                paths: vec![file.to_string_lossy().to_string()],
+                named_address_map: BTreeMap::<String, _>::new(),
+            }],
+            vec![],
+            config.model_builder_options(),
+            Flags::model_compilation(),
+            &BTreeSet::new(),
+        )
+        .unwrap();
+        assert!(!env.has_errors());
+        env
+    }
+
+    /// The source of the operator token of each call to `name` in the functions of `env`.
+    fn call_operators(env: &GlobalEnv, name: &str) -> Vec<Option<String>> {
+        let mut ret = Vec::new();
+        for module_env in env.get_modules() {
+            for f in module_env.get_functions() {
+                if let Some(exp) = f.get_def().as_deref() {
+                    exp.visit_pre_order(&mut |e| {
+                        if let Call(node_id, MoveFunction(mid, fid), args) = e {
+                            let called = env.get_module(*mid);
+                            if called.get_function(*fid).get_name_str() == name {
+                                let call_loc = env.get_node_loc(*node_id);
+                                ret.push(
+                                    call_operator_loc(env, &call_loc, args, name)
+                                        .and_then(|x| env.get_source(&x).ok())
+                                        .map(|x| x.to_string()),
+                                );
+                            }
+                        }
+                        true
+                    });
+                }
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn test_receiver_call_name_001() {
+        let env = build_env(
+            r#"module 0x42::m {
+    struct S has drop { n: u64 }
+    fun len(self: &S, k: u64): u64 { self.n + k }
+    fun f(s: &S): u64 {
+        // the comments between the receiver and the name do not confuse the lookup.
+        s /* .len( */ .len(len(s, 1))
+    }
+}"#,
+        );
+        // the receiver call, then the call written with the function name in its argument.
+        assert_eq!(
+            call_operators(&env, "len"),
+            vec![Some("len".to_string()), None]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::movefmt::{
+        format_and_verify, item_lines, movefmt_config, text_edits, FmtConfig,
+    };
+    use lsp_types::{Position, Range};
+
+    const ORIGIN: &str = "module 0x1::m {
+    fun f() {
+      let x=1;
+    }
+
+    fun g() {
+      let y=2;
+    }
+}
+";
+
+    const FORMATTED: &str = "module 0x1::m {
+    fun f() {
+        let x = 1;
+    }
+
+    fun g() {
+        let y = 2;
+    }
+}
+";
+
+    #[test]
+    fn test_text_edits_001() {
+        let edits = text_edits(ORIGIN, FORMATTED, None);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(2, 0), Position::new(3, 0))
+        );
+        assert_eq!(edits[0].new_text, "        let x = 1;\n");
+        assert_eq!(
+            edits[1].range,
+            Range::new(Position::new(6, 0), Position::new(7, 0))
+        );
+    }
+
+    #[test]
+    fn test_range_text_edits_001() {
+        // a range inside of `g` formats `g` only.
+        let lines = item_lines(ORIGIN, Range::new(Position::new(6, 6), Position::new(6, 7)));
+        assert_eq!(lines, (5, 7));
+        let edits = text_edits(ORIGIN, FORMATTED, Some(lines));
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "        let y = 2;\n");
+    }
+
+    #[test]
+    fn test_movefmt_config_001() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sources")).unwrap();
+        let fpath = dir.path().join("sources").join("m.move");
+        std::fs::write(
+            dir.path().join("Move.toml"),
+            "[package]\nname = \"m\"\nversion = \"0.0.0\"\n\n[formatting]\nmax_width = 70\n",
+        )
+        .unwrap();
+        let client = FmtConfig {
+            enable: true,
+            max_width: 100,
+            indent_size: 2,
+        };
+
+        // `[formatting]` of Move.toml wins over the client, the rest comes from the client.
+        let cfg = movefmt_config(&fpath, &client);
+        assert_eq!(cfg.max_width(), 70);
+        assert_eq!(cfg.indent_size(), 2);
+
+        // movefmt.toml wins over `[formatting]`.
+        std::fs::write(
+            dir.path().join("movefmt.toml"),
+            "max_width = 80\nindent_size = 4\n",
+        )
+        .unwrap();
+        let cfg = movefmt_config(&fpath, &client);
+        assert_eq!(cfg.max_width(), 80);
+        assert_eq!(cfg.indent_size(), 4);
+    }
+
+    #[test]
+    fn test_format_and_verify_001() {
+        // a syntax error is reported with its location, and nothing is formatted.
+        let err = format_and_verify(
+            "module 0x1::m {\n    fun f() {\n      let x=;\n    }\n}\n",
+            commentfmt::Config::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.range.unwrap().start.line, 2);
+
+        let formatted = format_and_verify(ORIGIN, commentfmt::Config::default()).unwrap();
+        assert!(formatted.contains("let x = 1;"));
+    }
+}
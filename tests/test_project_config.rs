@@ -0,0 +1,73 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::project::{build_dir, build_dir_lock, ProjectConfig};
+    use move_core_types::account_address::AccountAddress;
+    use std::{path::Path, sync::Arc};
+
+    #[test]
+    fn test_project_config_001() {
+        let config = serde_json::from_value::<ProjectConfig>(serde_json::json!({
+            "dev_mode": true,
+            "named_addresses": { "admin": "0xcafe", "bad": "cafe" },
+        }))
+        .unwrap();
+        // settings not sent keep their defaults.
+        assert!(config.test_mode);
+        assert!(config.language_version.is_none());
+        let addresses = config.additional_named_addresses();
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(
+            addresses.get("admin"),
+            Some(&AccountAddress::from_hex_literal("0xcafe").unwrap())
+        );
+        assert_eq!(config.check().len(), 1);
+    }
+
+    #[test]
+    fn test_project_config_002() {
+        let config = ProjectConfig {
+            test_mode: false,
+            language_version: Some("2.0".to_string()),
+            compiler_version: Some("not a version".to_string()),
+            ..Default::default()
+        };
+        assert!(config.language_version().is_some());
+        assert!(config.compiler_version().is_none());
+        assert_eq!(config.check().len(), 1);
+        let build_config = config.build_config(Path::new("/work/app"));
+        assert!(!build_config.test_mode);
+        assert!(build_config.skip_fetch_latest_git_deps);
+        // the build directory outlives the config and is the same for every build of a package
+        // with the same settings.
+        let install_dir = build_config.install_dir.unwrap();
+        assert!(install_dir.ends_with(format!(
+            "aptos-move-analyzer/build/70467eff2e0a2364-{}",
+            config.cache_key()
+        )));
+        assert_eq!(
+            config.build_config(Path::new("/work/app")).install_dir,
+            Some(install_dir.clone())
+        );
+        let dev = ProjectConfig {
+            dev_mode: true,
+            ..config.clone()
+        };
+        assert_ne!(
+            dev.build_config(Path::new("/work/app")).install_dir,
+            Some(install_dir)
+        );
+    }
+
+    /// The builds writing to the same directory share its lock.
+    #[test]
+    fn test_build_dir_lock_001() {
+        let config = ProjectConfig::default();
+        let dir = build_dir(Path::new("/work/app"), &config);
+        assert!(Arc::ptr_eq(&build_dir_lock(&dir), &build_dir_lock(&dir)));
+        let other = build_dir(Path::new("/work/lib"), &config);
+        assert!(!Arc::ptr_eq(&build_dir_lock(&dir), &build_dir_lock(&other)));
+    }
+}
@@ -0,0 +1,39 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::selection_range::selection_ranges;
+    use lsp_types::{Position, Range};
+
+    const SOURCE: &str = "module 0x1::m {
+    fun f(x: u64): u64 {
+        let y = x + 1;
+        y * 2
+    }
+}
+";
+
+    #[test]
+    fn test_selection_ranges_001() {
+        // on `x` of `x + 1`.
+        let ranges = selection_ranges(SOURCE, &[Position::new(2, 17)]);
+        assert_eq!(ranges.len(), 1);
+        let mut chain = vec![];
+        let mut current = Some(&ranges[0]);
+        while let Some(x) = current {
+            chain.push(x.range);
+            current = x.parent.as_deref();
+        }
+        let r = |l1, c1, l2, c2| Range::new(Position::new(l1, c1), Position::new(l2, c2));
+        // `x`, then `x + 1`.
+        assert_eq!(chain[0], r(2, 16, 2, 17));
+        assert_eq!(chain[1], r(2, 16, 2, 21));
+        // the function and the module.
+        assert!(chain.contains(&r(1, 4, 4, 5)));
+        assert_eq!(chain.last(), Some(&r(0, 0, 5, 1)));
+        for x in chain.windows(2) {
+            assert!(x[1].start <= x[0].start && x[0].end <= x[1].end);
+        }
+    }
+}
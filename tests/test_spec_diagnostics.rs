@@ -0,0 +1,213 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+
+    use aptos_move_analyzer::{
+        dependency_cache::DependencyCache,
+        project::{Project, ProjectConfig},
+        spec_diagnostics::{
+            model_diagnostics, pragma_typos, spec_ranges, MOVE_SOURCE, SPEC_SOURCE,
+        },
+    };
+    use lsp_types::{DiagnosticSeverity, Position, Range, Url};
+    use std::{
+        cell::RefCell,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    const SOURCE: &str = "module 0x1::m {\n    struct S { x: u64 }\n    fun f(s: &S): u64 {\n        s.x\n    }\n    spec f {\n        include Missing;\n        pragma verfy = false;\n    }\n}\n";
+
+    /// A package of `SOURCE` and its spec file `spec_source`, built. The diagnostics of the model
+    /// are in `model_diags` of the project.
+    fn build_package(dir: &Path, spec_source: &str) -> (Project, PathBuf) {
+        let root = dir.join("app");
+        std::fs::create_dir_all(root.join("sources")).unwrap();
+        std::fs::write(
+            root.join("Move.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.0.0\"\n",
+        )
+        .unwrap();
+        let fpath = root.join("sources/m.move");
+        std::fs::write(&fpath, SOURCE).unwrap();
+        std::fs::write(root.join("sources/m.spec.move"), spec_source).unwrap();
+        (load(&root), fpath)
+    }
+
+    fn load(root: &Path) -> Project {
+        let project = Project::new_with_cache(
+            root,
+            |_| {},
+            Rc::new(RefCell::new(DependencyCache::new())),
+            ProjectConfig::default(),
+        )
+        .expect("the package loads");
+        assert!(project.load_ok());
+        project
+    }
+
+    #[test]
+    fn test_spec_ranges() {
+        let actual = spec_ranges(SOURCE);
+        assert_eq!(
+            actual,
+            vec![Range::new(Position::new(5, 4), Position::new(8, 5))]
+        );
+        let inline = "module 0x1::m {\n    fun f() {\n        spec { assert true; };\n    }\n}\n";
+        assert_eq!(
+            spec_ranges(inline),
+            vec![Range::new(Position::new(2, 8), Position::new(2, 29))]
+        );
+    }
+
+    #[test]
+    fn test_pragma_typos() {
+        let actual = pragma_typos(SOURCE);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(
+            actual[0].range,
+            Range::new(Position::new(7, 15), Position::new(7, 20))
+        );
+        assert_eq!(
+            actual[0].message,
+            "unknown pragma `verfy`, did you mean `verify`?"
+        );
+        assert!(pragma_typos(
+            "spec 0x1::m {\n    spec module {\n        pragma verify = true, aborts_if_is_strict;\n    }\n}\n"
+        )
+        .is_empty());
+    }
+
+    /// The errors of the model are read with their locations.
+    #[test]
+    fn test_env_diagnostics_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let (project, fpath) = build_package(dir.path(), "");
+        let (path, d) = project
+            .model_diags
+            .iter()
+            .find(|(_, x)| x.message.contains("Missing"))
+            .expect("the undeclared schema is reported");
+        assert_eq!(path, &fpath);
+        assert_eq!(
+            d.range,
+            Range::new(Position::new(6, 16), Position::new(6, 23))
+        );
+        assert_eq!(d.severity, Some(DiagnosticSeverity::ERROR));
+    }
+
+    /// The warnings are read too, their columns are in UTF-16 code units.
+    #[test]
+    fn test_env_diagnostics_002() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, fpath) = build_package(dir.path(), "");
+        std::fs::write(
+            &fpath,
+            "module 0x1::m {\n    fun f() {\n        let /* \u{1F600} */ y = 1;\n    }\n}\n",
+        )
+        .unwrap();
+        let project = load(dir.path().join("app").as_path());
+        let (path, d) = project
+            .model_diags
+            .iter()
+            .find(|(_, x)| x.range.start.line == 2)
+            .expect("the unused variable is reported");
+        assert_eq!(path, &fpath);
+        assert_eq!(d.severity, Some(DiagnosticSeverity::WARNING));
+        // the emoji is two UTF-16 code units.
+        assert_eq!(d.range.start, Position::new(2, 21));
+    }
+
+    /// The errors in spec blocks are told apart from those in the code, the pragma typos of the
+    /// spec file are reported too.
+    #[test]
+    fn test_model_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let (project, fpath) = build_package(
+            dir.path(),
+            "spec 0x1::m {\n    spec module {\n        pragma aborts_if_is_strct;\n    }\n}\n",
+        );
+        assert!(!project.err_diags.is_empty());
+        let mut actual = model_diagnostics(&project, fpath.as_path());
+
+        let ds = actual
+            .remove(&Url::from_file_path(&fpath).unwrap())
+            .expect("the errors of the Move file are reported");
+        let schema = ds
+            .iter()
+            .find(|x| x.message.contains("Missing"))
+            .expect("the undeclared schema is reported");
+        assert_eq!(schema.range.start.line, 6);
+        assert_eq!(schema.source.as_deref(), Some(SPEC_SOURCE));
+        // only the typo is reported on its line.
+        let typos: Vec<_> = ds.iter().filter(|x| x.range.start.line == 7).collect();
+        assert_eq!(typos.len(), 1);
+        assert_eq!(
+            typos[0].message,
+            "unknown pragma `verfy`, did you mean `verify`?"
+        );
+
+        let spec_ds = actual
+            .remove(&Url::from_file_path(fpath.with_extension("spec.move")).unwrap())
+            .expect("the pragma typo of the spec file is reported");
+        assert!(spec_ds.iter().any(|x| x.range.start.line == 2
+            && x.message
+                == "unknown pragma `aborts_if_is_strct`, did you mean `aborts_if_is_strict`?"));
+        assert!(actual.is_empty());
+    }
+
+    /// The errors in the code are in `move`.
+    #[test]
+    fn test_model_diagnostics_move_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, fpath) = build_package(dir.path(), "");
+        std::fs::write(&fpath, SOURCE.replace("s.x\n", "s.x + true\n")).unwrap();
+        let project = load(dir.path().join("app").as_path());
+        let ds = model_diagnostics(&project, fpath.as_path())
+            .remove(&Url::from_file_path(&fpath).unwrap())
+            .expect("the errors of the Move file are reported");
+        let d = ds
+            .iter()
+            .find(|x| x.range.start.line == 3)
+            .expect("the type error is reported");
+        assert_eq!(d.source.as_deref(), Some(MOVE_SOURCE));
+    }
+
+    /// The diagnostics of the dependencies are not reported.
+    #[test]
+    fn test_model_diagnostics_dependency() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, fpath) = build_package(dir.path(), "");
+        let dep = dir.path().join("dep");
+        std::fs::create_dir_all(dep.join("sources")).unwrap();
+        std::fs::write(
+            dep.join("Move.toml"),
+            "[package]\nname = \"dep\"\nversion = \"0.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dep.join("sources/d.move"),
+            "module 0x2::d {\n    public fun g() {\n        let y = 1;\n    }\n}\n",
+        )
+        .unwrap();
+        let root = dir.path().join("app");
+        std::fs::write(
+            root.join("Move.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.0.0\"\n\n[dependencies]\ndep = { local = \"../dep\" }\n",
+        )
+        .unwrap();
+        let project = load(root.as_path());
+        let dep_file = dep.join("sources/d.move");
+        assert!(project
+            .model_diags
+            .iter()
+            .any(|(path, _)| path.file_name() == dep_file.file_name()));
+        let actual = model_diagnostics(&project, fpath.as_path());
+        assert!(actual.contains_key(&Url::from_file_path(&fpath).unwrap()));
+        assert!(actual
+            .keys()
+            .all(|x| !x.path().ends_with("dep/sources/d.move")));
+    }
+}
@@ -6,7 +6,7 @@ mod tests {
 
     use aptos_move_analyzer::{
         context::*,
-        move_generate_spec_file::on_generate_spec_file,
+        move_generate_spec_file::{on_generate_spec_file, spec_file_update},
         multiproject::MultiProject,
         utils::{discover_manifest_and_kind, path_concat},
     };
@@ -80,7 +80,7 @@ mod tests {
 
         let actual_r = on_generate_spec_file(&context, &request, false);
         let ex = Some(
-            String::from("spec 0x0::LPResourceAccount {\n\n    spec module {\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n    }\n    spec CapabilityStorage{\n    }\n    spec initialize_lp_account(admin: &signer, lp_coin_metadata_serialized: vector<u8>, lp_coin_code: vector<u8>){\n        aborts_if signer::address_of(admin) != @SwapDeployer with ERR_FORBIDDEN;\n    }\n    spec retrieve_signer_cap(admin: &signer): SignerCapability{\n        aborts_if signer::address_of(admin) != @SwapDeployer with ERR_FORBIDDEN;\n    }\n}\n\n"));
+            String::from("spec 0x0::LPResourceAccount {\n\n    spec module {\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n        // invariant [suspendable] forall addr: address where exists<CapabilityStorage>(addr): ...;\n    }\n    spec CapabilityStorage{\n    }\n    spec initialize_lp_account(admin: &signer, lp_coin_metadata_serialized: vector<u8>, lp_coin_code: vector<u8>){\n        aborts_if signer::address_of(admin) != @SwapDeployer with ERR_FORBIDDEN;\n    }\n    spec retrieve_signer_cap(admin: &signer): SignerCapability{\n        aborts_if signer::address_of(admin) != @SwapDeployer with ERR_FORBIDDEN;\n    }\n}\n\n"));
         let expect_r = Response::new_ok(
             "generate_spec_file_request_001".to_string().into(),
             serde_json::to_value(ex).unwrap(),
@@ -129,7 +129,7 @@ mod tests {
 
         let actual_r = on_generate_spec_file(&context, &request, false);
         let ex = Some(
-            String::from("spec 0x0::M3 {\n\n    spec module {\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n    }\n    spec create_u128(a: u128, b: u128): u128{\n        aborts_if a + b > MAX_U128;\n    }\n    spec test_may_overflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 + 1;\n        aborts_if var_u64 + 1 > MAX_U64;\n        let var_local_u128 = var_u128 * 2;\n        aborts_if var_u128 * 2 > MAX_U128;\n        let var_local_u256 = var_u256 << 3;\n        aborts_if var_u256 << 3 > MAX_U256;\n        aborts_if (var_local_u64 as u128) + var_local_u128 > MAX_U128;\n        aborts_if (((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256 > MAX_U256;\n        aborts_if ((((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256) << 3 > MAX_U256;\n    }\n    spec test_may_underflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        aborts_if var_u64 - 1 < 0;\n        let var_local_u128 = (var_u128 * 2) - 1000;\n        aborts_if var_u128 * 2 > MAX_U128;\n        aborts_if (var_u128 * 2) - 1000 < 0;\n        aborts_if var_local_u128 <= 0 with 0;\n    }\n    spec test_may_div_zero(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        aborts_if 100 + var_u64 > MAX_U64;\n        aborts_if var_u64 == 0;\n        aborts_if var_u256 == 0;\n        aborts_if ((var_u128 as u256) / var_u256) == 0;\n    }\n}\n\n")
+            String::from("spec 0x0::M3 {\n\n    spec module {\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n    }\n    spec create_u128(a: u128, b: u128): u128{\n        aborts_if a + b > MAX_U128;\n        ensures result == a + b;\n    }\n    spec test_may_overflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 + 1;\n        aborts_if var_u64 + 1 > MAX_U64;\n        let var_local_u128 = var_u128 * 2;\n        aborts_if var_u128 * 2 > MAX_U128;\n        let var_local_u256 = var_u256 << 3;\n        aborts_if var_u256 << 3 > MAX_U256;\n        aborts_if (var_local_u64 as u128) + var_local_u128 > MAX_U128;\n        aborts_if (((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256 > MAX_U256;\n        aborts_if ((((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256) << 3 > MAX_U256;\n    }\n    spec test_may_underflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 - 1;\n        aborts_if var_u64 - 1 < 0;\n        let var_local_u128 = (var_u128 * 2) - 1000;\n        aborts_if var_u128 * 2 > MAX_U128;\n        aborts_if (var_u128 * 2) - 1000 < 0;\n        aborts_if var_local_u128 <= 0 with 0;\n        ensures result == var_local_u64;\n    }\n    spec test_may_div_zero(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = (100 + var_u64) / var_u64;\n        aborts_if 100 + var_u64 > MAX_U64;\n        aborts_if var_u64 == 0;\n        aborts_if var_u256 == 0;\n        aborts_if ((var_u128 as u256) / var_u256) == 0;\n        ensures result == var_local_u64;\n    }\n}\n\n")
         );
         let expect_r = Response::new_ok(
             "generate_spec_file_request_002".to_string().into(),
@@ -143,4 +143,86 @@ mod tests {
         log::info!("\n------------------------------\n");
         assert_eq!(actual_r.result, expect_r.result);
     }
+
+    /// Spec file of a package generated without a request, as by `generate-spec`.
+    #[test]
+    fn test_generate_spec_file_update_003() {
+        let (connection, _) = Connection::stdio();
+        let mut context = Context {
+            projects: MultiProject::new(),
+            connection,
+            diag_version: FileDiags::new(),
+        };
+
+        let fpath = path_concat(
+            std::env::current_dir().unwrap().as_path(),
+            PathBuf::from("tests/symbols/sources/SpecTest.move").as_path(),
+        );
+        prepare_project(&mut context, fpath.clone());
+
+        let project = context
+            .projects
+            .get_project(&fpath)
+            .expect("the project of the test file loads");
+
+        let update = spec_file_update(project, &fpath, false).unwrap();
+        assert!(update.path.ends_with("SpecTest.spec.move"));
+        assert_eq!(update.origin, None);
+        assert!(update.is_changed());
+
+        let request = Request {
+            id: "generate_spec_file_request_003".to_string().into(),
+            method: String::from("move/generate/spec/file"),
+            params: json!({
+                "fpath": fpath.to_string_lossy().to_string()
+            }),
+        };
+        let actual_r = on_generate_spec_file(&context, &request, false);
+        assert_eq!(actual_r.result, Some(json!(update.content)));
+    }
+
+    fn generate_spec_command(args: &[&str]) -> std::process::Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_aptos-move-analyzer"))
+            .arg("generate-spec")
+            .args(args)
+            .output()
+            .expect("run aptos-move-analyzer generate-spec")
+    }
+
+    /// `generate-spec --check` fails on a file without specs, `--dry-run` prints the spec file it
+    /// would write, and neither writes it.
+    #[test]
+    fn test_generate_spec_command_001() {
+        let file = "tests/symbols/sources/SpecTest.move";
+        let spec_file = PathBuf::from("tests/symbols/sources/SpecTest.spec.move");
+        assert!(!spec_file.exists());
+
+        let output = generate_spec_command(&["--check", file]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(1), "{}", stdout);
+        assert!(
+            stdout.contains("specs missing in") && stdout.contains("SpecTest.spec.move"),
+            "{}",
+            stdout
+        );
+
+        let output = generate_spec_command(&["--dry-run", file]);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(output.status.code(), Some(0), "{}", stdout);
+        assert!(stdout.contains("--- /dev/null"), "{}", stdout);
+        assert!(stdout.contains("SpecTest.spec.move"), "{}", stdout);
+        assert!(stdout.contains("+spec "), "{}", stdout);
+        assert!(!spec_file.exists());
+    }
+
+    /// A file outside of any package is an error.
+    #[test]
+    fn test_generate_spec_command_002() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("m.move");
+        std::fs::write(&file, "module 0x1::m {}").unwrap();
+        let output = generate_spec_command(&["--check", file.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(2));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("is not in a Move package"));
+    }
 }
@@ -0,0 +1,105 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+
+    use aptos_move_analyzer::move_generate_spec_merge::{
+        drop_inline_specs, find_spec_block, merge_spec_file, GeneratedModuleSpec, GeneratedSpec,
+    };
+
+    const EXISTING: &str = "spec 0x0::M3 {\n\n    spec module {\n        pragma verify = true;\n    }\n    spec foo(x: u64): u64{\n        // auto-generated begin\n        aborts_if x + 1 > MAX_U64;\n        // auto-generated end\n        ensures result > x;\n    }\n}\n";
+
+    fn generated(module_name: &str) -> Vec<GeneratedModuleSpec> {
+        vec![GeneratedModuleSpec {
+            addr_name: "0x0".to_string(),
+            address: "0x0".to_string(),
+            module_name: module_name.to_string(),
+            specs: vec![
+                GeneratedSpec {
+                    name: "module".to_string(),
+                    content: "    spec module {\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n    }\n".to_string(),
+                },
+                GeneratedSpec {
+                    name: "foo".to_string(),
+                    content: "    spec foo(x: u64): u64{\n        aborts_if x + 2 > MAX_U64;\n    }\n".to_string(),
+                },
+                GeneratedSpec {
+                    name: "bar".to_string(),
+                    content: "    spec bar(y: u64){\n        aborts_if y == 0;\n    }\n".to_string(),
+                },
+            ],
+        }]
+    }
+
+    /// Only the spec blocks missing from the spec file are added.
+    #[test]
+    fn test_merge_spec_001() {
+        let actual = merge_spec_file(EXISTING, "", &generated("M3"), false);
+        let ex = "spec 0x0::M3 {\n\n    spec module {\n        pragma verify = true;\n    }\n    spec foo(x: u64): u64{\n        // auto-generated begin\n        aborts_if x + 1 > MAX_U64;\n        // auto-generated end\n        ensures result > x;\n    }\n    spec bar(y: u64){\n        // auto-generated begin\n        aborts_if y == 0;\n        // auto-generated end\n    }\n}\n";
+        assert_eq!(actual, Some(ex.to_string()));
+    }
+
+    /// The marked clauses are generated again, the hand-written ones are kept.
+    #[test]
+    fn test_merge_spec_002() {
+        let actual = merge_spec_file(EXISTING, "", &generated("M3"), true);
+        let ex = "spec 0x0::M3 {\n\n    spec module {\n        pragma verify = true;\n    }\n    spec foo(x: u64): u64{\n        // auto-generated begin\n        aborts_if x + 2 > MAX_U64;\n        // auto-generated end\n        ensures result > x;\n    }\n    spec bar(y: u64){\n        // auto-generated begin\n        aborts_if y == 0;\n        // auto-generated end\n    }\n}\n";
+        assert_eq!(actual, Some(ex.to_string()));
+    }
+
+    /// A module without specs yet gets a new block at the end of the spec file.
+    #[test]
+    fn test_merge_spec_003() {
+        let actual = merge_spec_file(EXISTING, "", &generated("M9"), false);
+        let ex = format!("{}spec 0x0::M9 {{\n\n    spec module {{\n        // auto-generated begin\n        pragma verify = true;\n        pragma aborts_if_is_strict;\n        // auto-generated end\n    }}\n    spec foo(x: u64): u64{{\n        // auto-generated begin\n        aborts_if x + 2 > MAX_U64;\n        // auto-generated end\n    }}\n    spec bar(y: u64){{\n        // auto-generated begin\n        aborts_if y == 0;\n        // auto-generated end\n    }}\n}}\n\n", EXISTING);
+        assert_eq!(actual, Some(ex));
+    }
+
+    #[test]
+    fn test_merge_spec_004() {
+        let (start, end) = find_spec_block(EXISTING, "0x0", "0x0", "M3", "foo").unwrap();
+        assert!(EXISTING[start..end].starts_with("spec foo(x: u64): u64{"));
+        assert!(EXISTING[start..end].ends_with('}'));
+        assert_eq!(find_spec_block(EXISTING, "0x0", "0x0", "M3", "bar"), None);
+        assert!(merge_spec_file("spec 0x0::M3 {", "", &generated("M3"), false).is_none());
+    }
+
+    /// The spec blocks the Move file has inline are not added to the spec file.
+    #[test]
+    fn test_merge_spec_005() {
+        let source = "module 0x0::M3 {\n    fun bar(y: u64) {}\n    spec bar {\n        aborts_if y == 0;\n    }\n}\n";
+        assert_eq!(
+            merge_spec_file(EXISTING, source, &generated("M3"), false),
+            Some(EXISTING.to_string())
+        );
+        let actual = drop_inline_specs(source, &generated("M3"));
+        let names: Vec<&str> = actual[0].specs.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["module", "foo"]);
+    }
+
+    /// A module of the same name at another address is another module.
+    #[test]
+    fn test_merge_spec_006() {
+        let existing = EXISTING.replace("0x0::M3", "0x1::M3");
+        let actual = merge_spec_file(existing.as_str(), "", &generated("M3"), false).unwrap();
+        assert!(actual.starts_with(existing.as_str()));
+        assert!(actual.ends_with("}\n\n") && actual.contains("spec 0x0::M3 {"));
+        assert_eq!(
+            find_spec_block(existing.as_str(), "0x0", "0x0", "M3", "foo"),
+            None
+        );
+        assert!(find_spec_block(EXISTING, "SwapDeployer", "0x00", "M3", "foo").is_some());
+    }
+
+    #[test]
+    fn test_generated_spec_is_empty() {
+        let specs = &generated("M3")[0].specs;
+        assert!(!specs[0].is_empty() && !specs[1].is_empty());
+        let empty = GeneratedSpec {
+            name: "baz".to_string(),
+            content: "    spec baz(){\n    }\n".to_string(),
+        };
+        assert!(empty.is_empty());
+    }
+}
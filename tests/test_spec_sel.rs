@@ -85,6 +85,7 @@ mod tests {
             line: 11,
             col: 4,
             content: String::from("    spec CapabilityStorage{\n    }\n"),
+            edit: None,
         });
         let expect_r = Response::new_ok(
             "generate_spec_request_001".to_string().into(),
@@ -139,7 +140,8 @@ mod tests {
             Resp {
                 line: 15,
                 col: 5,
-                content: String::from("    spec test_may_overflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 + 1;\n        aborts_if var_u64 + 1 > MAX_U64;\n        let var_local_u128 = var_u128 * 2;\n        aborts_if var_u128 * 2 > MAX_U128;\n        let var_local_u256 = var_u256 << 3;\n        aborts_if var_u256 << 3 > MAX_U256;\n        aborts_if (var_local_u64 as u128) + var_local_u128 > MAX_U128;\n        aborts_if (((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256 > MAX_U256;\n        aborts_if ((((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256) << 3 > MAX_U256;\n    }\n")
+                content: String::from("    spec test_may_overflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 + 1;\n        aborts_if var_u64 + 1 > MAX_U64;\n        let var_local_u128 = var_u128 * 2;\n        aborts_if var_u128 * 2 > MAX_U128;\n        let var_local_u256 = var_u256 << 3;\n        aborts_if var_u256 << 3 > MAX_U256;\n        aborts_if (var_local_u64 as u128) + var_local_u128 > MAX_U128;\n        aborts_if (((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256 > MAX_U256;\n        aborts_if ((((var_local_u64 as u128) + var_local_u128) as u256) * var_local_u256) << 3 > MAX_U256;\n    }\n"),
+                edit: None,
             }
         );
         let expect_r = Response::new_ok(
@@ -195,7 +197,8 @@ mod tests {
             Resp {
                 line: 25,
                 col: 5,
-                content: String::from("    spec test_may_underflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        aborts_if var_u64 - 1 < 0;\n        let var_local_u128 = (var_u128 * 2) - 1000;\n        aborts_if var_u128 * 2 > MAX_U128;\n        aborts_if (var_u128 * 2) - 1000 < 0;\n        aborts_if var_local_u128 <= 0 with 0;\n    }\n")
+                content: String::from("    spec test_may_underflow(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = var_u64 - 1;\n        aborts_if var_u64 - 1 < 0;\n        let var_local_u128 = (var_u128 * 2) - 1000;\n        aborts_if var_u128 * 2 > MAX_U128;\n        aborts_if (var_u128 * 2) - 1000 < 0;\n        aborts_if var_local_u128 <= 0 with 0;\n        ensures result == var_local_u64;\n    }\n"),
+                edit: None,
             }
         );
         let expect_r = Response::new_ok(
@@ -251,7 +254,8 @@ mod tests {
              Resp {
                  line: 34,
                  col: 5,
-                 content: String::from("    spec test_may_div_zero(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        aborts_if 100 + var_u64 > MAX_U64;\n        aborts_if var_u64 == 0;\n        aborts_if var_u256 == 0;\n        aborts_if ((var_u128 as u256) / var_u256) == 0;\n    }\n")
+                 content: String::from("    spec test_may_div_zero(var_u64: u64, var_u128: u128, var_u256: u256): u64{\n        let var_local_u64 = (100 + var_u64) / var_u64;\n        aborts_if 100 + var_u64 > MAX_U64;\n        aborts_if var_u64 == 0;\n        aborts_if var_u256 == 0;\n        aborts_if ((var_u128 as u256) / var_u256) == 0;\n        ensures result == var_local_u64;\n    }\n"),
+                 edit: None,
              }
          );
         let expect_r = Response::new_ok(
@@ -266,4 +270,219 @@ mod tests {
         log::info!("\n------------------------------\n");
         assert_eq!(actual_r.result, expect_r.result);
     }
+
+    fn generate_spec_sel_resource(line: u32, col: u32) -> Response {
+        generate_spec_sel_in("tests/symbols/sources/SpecResource.move", line, col)
+    }
+
+    fn generate_spec_sel_in(file: &str, line: u32, col: u32) -> Response {
+        let (connection, _) = Connection::stdio();
+        let mut context = Context {
+            projects: MultiProject::new(),
+            connection,
+            diag_version: FileDiags::new(),
+        };
+
+        let fpath = path_concat(
+            std::env::current_dir().unwrap().as_path(),
+            PathBuf::from(file).as_path(),
+        );
+        prepare_project(&mut context, fpath.clone());
+        context
+            .projects
+            .get_project(&fpath)
+            .expect("the project of the test file loads");
+
+        let request = Request {
+            id: "generate_spec_request_resource".to_string().into(),
+            method: String::from("move/generate/spec/sel"),
+            params: json!({
+                "col": col,
+                "line": line,
+                "fpath": fpath.to_string_lossy().to_string()
+            }),
+        };
+        let response = on_generate_spec_sel(&mut context, &request);
+        assert!(response.error.is_none(), "{:?}", response.error);
+        response
+    }
+
+    /// Generate Spec for a Function which publishes a resource.
+    #[test]
+    fn test_generate_spec_request_005() {
+        let actual = generate_spec_sel_resource(9, 5);
+        let ex = Resp {
+            line: 10,
+            col: 5,
+            content: String::from("    spec init(account: &signer){\n        aborts_if exists<Counter>(signer::address_of(account));\n        modifies global<Counter>(signer::address_of(account));\n        ensures exists<Counter>(signer::address_of(account));\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Generate Spec for a Function calling a helper which borrows a resource.
+    #[test]
+    fn test_generate_spec_request_006() {
+        let actual = generate_spec_sel_resource(22, 5);
+        let ex = Resp {
+            line: 23,
+            col: 5,
+            content: String::from("    spec reset(account: &signer){\n        aborts_if !exists<Counter>(signer::address_of(account));\n        modifies global<Counter>(signer::address_of(account));\n        ensures global<Counter>(signer::address_of(account)).value == 1;\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Generate Spec for a Function calling a helper which may abort.
+    #[test]
+    fn test_generate_spec_request_007() {
+        let actual = generate_spec_sel_resource(37, 5);
+        let ex = Resp {
+            line: 38,
+            col: 5,
+            content: String::from("    spec add_twice(x: u64): u64{\n        aborts_if x * 2 > MAX_U64;\n        aborts_if (x * 2) + 1 > MAX_U64;\n        aborts_if ((x * 2) + 1) >= 100 with 1;\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Generate Spec for a Struct whose fields are checked by its constructor.
+    #[test]
+    fn test_generate_spec_request_008() {
+        let actual = generate_spec_sel_resource(41, 5);
+        let ex = Resp {
+            line: 45,
+            col: 4,
+            content: String::from("    spec Inventory{\n        invariant len(items) <= MAX_ITEMS;\n        invariant 0x1::option::is_some(owner);\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Spec types named through a module alias.
+    #[test]
+    fn test_generate_spec_request_009() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 11, 5);
+        let ex = Resp {
+            line: 12,
+            col: 5,
+            content: String::from("    spec module_alias(_x: opt::Option<u64>){\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Spec types named through a member alias.
+    #[test]
+    fn test_generate_spec_request_010() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 14, 5);
+        let ex = Resp {
+            line: 15,
+            col: 5,
+            content: String::from("    spec member_alias(_x: Text){\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Spec types named through a `Self` member.
+    #[test]
+    fn test_generate_spec_request_011() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 17, 5);
+        let ex = Resp {
+            line: 18,
+            col: 5,
+            content: String::from("    spec self_member(_x: ascii::String){\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Nested generic spec types, with a struct the module does not use.
+    #[test]
+    fn test_generate_spec_request_012() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 20, 5);
+        let ex = Resp {
+            line: 21,
+            col: 5,
+            content: String::from("    spec nested(_x: vector<opt::Option<Wrapper<0x1::fixed_point32::FixedPoint32>>>){\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Spec types behind references.
+    #[test]
+    fn test_generate_spec_request_013() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 23, 5);
+        let ex = Resp {
+            line: 24,
+            col: 5,
+            content: String::from(
+                "    spec references(_x: &mut vector<Text>, _y: &Inventory){\n    }\n",
+            ),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    /// Function types of the parameters of an inline function.
+    #[test]
+    fn test_generate_spec_request_014() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecTypes.move", 26, 5);
+        let ex = Resp {
+            line: 27,
+            col: 5,
+            content: String::from("    spec fun_param(_f: |u64, u64| u64, _g: |u64|){\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
+
+    fn spec_content(response: Response) -> String {
+        response.result.expect("a spec is generated")["content"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Only the last write of a field is in the spec.
+    #[test]
+    fn test_generate_spec_request_015() {
+        let content = spec_content(generate_spec_sel_resource(52, 5));
+        assert!(content.contains("ensures global<Counter>(addr).value == x;\n"));
+        assert!(!content.contains("ensures global<Counter>(addr).value == 1;"));
+    }
+
+    /// A write of a value computed in the body has no spec.
+    #[test]
+    fn test_generate_spec_request_016() {
+        let content = spec_content(generate_spec_sel_resource(58, 5));
+        assert!(content.contains("modifies global<Counter>(addr);"));
+        assert!(!content.contains("ensures global<Counter>(addr).value"));
+    }
+
+    /// The bound of a field the module changes after the pack is left for the user to check.
+    #[test]
+    fn test_generate_spec_request_017() {
+        let content = spec_content(generate_spec_sel_resource(64, 5));
+        assert_eq!(
+            content,
+            "    spec Bag{\n        // invariant len(items) <= MAX_ITEMS;\n    }\n"
+        );
+    }
+
+    /// Generate Spec for a Function which publishes a resource, in a module which imports
+    /// `signer::address_of` itself.
+    #[test]
+    fn test_generate_spec_request_018() {
+        let actual = generate_spec_sel_in("tests/symbols/sources/SpecSignerAlias.move", 9, 5);
+        let ex = Resp {
+            line: 10,
+            col: 5,
+            content: String::from("    spec init(account: &signer){\n        aborts_if exists<Flag>(address_of(account));\n        modifies global<Flag>(address_of(account));\n        ensures exists<Flag>(address_of(account));\n    }\n"),
+            edit: None,
+        };
+        assert_eq!(actual.result, Some(serde_json::to_value(ex).unwrap()));
+    }
 }
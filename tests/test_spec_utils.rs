@@ -0,0 +1,44 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::move_generate_spec_utils::{param_arg_str, substitute_params};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_substitute_params_001() {
+        let mut params = HashMap::new();
+        params.insert(
+            "addr".to_string(),
+            "signer::address_of(account)".to_string(),
+        );
+        params.insert("signer".to_string(), "s".to_string());
+        params.insert("value".to_string(), "(x + 1)".to_string());
+        assert_eq!(
+            substitute_params("addr", &params),
+            "signer::address_of(account)"
+        );
+        // a module, a field and an address are not parameters.
+        assert_eq!(
+            substitute_params("signer::address_of(signer) == @addr", &params),
+            "signer::address_of(s) == @addr"
+        );
+        assert_eq!(
+            substitute_params("counter.value + value * 0x1a", &params),
+            "counter.value + (x + 1) * 0x1a"
+        );
+    }
+
+    #[test]
+    fn test_param_arg_str_001() {
+        assert_eq!(param_arg_str("account"), "account");
+        assert_eq!(param_arg_str("&mut counter"), "counter");
+        assert_eq!(
+            param_arg_str("signer::address_of(account)"),
+            "signer::address_of(account)"
+        );
+        assert_eq!(param_arg_str("a + b"), "(a + b)");
+        assert_eq!(param_arg_str("v[i + 1]"), "v[i + 1]");
+    }
+}
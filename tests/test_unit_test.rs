@@ -0,0 +1,100 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::move_unit_test::{
+        is_same_module, parse_abort_codes, parse_test_output, AbortCodes,
+    };
+    use std::path::PathBuf;
+
+    const OUTPUT: &str = "INCLUDING DEPENDENCY MoveStdlib
+BUILDING Symbols
+Running Move unit tests
+[ PASS    ] 0xcafe::M4::test_ok
+[ FAIL    ] 0xcafe::M4::test_abort
+[ FAIL    ] 0xcafe::M4::test_no_abort
+Test failures:
+
+Failures in 0xcafe::M4:
+
+┌── test_abort ──────
+│ error[E11001]: test failure
+│    ┌─ /tmp/Symbols/sources/M4.move:12:9
+│    │
+│ 10 │     fun test_abort() {
+│    │         ---------- In this function in 0xcafe::M4
+│ 11 │         let x = 1;
+│ 12 │         assert!(x == 2, E_NOT_OWNER);
+│    │         ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ Test was not expected to error, but it aborted with code 1 originating in the module 000000000000000000000000000000000000000000000000000000000000cafe::M4 rooted here
+│
+│
+└──────────────────
+
+
+┌── test_no_abort ──────
+│ Test did not error as expected
+└──────────────────
+
+Test result: FAILED. Total tests: 3; passed: 1; failed: 2
+";
+
+    #[test]
+    fn test_parse_test_output_001() {
+        let report = parse_test_output(OUTPUT);
+        assert_eq!(report.passed, vec!["0xcafe::M4::test_ok".to_string()]);
+        assert_eq!(report.failures.len(), 2);
+
+        let abort = &report.failures[0];
+        assert_eq!(abort.module_name, "0xcafe::M4");
+        assert_eq!(abort.test_name, "test_abort");
+        assert_eq!(
+            abort.abort_location,
+            Some((PathBuf::from("/tmp/Symbols/sources/M4.move"), 11, 8))
+        );
+        assert!(abort
+            .message
+            .starts_with("Test was not expected to error, but it aborted with code 1"));
+
+        let no_abort = &report.failures[1];
+        assert_eq!(no_abort.test_name, "test_no_abort");
+        assert_eq!(no_abort.abort_location, None);
+        assert_eq!(no_abort.message, "Test did not error as expected");
+    }
+
+    #[test]
+    fn test_parse_abort_codes_001() {
+        let codes = parse_abort_codes(
+            "Test did not abort with expected code. Expected test to abort with code 2, but \
+             instead it aborted with code 393217 originating in the module \
+             000000000000000000000000000000000000000000000000000000000000cafe::M4 rooted here",
+        );
+        assert_eq!(
+            codes,
+            AbortCodes {
+                expected: Some(2),
+                actual: Some(393217),
+                module_name: Some(
+                    "000000000000000000000000000000000000000000000000000000000000cafe::M4"
+                        .to_string()
+                ),
+            }
+        );
+
+        let codes = parse_abort_codes("Test was expected to fail but it succeeded");
+        assert_eq!(codes, AbortCodes::default());
+    }
+
+    /// Modules of the same name at other addresses are other modules.
+    #[test]
+    fn test_is_same_module_001() {
+        assert!(is_same_module(
+            "0xcafe::M4",
+            "000000000000000000000000000000000000000000000000000000000000cafe::M4"
+        ));
+        assert!(is_same_module("0xCAFE::M4", "0xcafe::M4"));
+        assert!(!is_same_module("0xcafe::M4", "0x1::M4"));
+        assert!(!is_same_module("0xcafe::M4", "0xcafe::M5"));
+        assert!(!is_same_module("M4", "0xcafe::M4"));
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(test)]
+mod tests {
+    use aptos_move_analyzer::{
+        context::{DiagKind, FileDiags},
+        index_cache::PackageIndex,
+        indexer::{spawn_indexer, IndexEvent, IndexJob},
+        multiproject::MultiProject,
+        project::ProjectConfig,
+        utils::discover_manifests,
+    };
+    use lsp_server::Connection;
+    use lsp_types::{Diagnostic, FileChangeType};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_discover_manifests_001() {
+        let dir = tempfile::tempdir().unwrap();
+        for p in ["a", "nested/b", "a/build/a", ".git/c"] {
+            std::fs::create_dir_all(dir.path().join(p)).unwrap();
+            std::fs::write(dir.path().join(p).join("Move.toml"), "").unwrap();
+        }
+        let mut manifests = discover_manifests(dir.path());
+        manifests.sort();
+        assert_eq!(
+            manifests,
+            vec![dir.path().join("a"), dir.path().join("nested/b")]
+        );
+
+        let mut projects = MultiProject::new();
+        projects.insert_indexes(
+            manifests
+                .iter()
+                .map(|x| PackageIndex {
+                    package: x.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        projects.remove_workspace_folder(&dir.path().join("nested"));
+        assert_eq!(projects.indexes.len(), 1);
+    }
+
+    /// The indexer reports the packages of a folder before indexing them.
+    #[test]
+    fn test_indexer_discovered_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let (sender, events) = crossbeam::channel::unbounded();
+        let indexer = spawn_indexer(sender);
+        indexer
+            .send(IndexJob {
+                folder: dir.path().to_path_buf(),
+                config: ProjectConfig::default(),
+            })
+            .unwrap();
+        match events.recv().unwrap() {
+            IndexEvent::Discovered(manifests) => assert!(manifests.is_empty()),
+            IndexEvent::Indexed(mani, _) => panic!("{:?} indexed before discovered", mani),
+            IndexEvent::Unindexed(mani) => panic!("{:?} queued before discovered", mani),
+        }
+    }
+
+    #[test]
+    fn test_watched_files_001() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("a");
+        std::fs::create_dir_all(package.join("sources")).unwrap();
+        std::fs::write(package.join("Move.toml"), "").unwrap();
+        let (connection, _client) = Connection::memory();
+        let mut projects = MultiProject::new();
+        // a new package is queued for indexing, a source of a package not loaded is ignored.
+        let reloaded = projects.on_watched_files_changed(
+            &connection,
+            &[
+                (package.join("Move.toml"), FileChangeType::CREATED),
+                (package.join("sources/m.move"), FileChangeType::DELETED),
+            ],
+        );
+        assert!(reloaded.is_empty());
+        assert_eq!(projects.pending_manifests.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_file_diags_001() {
+        let mut diags = FileDiags::new();
+        let mani = std::path::PathBuf::from("/a");
+        let url = url::Url::parse("file:///a/sources/m.move").unwrap();
+        for kind in [
+            DiagKind::Compiler,
+            DiagKind::Model,
+            DiagKind::Test,
+            DiagKind::Coverage,
+        ] {
+            diags.update(
+                kind,
+                &mani,
+                HashMap::from([(url.clone(), vec![Diagnostic::default(); 2])]),
+            );
+        }
+        assert!(diags.remove_file(&url));
+        assert!(diags.merged(&url).is_empty());
+        assert!(!diags.remove_file(&url));
+        // nothing left to clear when the kinds report on the file again.
+        let changed = diags.update(DiagKind::Test, &mani, HashMap::new());
+        assert!(changed.is_empty());
+    }
+
+    /// Test failures are published along with the compiler errors of the file, not instead.
+    #[test]
+    fn test_merge_file_diags_001() {
+        let mut diags = FileDiags::new();
+        let mani = std::path::PathBuf::from("/a");
+        let url = url::Url::parse("file:///a/sources/m.move").unwrap();
+        let diag = |message: &str| Diagnostic {
+            message: message.to_string(),
+            ..Default::default()
+        };
+        diags.update(
+            DiagKind::Compiler,
+            &mani,
+            HashMap::from([(url.clone(), vec![diag("compiler")])]),
+        );
+        let published = diags.update(
+            DiagKind::Test,
+            &mani,
+            HashMap::from([(url.clone(), vec![diag("test")])]),
+        );
+        assert_eq!(
+            published.get(&url),
+            Some(&vec![diag("compiler"), diag("test")])
+        );
+
+        // the compiler errors are fixed, the test failure stays.
+        let published = diags.update(DiagKind::Compiler, &mani, HashMap::new());
+        assert_eq!(published.get(&url), Some(&vec![diag("test")]));
+    }
+}